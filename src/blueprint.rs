@@ -1,5 +1,13 @@
-pub(crate) mod count_entities;
-pub(crate) mod upgrade_quality;
+//! Encoding and decoding blueprint strings, plus the tools that operate on blueprints.
+
+pub mod count_entities;
+pub mod inventory;
+pub mod loadout;
+pub mod migrate;
+pub mod model;
+pub mod quality_ladder;
+pub mod raw_materials;
+pub mod upgrade_quality;
 
 use std::{
     borrow::Borrow,
@@ -9,28 +17,55 @@ use std::{
 };
 
 use base64::{Engine, prelude::BASE64_STANDARD};
-use flate2::Compression;
+use flate2::{Compression, FlushDecompress};
 use serde_json::json;
 
-pub fn blueprint_to_json(blueprint_str: &str) -> String {
-    let (header, b64_body) = blueprint_str
+use crate::error::{Error, Result};
+use quality_ladder::QualityLadder;
+
+/// Decodes a blueprint string (`0` followed by base64-encoded zlib-compressed json) into its json.
+pub fn blueprint_to_json(blueprint_str: &str) -> Result<String> {
+    let b64_body = blueprint_str
         .trim()
-        .split_at_checked(1)
-        .unwrap_or_else(|| panic!("blueprint should start with '0'"));
-    assert_eq!(header, "0", "blueprint should start with '0'");
-    let compressed = BASE64_STANDARD
-        .decode(b64_body)
-        .unwrap_or_else(|e| panic!("bad base64: {e}"));
-    let mut json: String;
-    {
-        let mut cursor = &compressed[..];
-        let mut decode = flate2::read::ZlibDecoder::new(&mut cursor);
-        json = String::new();
-        decode.read_to_string(&mut json).expect("valid zlib data");
+        .strip_prefix('0')
+        .ok_or(Error::BadHeader)?;
+    let compressed = BASE64_STANDARD.decode(b64_body).map_err(Error::BadBase64)?;
+    let json = inflate(&compressed).map_err(Error::TruncatedZlib)?;
+    String::from_utf8(json)
+        .map_err(|e| Error::TruncatedZlib(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+/// Like `flate2::read::ZlibDecoder`, but errors if the stream ends early instead of returning
+/// whatever was decoded so far.
+fn inflate(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decompress = flate2::Decompress::new(true);
+    let mut out = Vec::with_capacity(compressed.len() * 8);
+    loop {
+        if out.len() == out.capacity() {
+            out.reserve(out.capacity().max(1024));
+        }
+        let consumed = usize::try_from(decompress.total_in()).expect("input fits in memory");
+        let produced_before = decompress.total_out();
+        let status = decompress
+            .decompress_vec(&compressed[consumed..], &mut out, FlushDecompress::Finish)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        match status {
+            flate2::Status::StreamEnd => return Ok(out),
+            flate2::Status::Ok | flate2::Status::BufError => {
+                let made_progress = decompress.total_in() as usize != consumed
+                    || decompress.total_out() != produced_before;
+                if !made_progress && out.len() < out.capacity() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "zlib stream ended early",
+                    ));
+                }
+            }
+        }
     }
-    json
 }
 
+/// Encodes json into a blueprint string that can be pasted into the game.
 pub fn json_to_blueprint(value: serde_json::Value) -> String {
     let mut result = "0".to_owned();
     let json = value.to_string();
@@ -45,7 +80,10 @@ pub fn json_to_blueprint(value: serde_json::Value) -> String {
     result
 }
 
-pub(crate) enum BlueprintType<T: Borrow<serde_json::Value>> {
+/// The contents of a blueprint string, or of a blueprint book entry, by kind.
+///
+/// Works on untyped json; see [`model::BlueprintEntry`] for the typed equivalent.
+pub enum BlueprintType<T: Borrow<serde_json::Value>> {
     Blueprint(T),
     BlueprintBook(T),
     UpgradePlanner(T),
@@ -53,52 +91,60 @@ pub(crate) enum BlueprintType<T: Borrow<serde_json::Value>> {
 }
 
 impl BlueprintType<&serde_json::Value> {
-    #[allow(
-        dead_code,
-        reason = "used in tests but not logically a test-only function"
-    )]
-    pub fn new(json: &serde_json::Value) -> BlueprintType<&serde_json::Value> {
+    pub fn new(json: &serde_json::Value) -> Result<BlueprintType<&serde_json::Value>> {
         let json = match json {
             serde_json::Value::Object(json) => json,
-            _ => panic!("blueprint entry should be an object, got {json:?}"),
+            _ => {
+                return Err(Error::InvalidBlueprint(format!(
+                    "blueprint entry should be an object, got {json}"
+                )));
+            }
         };
 
         if json.contains_key("blueprint") {
-            BlueprintType::Blueprint(&json["blueprint"])
+            Ok(BlueprintType::Blueprint(&json["blueprint"]))
         } else if json.contains_key("blueprint_book") {
-            BlueprintType::BlueprintBook(&json["blueprint_book"])
+            Ok(BlueprintType::BlueprintBook(&json["blueprint_book"]))
         } else if json.contains_key("upgrade_planner") {
-            BlueprintType::UpgradePlanner(&json["upgrade_planner"])
+            Ok(BlueprintType::UpgradePlanner(&json["upgrade_planner"]))
         } else if json.contains_key("deconstruction_planner") {
-            BlueprintType::DeconstructionPlanner(&json["deconstruction_planner"])
+            Ok(BlueprintType::DeconstructionPlanner(
+                &json["deconstruction_planner"],
+            ))
         } else {
-            panic!("blueprint has unknown type: {:?}", json.keys());
+            Err(Error::UnknownBlueprintType(json.keys().cloned().collect()))
         }
     }
 }
 
 impl BlueprintType<&mut serde_json::Value> {
-    pub fn new(json: &mut serde_json::Value) -> BlueprintType<&mut serde_json::Value> {
+    pub fn new(json: &mut serde_json::Value) -> Result<BlueprintType<&mut serde_json::Value>> {
         let json = match json {
             serde_json::Value::Object(json) => json,
-            _ => panic!("blueprint entry should be an object, got {json:?}"),
+            _ => {
+                return Err(Error::InvalidBlueprint(format!(
+                    "blueprint entry should be an object, got {json}"
+                )));
+            }
         };
 
         if json.contains_key("blueprint") {
-            BlueprintType::Blueprint(&mut json["blueprint"])
+            Ok(BlueprintType::Blueprint(&mut json["blueprint"]))
         } else if json.contains_key("blueprint_book") {
-            BlueprintType::BlueprintBook(&mut json["blueprint_book"])
+            Ok(BlueprintType::BlueprintBook(&mut json["blueprint_book"]))
         } else if json.contains_key("upgrade_planner") {
-            BlueprintType::UpgradePlanner(&mut json["upgrade_planner"])
+            Ok(BlueprintType::UpgradePlanner(&mut json["upgrade_planner"]))
         } else if json.contains_key("deconstruction_planner") {
-            BlueprintType::DeconstructionPlanner(&mut json["deconstruction_planner"])
+            Ok(BlueprintType::DeconstructionPlanner(
+                &mut json["deconstruction_planner"],
+            ))
         } else {
-            panic!("blueprint has unknown type: {:?}", json.keys());
+            Err(Error::UnknownBlueprintType(json.keys().cloned().collect()))
         }
     }
 
     #[inline]
-    pub(crate) fn as_ref(&self) -> BlueprintType<&serde_json::Value> {
+    pub fn as_ref(&self) -> BlueprintType<&serde_json::Value> {
         self.as_ref_inner()
     }
 
@@ -111,22 +157,27 @@ impl BlueprintType<&mut serde_json::Value> {
         }
     }
 
-    pub(crate) fn set_tag_in_description(&mut self, tag: &str, value: &str) {
+    /// Sets a `tag: value` line in the description, replacing the line if the tag is already present.
+    pub fn set_tag_in_description(&mut self, tag: &str, value: &str) -> Result<()> {
         let description = self
             .any_mut()
             .as_object_mut()
-            .expect("should be a json object")
+            .ok_or_else(|| Error::InvalidBlueprint("blueprint should be a json object".into()))?
             .entry("description")
             .or_insert_with(|| json!(""));
         let serde_json::Value::String(description) = description else {
-            panic!("expected description to be a string")
+            return Err(Error::InvalidBlueprint(
+                "expected description to be a string".into(),
+            ));
         };
         *description = set_tag_in_string(mem::take(description), tag, value);
+        Ok(())
     }
 }
 
 impl<T: Borrow<serde_json::Value>> BlueprintType<T> {
-    pub(crate) fn any(&self) -> &serde_json::Value {
+    /// The json of the blueprint itself, whatever its kind.
+    pub fn any(&self) -> &serde_json::Value {
         match self {
             BlueprintType::Blueprint(value) => value,
             BlueprintType::BlueprintBook(value) => value,
@@ -147,9 +198,19 @@ impl<T: Borrow<serde_json::Value>> BlueprintType<T> {
         }
     }
 
-    pub(crate) fn label(&self) -> Option<&str> {
+    pub fn label(&self) -> Option<&str> {
         self.any().get("label")?.as_str()
     }
+
+    /// The human-readable name of this kind of blueprint.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            BlueprintType::Blueprint(_) => "blueprint",
+            BlueprintType::BlueprintBook(_) => "blueprint book",
+            BlueprintType::UpgradePlanner(_) => "upgrade planner",
+            BlueprintType::DeconstructionPlanner(_) => "deconstruction planner",
+        }
+    }
 }
 
 fn set_tag_in_string(description: String, tag: &str, value: &str) -> String {
@@ -176,56 +237,176 @@ fn set_tag_in_string(description: String, tag: &str, value: &str) -> String {
     format!("{prefix}{tag}: {value}{suffix}")
 }
 
-pub fn make_constant_combinator_json(signals: Vec<((String, Quality), i64)>) -> serde_json::Value {
-    const COMBINATOR: &str = "0eNqNkNEOgjAMRf+lz8MIEYH9ijFmw6pNRkdGMRKyf3fDF5+Mj23uPbe3K1g34xiIBfQK1HueQJ9WmOjOxuWdLCOCBhIcQAGbIU9ZJ4al6P1giY34AFEB8RVfoMt4VoAsJIQf3DYsF54HiyEJfoIUjH5KXs85P/GK+ljvagUL6OpY7uqUdKWA/UdyUJkiwbuLxYd5UkIk342cYPirjQTD0+iDFBad5CK9n/NHyu9KMbfajPrrawqeKWU7pGrLQ9NVTVt1zb7tYnwDHB11ag==";
-    let json = blueprint_to_json(COMBINATOR);
-    let mut value = serde_json::from_str::<serde_json::Value>(&json)
-        .expect("constant combinator should be a valid blueprint");
-    let new_filters = signals
+/// Lays out signals as 2.0 logistic sections, as used by constant combinators and requests.
+///
+/// Returns groups of at most `max_signals` signals, each a list of sections. With `by_quality`,
+/// each quality gets its own section, in the order of `ladder`, worst first.
+pub(crate) fn logistic_sections(
+    mut signals: Vec<((String, Quality), i64)>,
+    by_quality: bool,
+    ladder: &QualityLadder,
+    max_signals: usize,
+) -> Vec<Vec<serde_json::Value>> {
+    if by_quality {
+        signals.sort_by(|((_, a), _), ((_, b), _)| {
+            ladder
+                .rank(a)
+                .cmp(&ladder.rank(b))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+    }
+    let max_signals = max_signals.max(1);
+    let mut groups = Vec::<Vec<Vec<serde_json::Value>>>::new();
+    let mut previous_quality = None;
+    for (i, ((name, quality), count)) in signals.into_iter().enumerate() {
+        let quality = quality.0.unwrap_or_else(|| "normal".to_owned());
+        if i % max_signals == 0 {
+            groups.push(Vec::new());
+        }
+        let sections = groups.last_mut().expect("pushed above");
+        if sections.is_empty() || (by_quality && previous_quality.as_ref() != Some(&quality)) {
+            sections.push(Vec::new());
+        }
+        let filters = sections.last_mut().expect("pushed above");
+        filters.push(json!({
+            "index": filters.len() + 1,
+            "name": name,
+            "quality": quality,
+            "comparator": "=",
+            "count": count,
+        }));
+        previous_quality = Some(quality);
+    }
+    groups
+        .into_iter()
+        .map(|sections| {
+            sections
+                .into_iter()
+                .enumerate()
+                .map(|(i, filters)| json!({"index": i + 1, "filters": filters}))
+                .collect()
+        })
+        .collect()
+}
+
+/// Options for [`make_constant_combinator_json`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CombinatorOptions {
+    /// The most signals to put in one combinator before starting another.
+    pub max_signals: usize,
+    /// Puts each quality in its own section, worst first.
+    pub by_quality: bool,
+    /// The order of the qualities for [`CombinatorOptions::by_quality`].
+    pub ladder: QualityLadder,
+}
+
+impl Default for CombinatorOptions {
+    fn default() -> Self {
+        CombinatorOptions {
+            max_signals: 1000,
+            by_quality: false,
+            ladder: QualityLadder::default(),
+        }
+    }
+}
+
+/// Makes a blueprint of constant combinators outputting the given signals, in 2.0 logistic
+/// sections.
+///
+/// If there are more than [`CombinatorOptions::max_signals`] signals, they're split across a row
+/// of combinators wired together with red wire, so the whole row outputs the total.
+pub fn make_constant_combinator_json(
+    signals: Vec<((String, Quality), i64)>,
+    options: &CombinatorOptions,
+) -> serde_json::Value {
+    let mut combinators = logistic_sections(
+        signals,
+        options.by_quality,
+        &options.ladder,
+        options.max_signals,
+    );
+    if combinators.is_empty() {
+        combinators.push(Vec::new());
+    }
+
+    let entities = combinators
         .into_iter()
         .enumerate()
-        .map(|(idx, ((item_name, quality), count))| {
-            let mut signal = json!({
-                "type": "item",
-                "name": item_name,
-            });
-            if let Some(quality) = quality.0 {
-                signal
-                    .as_object_mut()
-                    .unwrap()
-                    .insert("quality".to_owned(), json!(quality));
-            }
-
-            let filter = json!({
-                "index": idx + 1,
-                "count": count,
-                "signal": signal,
-            });
-            filter
+        .map(|(i, sections)| {
+            json!({
+                "entity_number": i + 1,
+                "name": "constant-combinator",
+                "position": {"x": i as f64 + 0.5, "y": 0.5},
+                "control_behavior": {"sections": {"sections": sections}},
+            })
         })
         .collect::<Vec<_>>();
-    let entities = value
-        .get_mut("blueprint")
-        .unwrap()
-        .get_mut("entities")
-        .unwrap()
-        .as_array_mut()
-        .unwrap();
-    let &mut [ref mut const_combinator] = &mut entities[..] else {
-        panic!("should only be one entity in constant combinator blueprint");
-    };
-    let filters = const_combinator
-        .get_mut("control_behavior")
-        .unwrap()
-        .get_mut("filters")
-        .unwrap()
-        .as_array_mut()
-        .unwrap();
-    *filters = new_filters;
-    value
+    // The red connector of each combinator to the next.
+    let wires = (1..entities.len())
+        .map(|i| json!([i, 1, i + 1, 1]))
+        .collect::<Vec<_>>();
+    let mut blueprint = json!({
+        "item": "blueprint",
+        "icons": [{"index": 1, "signal": {"name": "constant-combinator"}}],
+        "entities": entities,
+        "version": migrate::MIGRATED_VERSION.to_packed(),
+    });
+    if !wires.is_empty() {
+        blueprint["wires"] = json!(wires);
+    }
+    json!({ "blueprint": blueprint })
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A Factorio version, as packed into the `version` field of blueprints.
+///
+/// The field is a u64 holding four u16s: major, minor, patch and build, most significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build: u16,
+}
+
+impl Version {
+    pub fn from_packed(packed: u64) -> Version {
+        Version {
+            major: (packed >> 48) as u16,
+            minor: (packed >> 32) as u16,
+            patch: (packed >> 16) as u16,
+            build: packed as u16,
+        }
+    }
+
+    pub fn to_packed(self) -> u64 {
+        (u64::from(self.major) << 48)
+            | (u64::from(self.minor) << 32)
+            | (u64::from(self.patch) << 16)
+            | u64::from(self.build)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Version {
+            major,
+            minor,
+            patch,
+            build,
+        } = self;
+        write!(f, "{major}.{minor}.{patch}.{build}")
+    }
+}
+
+/// The base game's qualities, worst first.
+pub const QUALITY_TIERS: [&str; 5] = ["normal", "uncommon", "rare", "epic", "legendary"];
+
+/// The quality of an item. `None` means the quality wasn't specified, which the game treats as normal.
+///
+/// Qualities compare by name, so that they can key maps; use a [`QualityLadder`] to order them by
+/// tier.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Quality(pub Option<String>);
 
 impl Debug for Quality {
@@ -235,6 +416,7 @@ impl Debug for Quality {
 }
 
 impl Quality {
+    /// Formats as ` (quality)`, or nothing if the quality isn't specified.
     pub fn fmt_suffix(&self) -> impl Display {
         struct FromFn<T>(T);
         impl<T: Fn(&mut std::fmt::Formatter<'_>) -> std::fmt::Result> Display for FromFn<T> {
@@ -255,7 +437,97 @@ impl Quality {
 
 #[cfg(test)]
 mod tests {
-    use crate::blueprint::set_tag_in_string;
+    use serde_json::json;
+
+    use crate::blueprint::{
+        CombinatorOptions, Quality, Version, blueprint_to_json, make_constant_combinator_json,
+        set_tag_in_string,
+    };
+    use crate::error::Error;
+
+    #[test]
+    fn test_version() {
+        let version = Version::from_packed(562949957353472);
+        assert_eq!(
+            version,
+            Version {
+                major: 2,
+                minor: 0,
+                patch: 60,
+                build: 0
+            }
+        );
+        assert_eq!(version.to_string(), "2.0.60.0");
+        assert_eq!(version.to_packed(), 562949957353472);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(blueprint_to_json(""), Err(Error::BadHeader)));
+        assert!(matches!(blueprint_to_json("1eNo="), Err(Error::BadHeader)));
+        assert!(matches!(
+            blueprint_to_json("0not base64!"),
+            Err(Error::BadBase64(_))
+        ));
+        // "0eAGrrgUAAXUA+Q==" is `{}`, cut short
+        assert!(matches!(
+            blueprint_to_json("0eAGrrg=="),
+            Err(Error::TruncatedZlib(_))
+        ));
+        assert_eq!(blueprint_to_json("0eAGrrgUAAXUA+Q==").unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_make_constant_combinator() {
+        let signal = |name: &str, quality: Option<&str>, count| {
+            (
+                (name.to_owned(), Quality(quality.map(str::to_owned))),
+                count,
+            )
+        };
+        let signals = vec![
+            signal("rail", None, 250),
+            signal("small-lamp", Some("rare"), 19),
+            signal("splitter", None, 1),
+        ];
+        let options = CombinatorOptions {
+            max_signals: 2,
+            by_quality: true,
+            ..Default::default()
+        };
+        let json = make_constant_combinator_json(signals, &options);
+        let blueprint = &json["blueprint"];
+        let sections = |entity: usize| {
+            blueprint["entities"][entity]["control_behavior"]["sections"]["sections"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|section| {
+                    section["filters"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|filter| {
+                            format!(
+                                "{} {} {}",
+                                filter["count"], filter["name"], filter["quality"]
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sections(0),
+            [vec![r#"250 "rail" "normal""#, r#"1 "splitter" "normal""#]]
+        );
+        assert_eq!(sections(1), [vec![r#"19 "small-lamp" "rare""#]]);
+        assert_eq!(blueprint["wires"], json!([[1, 1, 2, 1]]));
+        assert_eq!(
+            blueprint["entities"][1]["position"],
+            json!({"x": 1.5, "y": 0.5})
+        );
+    }
 
     #[test]
     fn test_set_tag() {
@@ -1,75 +1,388 @@
-use std::collections::BTreeMap;
+//! Counting the items needed to build a blueprint.
 
-use serde::Deserialize;
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use serde::Serialize;
 
 use super::Quality;
+use super::model::{Blueprint, BlueprintEntry, BlueprintKind};
+use super::quality_ladder::QualityLadder;
+use crate::{
+    error::Result,
+    prototypes::Prototypes,
+    table::{TableFormat, format_table},
+};
+
+/// What [`count`] counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Include {
+    /// The entities and tiles themselves.
+    #[default]
+    Entities,
+    /// The entities and tiles, plus the modules, fuel, ammo etc. they request.
+    All,
+    /// Only the items requested by entities.
+    Requests,
+}
+
+/// Options for [`count`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct CountOptions {
+    pub include: Include,
+    /// Where to look up the item that places each entity and tile. Entities and tiles missing
+    /// from it, or all of them if there is none, go by a built-in table for the base game.
+    pub prototypes: Option<Prototypes>,
+}
+
+/// How many of each item, by quality.
+pub type Counts = BTreeMap<(String, Quality), u64>;
+
+fn add(result: &mut Counts, name: &str, quality: &Option<String>, count: u64) {
+    let entry = result
+        .entry((name.to_owned(), Quality(quality.clone())))
+        .or_insert(0);
+    *entry = entry.checked_add(count).unwrap();
+}
+
+fn count_entry(entry: &BlueprintEntry, options: &CountOptions, result: &mut Counts) {
+    match &entry.kind {
+        BlueprintKind::Blueprint(blueprint) => count_blueprint(blueprint, options, result),
+        BlueprintKind::BlueprintBook(book) => {
+            for bp in book.blueprints() {
+                count_entry(bp, options, result);
+            }
+        }
+        BlueprintKind::UpgradePlanner(_) | BlueprintKind::DeconstructionPlanner(_) => {}
+    }
+}
+
+fn count_blueprint(blueprint: &Blueprint, options: &CountOptions, result: &mut Counts) {
+    if options.include != Include::Requests {
+        let prototypes = options.prototypes.as_ref();
+        for entity in blueprint.entities() {
+            let (name, count) = match prototypes.and_then(|p| p.entity_item(&entity.name)) {
+                Some(item) => (item.item.as_str(), item.count),
+                None => entity_item(&entity.name),
+            };
+            add(result, name, &entity.quality, count);
+        }
+        for tile in blueprint.tiles() {
+            let (name, count) = match prototypes.and_then(|p| p.tile_item(&tile.name)) {
+                Some(item) => (item.item.as_str(), item.count),
+                None => (tile_item(&tile.name), 1),
+            };
+            add(result, name, &tile.quality, count);
+        }
+    }
+    if options.include != Include::Entities {
+        for entity in blueprint.entities() {
+            for (name, quality, count) in entity.items.iter().flat_map(|items| items.counts()) {
+                add(result, name, &quality.map(str::to_owned), count);
+            }
+        }
+    }
+}
+
+/// The item that places an entity, and how many of it, for the base game.
+fn entity_item(entity: &str) -> (&str, u64) {
+    match entity {
+        "curved-rail-a" => ("rail", 3),
+        "curved-rail-b" => ("rail", 3),
+        "half-diagonal-rail" => ("rail", 2),
+        "straight-rail" => ("rail", 1),
+        // 1.1 rails, as migrated to 2.0
+        "legacy-curved-rail" => ("rail", 4),
+        "legacy-straight-rail" => ("rail", 1),
+        name => (name, 1),
+    }
+}
+
+/// The item that places a tile, for the base game tiles whose item has a different name.
+fn tile_item(tile: &str) -> &str {
+    match tile {
+        "stone-path" => "stone-brick",
+        "hazard-concrete-left" | "hazard-concrete-right" => "hazard-concrete",
+        "refined-hazard-concrete-left" | "refined-hazard-concrete-right" => {
+            "refined-hazard-concrete"
+        }
+        tile => tile,
+    }
+}
 
-#[derive(Deserialize)]
-struct Top {
-    #[serde(rename = "blueprint")]
-    blueprint: Option<Blueprint>,
-    #[serde(rename = "blueprint_book")]
-    book: Option<BlueprintBook>,
+/// Counts the items needed to build the blueprint (or every blueprint in the book) in `json`.
+///
+/// Tiles are counted as the item that places them, e.g. `stone-path` as `stone-brick`. Items
+/// requested by entities are counted by their own quality, depending on [`CountOptions::include`].
+pub fn count(json: &str, options: &CountOptions) -> Result<Counts> {
+    let entry = serde_json::from_str::<BlueprintEntry>(json)?;
+    let mut result = BTreeMap::new();
+    count_entry(&entry, options, &mut result);
+    Ok(result)
 }
 
-impl Top {
-    fn count(self) -> BTreeMap<(String, Quality), u64> {
-        let mut result = if let Some(blueprint) = self.blueprint {
-            blueprint.count()
-        } else {
-            BTreeMap::new()
+/// One item, and how many of it are needed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ItemCount {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    pub count: u64,
+}
+
+impl Display for ItemCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quality = Quality(self.quality.clone());
+        write!(f, "{} {}{}", self.count, self.name, quality.fmt_suffix())
+    }
+}
+
+/// How to order counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortBy {
+    /// Most needed first.
+    #[default]
+    Count,
+    /// Alphabetically by item name.
+    Name,
+    /// Worst quality first, see [`QualityLadder::rank`].
+    Quality,
+}
+
+/// Sorts `counts`, breaking ties by name, then quality, then count.
+pub fn sort_counts(counts: &mut [ItemCount], sort: SortBy, ladder: &QualityLadder) {
+    counts.sort_by_cached_key(|count| {
+        let quality = Quality(count.quality.clone());
+        let tier = ladder.rank(&quality);
+        let primary = match sort {
+            SortBy::Count => (Reverse(count.count), 0),
+            SortBy::Name => (Reverse(0), 0),
+            SortBy::Quality => (Reverse(0), tier),
         };
-        if let Some(BlueprintBook { blueprints }) = self.book {
-            for bp in blueprints {
-                let counts = bp.count();
-                for (key, count) in counts {
-                    *result.entry(key).or_insert(0) += count
+        (
+            primary,
+            count.name.clone(),
+            tier,
+            quality,
+            Reverse(count.count),
+        )
+    });
+}
+
+/// Lists `counts`, most needed first.
+pub fn item_counts(counts: Counts) -> Vec<ItemCount> {
+    let mut counts = counts
+        .into_iter()
+        .map(|((name, quality), count)| ItemCount {
+            name,
+            quality: quality.0,
+            count,
+        })
+        .collect::<Vec<_>>();
+    sort_counts(&mut counts, SortBy::Count, &QualityLadder::default());
+    counts
+}
+
+impl ItemCount {
+    pub(crate) fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.quality.clone().unwrap_or_else(|| "normal".to_owned()),
+            self.count.to_string(),
+        ]
+    }
+}
+
+/// Formats `counts` as a table with name, quality and count columns.
+pub fn counts_table(counts: &[ItemCount], format: TableFormat) -> String {
+    let rows = counts.iter().map(ItemCount::row).collect::<Vec<_>>();
+    format_table(format, &["name", "quality", "count"], &rows)
+}
+
+/// The counts for a blueprint, or for a book along with each of its entries.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Breakdown {
+    /// E.g. `"blueprint book"`.
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// For a book, the total of all of its entries.
+    pub counts: Vec<ItemCount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<Breakdown>,
+}
+
+fn breakdown_entry(entry: &BlueprintEntry, options: &CountOptions) -> (Breakdown, Counts) {
+    let mut counts = Counts::new();
+    let mut entries = Vec::new();
+    match &entry.kind {
+        BlueprintKind::Blueprint(blueprint) => count_blueprint(blueprint, options, &mut counts),
+        BlueprintKind::BlueprintBook(book) => {
+            for bp in book.blueprints() {
+                let (breakdown, entry_counts) = breakdown_entry(bp, options);
+                for (key, count) in entry_counts {
+                    let total = counts.entry(key).or_insert(0);
+                    *total = total.checked_add(count).unwrap();
                 }
+                entries.push(breakdown);
             }
         }
-        result
+        BlueprintKind::UpgradePlanner(_) | BlueprintKind::DeconstructionPlanner(_) => {}
     }
+    let breakdown = Breakdown {
+        kind: entry.kind.kind_name(),
+        index: entry.index,
+        label: entry.kind.label().map(str::to_owned),
+        counts: item_counts(counts.clone()),
+        entries,
+    };
+    (breakdown, counts)
 }
 
-#[derive(Deserialize)]
-struct Blueprint {
-    entities: Vec<Entity>,
+/// Counts each blueprint in `json` separately, like [`count`], with totals for each book.
+pub fn breakdown(json: &str, options: &CountOptions) -> Result<Breakdown> {
+    let entry = serde_json::from_str::<BlueprintEntry>(json)?;
+    Ok(breakdown_entry(&entry, options).0)
 }
 
-#[derive(Deserialize)]
-struct BlueprintBook {
-    blueprints: Vec<Top>,
-}
+impl Breakdown {
+    /// Sorts the counts of this entry and every entry in it.
+    pub fn sort(&mut self, sort: SortBy, ladder: &QualityLadder) {
+        sort_counts(&mut self.counts, sort, ladder);
+        for entry in &mut self.entries {
+            entry.sort(sort, ladder);
+        }
+    }
 
-impl Blueprint {
-    fn count(self) -> BTreeMap<(String, Quality), u64> {
-        let mut result = BTreeMap::<(String, Quality), u64>::new();
-        for entity in self.entities {
-            let name = entity.name;
-            let (name, count) = match name.as_ref() {
-                "curved-rail-a" => ("rail".to_string(), 3),
-                "curved-rail-b" => ("rail".to_string(), 3),
-                "half-diagonal-rail" => ("rail".to_string(), 2),
-                "straight-rail" => ("rail".to_string(), 1),
-                _ => (name, 1),
-            };
-            let quality = Quality(entity.quality);
-            let entry = result.entry((name, quality)).or_insert(0);
-            *entry = entry.checked_add(count).unwrap();
+    /// Formats the counts of every blueprint as a table, with the path to the blueprint through
+    /// its books in the first column, e.g. `Base / [3] Rails / [0] Curves`.
+    pub fn to_table(&self, format: TableFormat) -> String {
+        let mut rows = Vec::new();
+        self.rows(&mut Vec::new(), &mut rows);
+        format_table(format, &["blueprint", "name", "quality", "count"], &rows)
+    }
+
+    fn rows(&self, path: &mut Vec<String>, rows: &mut Vec<Vec<String>>) {
+        let name = match (self.index, &self.label) {
+            (Some(index), Some(label)) => format!("[{index}] {label}"),
+            (Some(index), None) => format!("[{index}]"),
+            (None, Some(label)) => label.clone(),
+            (None, None) => self.kind.to_owned(),
+        };
+        path.push(name);
+        if self.kind != "blueprint book" {
+            for count in &self.counts {
+                rows.push([vec![path.join(" / ")], count.row()].concat());
+            }
+        }
+        for entry in &self.entries {
+            entry.rows(path, rows);
+        }
+        path.pop();
+    }
+
+    fn fmt_tree(&self, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let index = self
+            .index
+            .map(|index| format!("[{index}] "))
+            .unwrap_or_default();
+        let label = self.label.as_deref().unwrap_or("(no label)");
+        writeln!(f, "{indent}{index}{}: {label}", self.kind)?;
+        if self.kind != "blueprint book" {
+            for count in &self.counts {
+                writeln!(f, "{indent}  {count}")?;
+            }
+            return Ok(());
+        }
+        for entry in &self.entries {
+            entry.fmt_tree(depth + 1, f)?;
         }
-        result
+        writeln!(
+            f,
+            "{indent}  {}:",
+            if depth == 0 { "total" } else { "subtotal" }
+        )?;
+        for count in &self.counts {
+            writeln!(f, "{indent}    {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A tree of books and blueprints, each with their counts, and a subtotal after each book.
+impl Display for Breakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(0, f)
+    }
+}
+
+/// Wire connections in a blueprint, by type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct WireCounts {
+    pub red: u64,
+    pub green: u64,
+    pub copper: u64,
+}
+
+impl WireCounts {
+    /// The counts as the items used to make the connections: `red-wire`, `green-wire` and
+    /// `copper-cable`. Wire types without connections are left out.
+    pub fn item_counts(&self) -> Vec<ItemCount> {
+        [
+            ("red-wire", self.red),
+            ("green-wire", self.green),
+            ("copper-cable", self.copper),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| ItemCount {
+            name: name.to_owned(),
+            quality: None,
+            count,
+        })
+        .collect()
     }
 }
 
-#[derive(Deserialize)]
-struct Entity {
-    name: String,
-    quality: Option<String>,
+fn count_entry_wires(entry: &BlueprintEntry, result: &mut WireCounts) {
+    match &entry.kind {
+        BlueprintKind::Blueprint(blueprint) => {
+            for wire in blueprint.wires() {
+                // Both ends of a wire are the same type, so the first connector is enough.
+                let count = match wire.1 {
+                    1 | 3 => &mut result.red,
+                    2 | 4 => &mut result.green,
+                    5 | 6 => &mut result.copper,
+                    _ => continue,
+                };
+                *count += 1;
+            }
+        }
+        BlueprintKind::BlueprintBook(book) => {
+            for bp in book.blueprints() {
+                count_entry_wires(bp, result);
+            }
+        }
+        BlueprintKind::UpgradePlanner(_) | BlueprintKind::DeconstructionPlanner(_) => {}
+    }
 }
 
-pub(crate) fn count(json: &str) -> BTreeMap<(String, Quality), u64> {
-    let top = serde_json::from_str::<Top>(json).unwrap();
-    top.count()
+/// Counts the wire connections in the blueprint (or every blueprint in the book) in `json`.
+///
+/// The type of each wire comes from its circuit connector ids: red and green for circuit wires
+/// (including combinator outputs), copper for power poles and power switches.
+pub fn count_wires(json: &str) -> Result<WireCounts> {
+    let entry = serde_json::from_str::<BlueprintEntry>(json)?;
+    let mut result = WireCounts::default();
+    count_entry_wires(&entry, &mut result);
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -78,8 +391,8 @@ mod tests {
     #[test]
     fn test_count() {
         let bp = r"0eNrlXUuP47gR/isLHwN7IL6lQfaU3HPJJVgMGu62ukcZt+3I8sxOFvPfQ1KyRLcpu6oktZDZBJhe2tYnsh5UsYr89MficXvKD2WxqxYf/1gUT/vdcfHxtz8Wx+Jlt966z3br13zxcVGui+3ix3JR7Db574uP7Men5SLfVUVV5PUVvvH9YXd6fcxL+4Pl+cpjZa99+VytPMRycdgf7VX7nQO3SCuWpPbH3+1/ZZn48WN5BcVbqKdT+TXfeKDV+g4Ut53dFGX+VP8gjQALUh9ZrI+SApVmMShFgjIxKB2V3GMUymQd1F3JGVIfVayPKQkqaigZZriqg7oYLpMRZJYsQ0dYPX1eF7tV4yQxeG0+tDfgH9TlLaJ3IDlMGjVGhvEYk3RYl4LgMWgxCnRUAhgf0qYHOoqMcSmtMMgagywwyBgX0wyDjPE4lWGQMwwyRoM8wSBjNMgxfqcwGuQcg4zRIMc8uCRGgxzjgxKlQYwPSpQGMT4oURrE+KBEaRDjgwKlQYwPCowGBcYHBUaDAuODAqNBgfFBgdGgwPggx2hQYHyQozSI8UGO0iDGBzlKgwYRcPC+gEPHkEmRp4nG7IIUelqs+xGXTBDQrA86JgBJCjlNdKUhOQkruiKQAhlsm6wNto16G2yz2B0kZWFk3iBHtaUw2rojB42Ug1tpnBHFhzgmaeVmosstSXOg+Po5o49Vp/GxqoSSODDJfS0rkufo+HKfByPfrMt4eJjeHy3mgZRkt/skEZJrsazkYlikfIaOzjIq8Ij94/6wL6v4eki2wrLKLPP/nPJj9fBcbKu8PLqfHWvt1pmrLqUVvWfnMY/Fyyrf2kvL4ml12G/z24sxe/MYXorEa4OHHrwMJhMXJo8lE52QbC2eo2JIz0+61IrLstxPVmnSs0lHvUwLypyiNaCXkiTTeC8VxX/f9pIlMegBDyXN41OXJj2UdPShpFOSGONY2QDT5BDTNAlp5NFHqGH0sEknV2GTit2CU+ImzQCCEJS46S1y1GKNRMqFJTfkksXuoEg2F9eipliEij7GjaH0qweLFOyp6JRvSKslZQBJ5JS0WnoLHUVmJGFGBZBy+sSiQM+8lPScUoDnVEoqdal47UdRqglKQQxBU6oJClCpSQ2lmgBCTinVBBByRqkmQJCzhFJNACEzSjUBhMwp1QQQsqBUE0DIklJNACErSjUBhKwp1QQQsqFUE0DIKaWaAELOKNUECLJ9PlDKCTBoRqknwKA5paAAgxaUigIMWlJKCjBoRakpwKA1pagAgzaUqgIMOqWUFWDQGaWuAIJmCWX1rSCrb8ZoUWg0+mKMlBhRIg5Gyj/2gckBATJo5c0YKS+p4htumKavvRVs7c2YoSy+FYPIovMztwOwWu+q1dP+9bHYrat9T0a6S+so5gdgr6zK/fbhMf+8/lrYyy5yij35xeWizUBefHruzbZ4fl7lvx+2tgNf86Ptyn9O663tvP1yty9fvXhtVw/r0nf14+JX/8HJbWhUPz65/GV0xBl6xFK9x4iLcr+z5pMfK9xYpZ8uGrjOrw/roxPc6lDuvxabvBwM3Xn54+n5eQTAwNPrnPQImJ1rH/bfLN7xW1E9fcYaT4fXeffaanS4OHvNMtjnBDVLkb6HWZ5LU4hx8rhFHu0P1y/5yo7tCw5QxO2wraC8Frti97LalMV2i0NO4wZ5OL0e/r1+QnaTqaglPp62X1bF7piXVsoDutfZ4nZvR/t5bT/eEIH1LVNkeFNk72GKZwMiuF4aN8nXfFOcXq9KcVRgcbPAN9wsj6dHqw4v+jGmyOf1sRrXMMlg/cbIKeUBBSkPMC7o9QEFqg8wTqrt9cSanBS4yiwOppHV6C7RlsQBg9Wg1fd2tV2/Hu5FkDJtZottPUXU50de/L+P/t+1q0VH79eFruuyqD6/5pXtPiJ4fbPdI41PWh32g/16U7Qz2HNRHquH7rhM9f3g+vK1KKuTt5+zMPwvVvnaxiH2jnbiszAP55nV9Wq52B/ysvbrj4u/2Ev3p+pwQoP3eFAG1UsQ5w7QS7BbFK6XMMT+k+gl3Pt6Wy9BoDdEL5yglzDG/LPoRYD1wkbRi6Tohf359KKw26f07QcWauty+2zueZwK0k4WaeJgKaVMLiFlchZsWO6Jfm8/sE18Jw8Ldiu/jStjBbAOUF8l7eIG/FSUT6eiesh368dtvll8rMpTvmw/bo06ZtN9SaV6odIYcPImQv2rtcQpslTuAO3pmHc78NxAoiLtX/rcFmm9UQC602/STByrQ/vY6DglXtTkeTbYXA4wUDaLgcaTTPesdLnY7l+K48XU7jvUXiQJtk1PeMENXGIMnE1k4CNm9mJjVJgxJhONkZ4NvTU0jfGoZBaPuhj4PI6Elj3cfwxCATKdRQEXxYJ5FICuV8AVkCKcW2YTOffQ8kmvf2eY0aUTjW5QtalvaAoTrYaKe0fPeavWeZyHYlxg/1GYCFeaqR6O45RKe22NYwapJxrkgPJy78AwEXWovfd8/sd1O1MkQDc0uEtJjFL0LEoJbHEeRSCdAS58Rcm109e0wbFIcDInvHNfMkcZzJQlJ5qyhpe5+Y3JK8X4iZzFT6ICGO4xguAxZF3AfSfDKETMtJgJt3LMown8dhKwCi5O9951ezFZwE/eL3PD34PTxgDz4vMEK+dtR/NYFmrXE9yoUOEvnyrGJ+3oumVPAjOsqfJ69D11t4aGScvKqdKyI+zuuzVGhZkO5sldXih3nikBbV/waQGTPJbzlGPiJjiPJujuAFcJJuoXk2UzSRuOe/08peyyoa/INCaKFTMlFr2E5zFjuHbBZhtQaYAXwSK9vwg2mOSkmCpvN2yTbp9bGExQJqZKvI4/LExGUsyT/Lrs2HAvTAleiDcquDdi8o9inqTwzNKfSPCYiFaoeSLa7gDDPKJHnqCAC19j5tOpEqbDT530Tqyo0FBNtdolnn+5NTBMGljMkwa+Vus8zkMzL7gPYbYbCD5xRoV2yKzPzFJMZlVMlVkd5bhb7xAxCVbBZ82ojFV7TAekVCYoPwbsYgAVzFNCidrgPJoguwNcIYKwNGX3l6YhFRv8jAo9q5EqCrmeNJB3ktBOYcQPTgQMbWj2EAmi12MpiQ5R9ugxo5D1SQBZH8sSCsOehDDsMRRBW0uxJyEUOiiGtpZjDwYtKCR7MGhJYdmDQSsKzR4MWlN49mDQhkK0B4NOKUx7MOiMQrUHgeYoojaZoaAZhWwPBs0pbHswaEGh24NBSwrfHgxaUfj2YNCawrcHgzYUvj0YdErh24NBZxS+PRA0Syh8ezBoRuHbg0FzCt8eDFpQ+PZg0JLCtweDJtFvx2MxzjSFvE9CyPs4o50Ojr8UNeBs8z289TLKoMQhrsjmeBSdcj44qGP2MP1zjsmABG89kXzUappdKOXV59IfyapOZZkjczwq6c2AcI7JgIRDFL2USWPUmyJDHr7eVoSiE1H20NU25/ATxdm18NGrYs4xW8CUmcik3c62EW0YU75T+p1s+FAc8lW1X72UdgSbecz3TR/GtVxM7S60pGml7ixrHllDbRouYUyBLrTrcX0Vb0Mwr8WU55SacHQjjglztlbJicbkorXdynZgzDkWs0csHNmk3h6MdBafR0oa7PkCRUyj3vGJNt9zbGQJU3lq+Lj7RovteD4q+DCapL5lEJxdLESjx6hCEmmZJrV+r6tZrB9sJXDrpxK8jG79XSYAR1rc6wOaSM8z/sjeFPJGGp8h8tdM7hvnkY5wOlolNCfBCBvuKymRg2t6iV9Y2JxyR5s6XPoZka1mVH+Oc4qP49EyITLWjDrCPhrPkcbIiNQ1k/pQTKszeRHVwMB+JDmRdmtSDcStbiYd0F0ArgUq+8zUq+jd0b2Ke/WYb6u5xP+mF+PKXRI5m8bOy2BHCJpcFZE9Z1KjellX+Vym5O89rgFpIh/WqAYEHhfIbMwwHp6+bAGOX3CqHOjxYEWEPqbH+veKc0l6JwE9C4Kj+3uvjGsr1xFcm7QTHaNXsHvjOP2mygae7AdlXWahPCFu2K6i8pmMOr7t+kjcwXBzbILIwTSpn1zpciZ/IdkU3G8kkaBoUtlf2NlMckfbOlzmikjiw0c+crbLV9/WWHqbm56siRw+4wZZp934c5QhMvdM+yzvdDjX0xxnRXAfSYlEPdMuiTrLmkneSNuGyzsjsvCMOyeFYflInhvSC8K5eOhRP4ryT7xXpjQY+lwzBU63YMvVfBgRT98qGEW1J/RkEfdu8zwiBR1H0eyJqRJp5f5x7/Jo4w1LETl4Jo5jG+0N9zlGi2ER1gP3OE0k25m2nno2qZlkjTJpuKwNkSlkWlnnh3VRrg7jsMbSxB10YVyJp0Q6g2kkfufdhf+cS/7+8vLkp/6Vs/2xDT8jEnvMooZ/zTbDN6BTqMAkFL4HeuRs2DDeir5ILqAdvNjue+v4onhzEPDdLWq9+159LnYvAMM65rvNQ7V/8INbfHxeb4+2E7714IZ6yDeoCcVBVucXqrKVXDG/wF8di92X1VexuNRu8iGTmftfmiYmS4wrZWScpY4Z7sX3+NH/69W+XLyud/a2dV+PD9vitfDva4ypDfuidC7Q5w4N6U3pPUckAy48PA8Ih/CAcKNJRzp7/M1gXviadWCAfqZIUZhgIXz1knumovfIKAfvGHl6ShPKJuoB92OUcuWA+3FKomTA/UQsdo5FtaHlxabhMl9vHvwD8MFNXO0jDbxa7VlU0giPBkgEUxjg5q3HDB0tJnnP9dsBD727Id59nLGnRMmPM/YMQ3TFeibiOAlAwMgEm4lZcmMmzqK3YKSHaNwHUFRM7RNOZHEwQXjLfTh7Jz9+zvfa84A7Kr5z9aZc3AMARN3XecF7Hq9atkh8+JHKAE0MOHTKVNgvOcrh3DSEVAPO5osQSI/HW6GbhG3UAhXaAoM4590skLRjr8cAyXtH5YXxiDH3/Iu4WQ4+LyHitkk+MSbjJjrwnJa8ZaCa8PAIQ/Gf9+FhCJIJFw0/r2RS9KQWyOXdJrUhdfbY3EbdSyTT+LxG3cGTxicz+rZFGZ/Fhu3z7JnKaBueb85gGd4a2Tta492kOrY6fGGbdxOdA0xfkOtQ94I3asG/5/FIqrOfzxxEbEoElKKIuZ/9/HO/SEjL4PjKVSS0ZbCJgwlMold1YPdZmUVAe0op2ogesnGRKELuTZDJxkWiKWTjAkA2LlBEqJ1h9OiSxAQueoScUei6BYSuW6DYTlu6bgFg4BQottOWrhsGzSl03TBoQaHrhkFLCl03DFpR6Lph0JpC1w2DNhS6bhh0SqHrhkFnFLpuEDRPKHTdMGhGoeuGQXMKXTcMWlDoumHQkkLXDYNWFLpuGLSm0HXDoA2FrhsGnVLoumHQGYWuGwQtEgpdNwyaUei6YdCcQtcNgxYUum4YNGmLR0/AJBQp+hJxME2h1BawrS0i4Jkqyv0O9uYv0bOHSgjirkjf2yhgBu4ev989mdB2C/Z2T7JBqRN+raNpMnmHIi+f3Ju5fTLquHpdv6z/W+yQJYuVf1PI6/r3h3NWiWcy0ybJdH8mQPJhK79e0VOKtiLpFb1813wD+Lq/uYvGyUcsL/r8sMurb/vyy7HeGrNpdwS+lHm+a/fGXPT3+pp6J2NziQfoSXsEzCzQ3ZXi/2l3JX0f5bLemNRsdWw8qx5D+BnKZN7szXT/fyw2xS+/FVX++mvfbPDpl7+X+8Mv/3h+XiB2YAqp6DswoY8pSXoTW88TNWBEuSq33n5MJZDtlyJgRoHgczQ+cdO7m1ajAkFRkITy6AW8/WLWiEmkLWQckBNFyq/KE9HwLyCXwJnENX5UZQGBQlDAubGs4RkMV6H6zdD91lS5GJjcDVWvfRlhlVIlYmASyaj4GoQfHES+ud+19RieDt5aKDSjDkrBBsWp+BKGL6j4AoYvqfiRDfuflotvtu0U85u1f7VkXC/Vp2XdsPbr/zt1X9iFpGswli6Z/Zn917cy17KxrG/Zda5r8abFfEs2Le1Rshqlaamg5V6pVLfSpfC/9NdZbNuyzl+3uGuZ5n4W27ZS0bSUw7Qt1bbc7F+3fD/l+bq6JdqW8ndQbcvxmvuW+439ZXN399f+skGxz+7rVoOp/HWqGbv7241B1Shp21J+RKptOZ60uuVlrRpJKN9r6x++pb10dYOplW9lF63zd/66s8yM15GRbSuQmW+58+l1S1z8sm6ppuXvYJoxGN/PtNF76nt21krTOn/nUNy5qXNL+Za7H/e25N4OxZpWpwf/6dK9/8V/x+V1q7FB99aI61Y9BodmW5lYiq4l0vC74JedHhwDtv1ONj2rW+frpO+Z5E3LozSW5f8uHVli3VK+ZZqWR2msp2lluu6Zu2+rB/+bJW8sy/9d8say/N8lb2zJcb7YVmMhTev8nbcXh1m3fD8bC2lamanvrnmoFW9L7lQvC1rn6+r7ne+Q+lbWtLJA0/7v0pXiRdvijYU0LcPblvXbRNZ3z/xos2a0mWqkVLdqmTV3z7LmDq7lr1+6Bb9DEV5joplR/F//nW95/YUtO8NYvXHf4v472XxXt0Tb6uYX96n0v+Sf7JzqljYu3tye8kNZ7NzU/NU+Cv2kq7TLVGTK2EBPGv7jx/8ApECMsg==";
-        let json = crate::blueprint::blueprint_to_json(bp);
-        let counts = count(&json);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let counts = count(&json, &Default::default()).unwrap();
         let expected = [
             ("arithmetic-combinator", 9),
             ("big-electric-pole", 5),
@@ -107,8 +420,8 @@ mod tests {
     #[test]
     fn test_count_rails() {
         let bp = r"0eNqdlt2OgjAQhd+l15jQTn+AV9lsNihdbYJlA2jWGN99qwKbxXGdckWA9sthOucwZ7auD/ardb5nxZm5TeM7VrydWee2vqyvz3y5t6xgbelqdkmY85X9ZgW/vCfM+t71zt533G5OH/6wX9s2LEjGnZtDe7TV6gpYlSxhX00XdjX+Cg+klcgSdgpXDQFfudZu7m+5uCQPWEHHShyrESqg1DVCBRionCBWkrE8x7GYWEUXa+5UZf5SMa2arlVNVIRjyAfE+cDJZqVMEWw2YXdl/bmqXLltQoPe6NjhjxoB05hPsK4PgO2uf8qB/zg8jVTFJ9rL8+B0A8HQPEoQsCJS8thChMbkQK+rxiWjWEnHphGVUHGVGC06r4TE2JruAoiQbJZgMVBGL+nQADInfDbdWGM1KViRRltBzjIlw7B8mRWkIUgWy5przsb8ICC6CyQlYoWMtq/Ur5tWqGXRLRWBraOTPGAxkFmW5E9oWXRizSuJHnwe/eeX8NoHkEb/+udYtJ+AR09VMEsDjnkLRPRYBYSUAYieWMFQ5MrokRUeEiYM2663+8D4ndcTdrRtd1ugtMhlnisDCqQJkf8DcPXVjw==";
-        let json = crate::blueprint::blueprint_to_json(bp);
-        let counts = count(&json);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let counts = count(&json, &Default::default()).unwrap();
         let expected = [("rail", 78)]
             .into_iter()
             .map(|(k, v)| ((k.to_owned(), Quality(None)), v))
@@ -119,8 +432,8 @@ mod tests {
     #[test]
     fn test_count_book() {
         let bp = "0eNrtWF2PoyAU/S8866SiaO1fmTSNH2SGVMFF7U4z8b/vpe62pisK0sdJ5mEUOOdyuedy7DfKq542kvHulAtxRofvx5sWHd4nj2qMFYKPr1v2wbNKveNZTdEB0a9G0rb1O5nxthGy83NadWjwEOMl/UKHYPBmlnW9zIV+ER6OHqK8Yx2jI/Ht4XrifZ1TCajeIpKHGtHCYsEVJwD6ePdGPHQF6PSNAFHJJC3GCZGK8Akf2+IHeyv80Bo/tcKPrPFjK3xijZ9Y4cebzzcMAH8GMdmOuJtH3HsrIliokluUq1lINzCk06hXGYKdyybMKAKXXWhOM8AbQBOr5AfhBorYjiJy2YVZ9onLLswoHMQaao7XQa1YA+kkV12cqQuoJlK8cxGMJlLspEJdpE4qxEYXcehCoUtG5CKK0ChuJ93NpAYMEetoDXgPa+ahC5XtbQKJcRqlKUlCEkYJflipnQrO1M1lbUvrvGL8w6+z4pNx6ofA8qvPKtgcTOh5IeoaCNcM3qovtLB4pgmMMPmbQD/cP2dwv+jyzCniO4Wl0dtCsZ+v30f55n119hlvqexgYAYr0Ycb4EVjVwmogs8MTqs0ZNjbOTtNsf1HkD4RzFSjh4CTNQq175ga9duCUV5Qv8mK83389FjLhayhYkdVjQXIykkBN1KUPezjosBq+L9S4Q33+TCdnxi/wPaEvI7rH09wQm2nmEf9zY4E2hGsHQmH4wB/S/Z2rSSUNbGpYPt7M1L9y6rqUicOjUo2eNtpB0lMOkjg1qViIw63NpVosuPWmWIN6qbWFBsVycTK1rRkfe3TChZIVviNqOgyhS4L8RbdxEa2OLENd8KQvMzEToWSvMzFTlHjl9nYqTSI0QXuJr/IiMNNfkSTHTf5RavG1v76Jpa+1v7+Jj/3t+7+xrGTBnX1kDih3moXbPpvqAmV4ndowcSDxkaOWz9HgrkPGf/2I7SHMnVe9PTvy2UBb/gD/vaEEw==";
-        let json = crate::blueprint::blueprint_to_json(bp);
-        let counts = count(&json);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let counts = count(&json, &Default::default()).unwrap();
         let expected = [
             (("assembling-machine-3", Some("uncommon")), 2),
             (("bulk-inserter", None), 4),
@@ -138,8 +451,8 @@ mod tests {
     #[test]
     fn test_count_book_with_book() {
         let bp = "0eNrtWF2PoyAU/S8866Si+NG/MmkatWSGjIKL2p2m8b/vpe62pisK0sdJ+tAKnHO53HM59oqKqqeNZLw7FkJ8of318aRF+/erxQQ1xkrBx8ct++B5pZ7xvKZoj+h3I2nb+p3MedsI2fkFrTo0eIjxE/1G+2DwZpZ1vSyEfhEeDh6ivGMdoyPx7cflyPu6oBJQvUUkDzWihcWCK04A9PHujXjoAtDZGwGiE5O0HCdEKsInfGyLH6RW+KE1fmaFH1njx1b4xBo/scKPN59vGAD+DGKyHXE3j5h6KyJYqJJblKtZyDYwZNOoVxmCncsmzCgCl11oTjPAG0ATq+QH4QaK2I4ictmFWfaJyy7MKBzEGmqO10GtWAPpJFddnJkLqCZSvHMRjCZS7KRCXaROKsRGF3HoQqFLRuQiitAobifdzaQGDBHraA14D2vmoTOV7W0CiXEWZRlJQhJGCX5YqZ0KztTN5W1L66Ji/MOv8/KTceqHwPKrzyvYHEzoeSnqGgjXDN6qL7SweKYJjDD5m0A/TJ8zmC66PHOK+E5hafS2UKTz9fso36KvvnzGWyo7GJjBSvThBnjR2FUCquAzh9M6GTKkds5OU2z/EWRPBDPV6CHgZI1C7TumRv22ZJSX1G/y8us+fnys5ULWULGjqsYCZKdJATdSnHrYx1mB1fC9UuEN9/kwnR8ZP8P2hLyM6x+/4ITaTjGP+psdCbQjWDsSDocBPkv2dq0klDWxqWD7ezNS/cuq6jInDo1KNnjbaQdJTDpI4NalYiMOtzaVaLLj1pliDeqm1hQbFcnEytb0xPrapxUskKz0G1HRZQpdFuItuomNbHFiG+6EIXmZiZ0KJXmZi52ixi+zsVNpEKML3E1+kRGHm/yIJjtu8otWja399U0sfa39/U1+7m/d/Y1jJw3q6iFxQr3VLtj031ATKsXv0IKJB42NHLa+jgRzLzL+7U9oD+XqvOjx35uL2evNS/CGP6QVqbc=";
-        let json = crate::blueprint::blueprint_to_json(bp);
-        let counts = count(&json);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let counts = count(&json, &Default::default()).unwrap();
         let expected = [
             (("assembling-machine-3", Some("uncommon")), 2),
             (("bulk-inserter", None), 4),
@@ -153,4 +466,335 @@ mod tests {
         .collect::<BTreeMap<(String, Quality), u64>>();
         assert_eq!(counts, expected);
     }
+
+    #[test]
+    fn test_count_tiles() {
+        let json = r#"{"blueprint": {
+            "item": "blueprint",
+            "entities": [{"entity_number": 1, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}}],
+            "tiles": [
+                {"name": "landfill", "position": {"x": 0, "y": 0}},
+                {"name": "landfill", "position": {"x": 1, "y": 0}},
+                {"name": "stone-path", "position": {"x": 0, "y": 1}},
+                {"name": "hazard-concrete-left", "position": {"x": 1, "y": 1}},
+                {"name": "hazard-concrete-right", "position": {"x": 2, "y": 1}},
+                {"name": "refined-concrete", "position": {"x": 2, "y": 0}, "quality": "rare"}
+            ]
+        }}"#;
+        let counts = count(json, &Default::default()).unwrap();
+        let expected = [
+            (("hazard-concrete", None), 2),
+            (("landfill", None), 2),
+            (("refined-concrete", Some("rare")), 1),
+            (("small-lamp", None), 1),
+            (("stone-brick", None), 1),
+        ]
+        .into_iter()
+        .map(|((name, quality), v)| ((name.to_owned(), Quality(quality.map(|q| q.to_owned()))), v))
+        .collect::<BTreeMap<(String, Quality), u64>>();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_count_requests() {
+        let json = r#"{"blueprint": {
+            "item": "blueprint",
+            "entities": [
+                {"entity_number": 1, "name": "assembling-machine-3", "position": {"x": 1.5, "y": 1.5},
+                    "items": [{"id": {"name": "productivity-module-3", "quality": "legendary"},
+                        "items": {"in_inventory": [{"inventory": 4, "stack": 0}, {"inventory": 4, "stack": 1}]}}]},
+                {"entity_number": 2, "name": "locomotive", "position": {"x": 5, "y": 3}, "quality": "rare",
+                    "items": [
+                        {"id": {"name": "nuclear-fuel"}, "items": {"in_inventory": [{"inventory": 1, "stack": 0, "count": 1}]}},
+                        {"id": {"name": "rocket-fuel"}, "items": {"in_inventory": [{"inventory": 1, "stack": 1, "count": 20}]}},
+                        {"id": {"name": "solar-panel-equipment"}, "items": {"grid_count": 4}}
+                    ]}
+            ]
+        }}"#;
+        let counts = |include| {
+            let options = CountOptions {
+                include,
+                ..Default::default()
+            };
+            count(json, &options)
+                .unwrap()
+                .into_iter()
+                .map(|((name, quality), count)| (name, quality.0, count))
+                .collect::<Vec<_>>()
+        };
+        let entities = [
+            ("assembling-machine-3".to_owned(), None, 1),
+            ("locomotive".to_owned(), Some("rare".to_owned()), 1),
+        ];
+        let requests = [
+            ("nuclear-fuel".to_owned(), None, 1),
+            (
+                "productivity-module-3".to_owned(),
+                Some("legendary".to_owned()),
+                2,
+            ),
+            ("rocket-fuel".to_owned(), None, 20),
+            ("solar-panel-equipment".to_owned(), None, 4),
+        ];
+        assert_eq!(counts(Include::Entities), entities);
+        assert_eq!(counts(Include::Requests), requests);
+        let mut all = [entities.to_vec(), requests.to_vec()].concat();
+        all.sort();
+        assert_eq!(counts(Include::All), all);
+    }
+
+    #[test]
+    fn test_count_legacy_blueprint() {
+        let json = r#"{"blueprint": {
+            "item": "blueprint",
+            "version": 281479278886912,
+            "entities": [
+                {"entity_number": 1, "name": "assembling-machine-3", "position": {"x": 1.5, "y": 1.5},
+                    "items": {"speed-module-3": 2, "productivity-module": 1}},
+                {"entity_number": 2, "name": "beacon", "position": {"x": 4.5, "y": 1.5},
+                    "items": {"speed-module-3": 2}},
+                {"entity_number": 3, "name": "constant-combinator", "position": {"x": 0.5, "y": 4.5},
+                    "control_behavior": {"filters": []}},
+                {"entity_number": 4, "name": "decider-combinator", "position": {"x": 2, "y": 4.5},
+                    "control_behavior": {"decider_conditions": {"conditions": [{
+                        "first_signal": {"type": "virtual", "name": "signal-A"},
+                        "constant": 2.5, "comparator": ">"}]}}}
+            ]
+        }}"#;
+        let options = CountOptions {
+            include: Include::All,
+            ..Default::default()
+        };
+        let counts = count(json, &options)
+            .unwrap()
+            .into_iter()
+            .map(|((name, quality), count)| (name, quality.0, count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                ("assembling-machine-3".to_owned(), None, 1),
+                ("beacon".to_owned(), None, 1),
+                ("constant-combinator".to_owned(), None, 1),
+                ("decider-combinator".to_owned(), None, 1),
+                ("productivity-module".to_owned(), None, 1),
+                ("speed-module-3".to_owned(), None, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_prototypes() {
+        let json = r#"{"blueprint": {
+            "item": "blueprint",
+            "entities": [
+                {"entity_number": 1, "name": "curved-rail-a", "position": {"x": 0, "y": 0}},
+                {"entity_number": 2, "name": "elevated-curved-rail-a", "position": {"x": 8, "y": 0}},
+                {"entity_number": 3, "name": "modded-thing", "position": {"x": 16.5, "y": 0.5}}
+            ],
+            "tiles": [{"name": "stone-path", "position": {"x": 0, "y": 0}}]
+        }}"#;
+        let dump = r#"{
+            "elevated-curved-rail-a": {
+                "elevated-curved-rail-a": {"placeable_by": {"item": "rail", "count": 3}}
+            },
+            "item": {
+                "modded-thing-item": {"place_result": "modded-thing"}
+            }
+        }"#;
+        let options = CountOptions {
+            prototypes: Some(Prototypes::from_dump(dump).unwrap()),
+            ..Default::default()
+        };
+        let counts = count(json, &options).unwrap();
+        let expected = [("modded-thing-item", 1), ("rail", 6), ("stone-brick", 1)]
+            .into_iter()
+            .map(|(k, v)| ((k.to_owned(), Quality(None)), v))
+            .collect::<BTreeMap<(String, Quality), u64>>();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let json = r#"{"blueprint_book": {
+            "item": "blueprint-book",
+            "label": "Base",
+            "blueprints": [
+                {"index": 0, "blueprint": {"item": "blueprint", "label": "Lamps", "entities": [
+                    {"entity_number": 1, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}},
+                    {"entity_number": 2, "name": "small-lamp", "position": {"x": 2.5, "y": 0.5}}
+                ]}},
+                {"index": 3, "blueprint_book": {"item": "blueprint-book", "label": "Rails", "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "entities": [
+                        {"entity_number": 1, "name": "straight-rail", "position": {"x": 1, "y": 1}},
+                        {"entity_number": 2, "name": "small-lamp", "position": {"x": 3.5, "y": 0.5}, "quality": "rare"}
+                    ]}}
+                ]}},
+                {"index": 4, "upgrade_planner": {"item": "upgrade-planner", "settings": {}}}
+            ]
+        }}"#;
+        let breakdown = breakdown(json, &Default::default()).unwrap();
+        assert_eq!(
+            breakdown.to_string(),
+            "\
+blueprint book: Base
+  [0] blueprint: Lamps
+    2 small-lamp
+  [3] blueprint book: Rails
+    [0] blueprint: (no label)
+      1 rail
+      1 small-lamp (rare)
+    subtotal:
+      1 rail
+      1 small-lamp (rare)
+  [4] upgrade planner: (no label)
+  total:
+    2 small-lamp
+    1 rail
+    1 small-lamp (rare)
+"
+        );
+        let json = serde_json::to_value(&breakdown.entries[1]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "blueprint book",
+                "index": 3,
+                "label": "Rails",
+                "counts": [
+                    {"name": "rail", "count": 1},
+                    {"name": "small-lamp", "quality": "rare", "count": 1},
+                ],
+                "entries": [{
+                    "kind": "blueprint",
+                    "index": 0,
+                    "counts": [
+                        {"name": "rail", "count": 1},
+                        {"name": "small-lamp", "quality": "rare", "count": 1},
+                    ],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_sort_and_tables() {
+        let json = r#"{"blueprint_book": {
+            "item": "blueprint-book",
+            "label": "Base",
+            "blueprints": [{"index": 2, "blueprint": {"item": "blueprint", "label": "Lamps", "entities": [
+                {"entity_number": 1, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}, "quality": "legendary"},
+                {"entity_number": 2, "name": "small-lamp", "position": {"x": 2.5, "y": 0.5}, "quality": "rare"},
+                {"entity_number": 3, "name": "small-lamp", "position": {"x": 4.5, "y": 0.5}, "quality": "rare"},
+                {"entity_number": 4, "name": "medium-electric-pole", "position": {"x": 1.5, "y": 1.5}},
+                {"entity_number": 5, "name": "assembling-machine-1", "position": {"x": 1.5, "y": 4.5}, "quality": "epic"}
+            ]}}]
+        }}"#;
+        let counts = count(json, &Default::default()).unwrap();
+        let mut counts = item_counts(counts);
+        let order = |counts: &[ItemCount]| {
+            counts
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order(&counts),
+            [
+                "2 small-lamp (rare)",
+                "1 assembling-machine-1 (epic)",
+                "1 medium-electric-pole",
+                "1 small-lamp (legendary)"
+            ]
+        );
+        sort_counts(&mut counts, SortBy::Name, &QualityLadder::default());
+        assert_eq!(
+            order(&counts),
+            [
+                "1 assembling-machine-1 (epic)",
+                "1 medium-electric-pole",
+                "2 small-lamp (rare)",
+                "1 small-lamp (legendary)"
+            ]
+        );
+        sort_counts(&mut counts, SortBy::Quality, &QualityLadder::default());
+        assert_eq!(
+            order(&counts),
+            [
+                "1 medium-electric-pole",
+                "2 small-lamp (rare)",
+                "1 assembling-machine-1 (epic)",
+                "1 small-lamp (legendary)"
+            ]
+        );
+        let modded = QualityLadder::new(
+            ["normal", "legendary", "epic", "rare"]
+                .map(str::to_owned)
+                .to_vec(),
+        )
+        .unwrap();
+        let mut modded_counts = counts.clone();
+        sort_counts(&mut modded_counts, SortBy::Quality, &modded);
+        assert_eq!(
+            order(&modded_counts),
+            [
+                "1 medium-electric-pole",
+                "1 small-lamp (legendary)",
+                "1 assembling-machine-1 (epic)",
+                "2 small-lamp (rare)"
+            ]
+        );
+        assert_eq!(
+            counts_table(&counts, TableFormat::Csv),
+            "\
+name,quality,count
+medium-electric-pole,normal,1
+small-lamp,rare,2
+assembling-machine-1,epic,1
+small-lamp,legendary,1
+"
+        );
+
+        let mut breakdown = breakdown(json, &Default::default()).unwrap();
+        breakdown.sort(SortBy::Name, &QualityLadder::default());
+        assert_eq!(
+            breakdown.to_table(TableFormat::Tsv),
+            "\
+blueprint\tname\tquality\tcount
+Base / [2] Lamps\tassembling-machine-1\tepic\t1
+Base / [2] Lamps\tmedium-electric-pole\tnormal\t1
+Base / [2] Lamps\tsmall-lamp\trare\t2
+Base / [2] Lamps\tsmall-lamp\tlegendary\t1
+"
+        );
+    }
+
+    #[test]
+    fn test_count_wires() {
+        let json = r#"{"blueprint_book": {"item": "blueprint-book", "blueprints": [
+            {"index": 0, "blueprint": {"item": "blueprint", "wires": [
+                [1, 1, 2, 1],
+                [1, 3, 3, 1],
+                [2, 2, 3, 4],
+                [4, 5, 5, 5],
+                [5, 5, 6, 6]
+            ]}},
+            {"index": 1, "blueprint": {"item": "blueprint", "wires": [[1, 2, 2, 2]]}}
+        ]}}"#;
+        let wires = count_wires(json).unwrap();
+        assert_eq!(
+            wires,
+            WireCounts {
+                red: 2,
+                green: 2,
+                copper: 2
+            }
+        );
+        let items = wires
+            .item_counts()
+            .iter()
+            .map(|count| count.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(items, ["2 red-wire", "2 green-wire", "2 copper-cable"]);
+    }
 }
@@ -0,0 +1,367 @@
+//! Comparing counted items against what's already at hand.
+//!
+//! An inventory can be a csv or tsv table with `name`, `count` and optionally `quality` columns
+//! (like `count-entities --format csv` writes), a json list of `{"name", "quality", "count"}`
+//! objects or a `{"name": count}` object, or a blueprint whose constant combinators output the
+//! items as signals.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    Quality, blueprint_to_json,
+    count_entities::{Counts, ItemCount, SortBy, item_counts, sort_counts},
+    quality_ladder::QualityLadder,
+};
+use crate::{
+    error::{Error, Result},
+    table::{TableFormat, format_table},
+};
+
+/// `normal` quality is the same as no quality.
+fn normalize(quality: Option<String>) -> Quality {
+    Quality(quality.filter(|quality| quality != "normal"))
+}
+
+fn add(inventory: &mut Counts, name: String, quality: Option<String>, count: u64) {
+    let total = inventory.entry((name, normalize(quality))).or_insert(0);
+    *total = total.saturating_add(count);
+}
+
+/// Reads an inventory, working out which kind it is from its contents.
+pub fn parse(text: &str) -> Result<Counts> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        let json = serde_json::from_str::<Value>(trimmed)?;
+        let is_blueprint = ["blueprint", "blueprint_book"]
+            .iter()
+            .any(|key| json.get(key).is_some());
+        if is_blueprint {
+            from_blueprint(&json)
+        } else {
+            from_json(json)
+        }
+    } else if trimmed.starts_with('0') {
+        let json = blueprint_to_json(trimmed)?;
+        from_blueprint(&serde_json::from_str(&json)?)
+    } else {
+        from_table(text)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonItem {
+    name: String,
+    #[serde(default)]
+    quality: Option<String>,
+    count: u64,
+}
+
+fn from_json(json: Value) -> Result<Counts> {
+    let mut inventory = Counts::new();
+    match json {
+        Value::Array(_) => {
+            for item in serde_json::from_value::<Vec<JsonItem>>(json)? {
+                add(&mut inventory, item.name, item.quality, item.count);
+            }
+        }
+        Value::Object(_) => {
+            for (name, count) in serde_json::from_value::<BTreeMap<String, u64>>(json)? {
+                add(&mut inventory, name, None, count);
+            }
+        }
+        _ => {
+            return Err(Error::InvalidInventory(
+                "json should be a list of items or an object of counts".to_owned(),
+            ));
+        }
+    }
+    Ok(inventory)
+}
+
+/// Splits a csv line, handling quoted values.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let value = values.last_mut().expect("starts with one value");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                value.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(String::new()),
+            c => value.push(c),
+        }
+    }
+    values
+}
+
+fn from_table(text: &str) -> Result<Counts> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines
+        .next()
+        .ok_or_else(|| Error::InvalidInventory("empty inventory".to_owned()))?;
+    let split = |line: &str| -> Vec<String> {
+        if header.contains('\t') {
+            line.split('\t').map(str::to_owned).collect()
+        } else {
+            split_csv(line)
+        }
+    };
+    let columns = split(header);
+    let column = |name: &str| columns.iter().position(|column| column.trim() == name);
+    let (Some(name_column), Some(count_column)) = (column("name"), column("count")) else {
+        return Err(Error::InvalidInventory(
+            "table should have a header with name and count columns".to_owned(),
+        ));
+    };
+    let quality_column = column("quality");
+    let mut inventory = Counts::new();
+    for (i, line) in lines.enumerate() {
+        let values = split(line);
+        let value = |column: usize| values.get(column).map(|value| value.trim());
+        let name = value(name_column).filter(|name| !name.is_empty());
+        let count = value(count_column).and_then(|count| count.parse::<u64>().ok());
+        let (Some(name), Some(count)) = (name, count) else {
+            return Err(Error::InvalidInventory(format!(
+                "row {} should have a name and a whole number count",
+                i + 1
+            )));
+        };
+        let quality = quality_column
+            .and_then(value)
+            .filter(|quality| !quality.is_empty())
+            .map(str::to_owned);
+        add(&mut inventory, name.to_owned(), quality, count);
+    }
+    Ok(inventory)
+}
+
+/// The item signals output by every constant combinator in a blueprint or book.
+fn from_blueprint(json: &Value) -> Result<Counts> {
+    let mut inventory = Counts::new();
+    add_blueprint_signals(json, &mut inventory);
+    Ok(inventory)
+}
+
+fn add_blueprint_signals(json: &Value, inventory: &mut Counts) {
+    if let Some(book) = json.get("blueprint_book") {
+        for entry in book["blueprints"].as_array().into_iter().flatten() {
+            add_blueprint_signals(entry, inventory);
+        }
+        return;
+    }
+    let entities = json["blueprint"]["entities"]
+        .as_array()
+        .into_iter()
+        .flatten();
+    for control in entities.map(|entity| &entity["control_behavior"]) {
+        let sections = control["sections"]["sections"]
+            .as_array()
+            .into_iter()
+            .flatten();
+        let filters =
+            sections.flat_map(|section| section["filters"].as_array().into_iter().flatten());
+        // 1.1 combinators have their signals directly in `filters`.
+        let legacy = control["filters"].as_array().into_iter().flatten();
+        for (signal, count) in filters
+            .map(|filter| (filter, &filter["count"]))
+            .chain(legacy.map(|filter| (&filter["signal"], &filter["count"])))
+        {
+            let is_item = signal["type"].as_str().is_none_or(|kind| kind == "item");
+            let (Some(name), Some(count)) = (signal["name"].as_str(), count.as_u64()) else {
+                continue;
+            };
+            if is_item {
+                let quality = signal["quality"].as_str().map(str::to_owned);
+                add(inventory, name.to_owned(), quality, count);
+            }
+        }
+    }
+}
+
+/// What's missing from an inventory, and what's left over.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Comparison {
+    /// How many more of each item are needed, most first.
+    pub shortfall: Vec<ItemCount>,
+    /// How many of each item aren't needed, most first.
+    pub surplus: Vec<ItemCount>,
+}
+
+/// Compares the items `needed` against an `inventory`, by name and quality.
+pub fn compare(needed: &Counts, inventory: &Counts) -> Comparison {
+    let normalized = |counts: &Counts| {
+        let mut normalized = Counts::new();
+        for ((name, quality), count) in counts {
+            add(&mut normalized, name.clone(), quality.0.clone(), *count);
+        }
+        normalized
+    };
+    let (needed, inventory) = (normalized(needed), normalized(inventory));
+    let shortfall = needed
+        .iter()
+        .filter_map(|(key, count)| {
+            let have = inventory.get(key).copied().unwrap_or(0);
+            (*count > have).then(|| (key.clone(), count - have))
+        })
+        .collect();
+    let surplus = inventory
+        .iter()
+        .filter_map(|(key, have)| {
+            let count = needed.get(key).copied().unwrap_or(0);
+            (*have > count).then(|| (key.clone(), have - count))
+        })
+        .collect();
+    Comparison {
+        shortfall: item_counts(shortfall),
+        surplus: item_counts(surplus),
+    }
+}
+
+impl Comparison {
+    /// Sorts the shortfall and the surplus.
+    pub fn sort(&mut self, sort: SortBy, ladder: &QualityLadder) {
+        sort_counts(&mut self.shortfall, sort, ladder);
+        sort_counts(&mut self.surplus, sort, ladder);
+    }
+
+    /// Lists the shortfall, and with `with_surplus` the surplus after it, as text.
+    pub fn to_text(&self, with_surplus: bool) -> String {
+        let mut out = String::new();
+        let section = |out: &mut String, title: &str, counts: &[ItemCount], empty: &str| {
+            writeln!(out, "{title}:").expect("writing to a String can't fail");
+            if counts.is_empty() {
+                writeln!(out, "  {empty}").expect("writing to a String can't fail");
+            }
+            for count in counts {
+                writeln!(out, "  {count}").expect("writing to a String can't fail");
+            }
+        };
+        section(&mut out, "shortfall", &self.shortfall, "nothing missing");
+        if with_surplus {
+            out.push('\n');
+            section(&mut out, "surplus", &self.surplus, "nothing left over");
+        }
+        out
+    }
+
+    /// Formats the shortfall as a table with name, quality and count columns. With
+    /// `with_surplus`, the surplus is included too, and a first `status` column says which is
+    /// which.
+    pub fn to_table(&self, format: TableFormat, with_surplus: bool) -> String {
+        if !with_surplus {
+            let rows = self
+                .shortfall
+                .iter()
+                .map(ItemCount::row)
+                .collect::<Vec<_>>();
+            return format_table(format, &["name", "quality", "count"], &rows);
+        }
+        let rows = (self.shortfall.iter().map(|count| ("shortfall", count)))
+            .chain(self.surplus.iter().map(|count| ("surplus", count)))
+            .map(|(status, count)| [vec![status.to_owned()], count.row()].concat())
+            .collect::<Vec<_>>();
+        format_table(format, &["status", "name", "quality", "count"], &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(counts: &[ItemCount]) -> Vec<String> {
+        counts.iter().map(|count| count.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let expected = [
+            (("rail".to_owned(), Quality(None)), 100),
+            (
+                ("small-lamp".to_owned(), Quality(Some("rare".to_owned()))),
+                5,
+            ),
+        ]
+        .into_iter()
+        .collect::<Counts>();
+        let csv = "name,quality,count\nrail,normal,60\n\"rail\",,40\nsmall-lamp,rare,5\n";
+        assert_eq!(parse(csv).unwrap(), expected);
+        let tsv = "count\tname\tquality\n100\trail\n5\tsmall-lamp\trare\n";
+        assert_eq!(parse(tsv).unwrap(), expected);
+        let json = r#"[{"name": "rail", "count": 100}, {"name": "small-lamp", "quality": "rare", "count": 5}]"#;
+        assert_eq!(parse(json).unwrap(), expected);
+        let blueprint = r#"{"blueprint": {"item": "blueprint", "entities": [
+            {"entity_number": 1, "name": "constant-combinator", "position": {"x": 0.5, "y": 0.5},
+                "control_behavior": {"sections": {"sections": [{"index": 1, "filters": [
+                    {"index": 1, "name": "rail", "quality": "normal", "comparator": "=", "count": 100},
+                    {"index": 2, "type": "virtual", "name": "signal-A", "quality": "normal", "comparator": "=", "count": 1}
+                ]}]}}},
+            {"entity_number": 2, "name": "constant-combinator", "position": {"x": 1.5, "y": 0.5},
+                "control_behavior": {"filters": [
+                    {"index": 1, "signal": {"type": "item", "name": "small-lamp", "quality": "rare"}, "count": 5}
+                ]}}
+        ]}}"#;
+        assert_eq!(parse(blueprint).unwrap(), expected);
+        assert_eq!(
+            parse(r#"{"rail": 100}"#).unwrap(),
+            [(("rail".to_owned(), Quality(None)), 100)]
+                .into_iter()
+                .collect()
+        );
+
+        assert!(matches!(
+            parse("item,amount\nrail,100\n"),
+            Err(Error::InvalidInventory(_))
+        ));
+        assert!(matches!(
+            parse("name,count\nrail,lots\n"),
+            Err(Error::InvalidInventory(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare() {
+        let needed = [
+            (("rail".to_owned(), Quality(None)), 250),
+            (
+                ("small-lamp".to_owned(), Quality(Some("rare".to_owned()))),
+                19,
+            ),
+            (
+                ("small-lamp".to_owned(), Quality(Some("normal".to_owned()))),
+                1,
+            ),
+        ]
+        .into_iter()
+        .collect::<Counts>();
+        let inventory =
+            parse("name,quality,count\nrail,,300\nsmall-lamp,rare,4\nsmall-lamp,,1\nradar,,2\n")
+                .unwrap();
+        let comparison = compare(&needed, &inventory);
+        assert_eq!(listed(&comparison.shortfall), ["15 small-lamp (rare)"]);
+        assert_eq!(listed(&comparison.surplus), ["50 rail", "2 radar"]);
+        assert_eq!(
+            comparison.to_text(true),
+            "shortfall:\n  15 small-lamp (rare)\n\nsurplus:\n  50 rail\n  2 radar\n"
+        );
+        assert_eq!(
+            comparison.to_table(TableFormat::Csv, true),
+            "\
+status,name,quality,count
+shortfall,small-lamp,rare,15
+surplus,rail,normal,50
+surplus,radar,normal,2
+"
+        );
+        assert_eq!(
+            compare(&needed, &needed).to_text(false),
+            "shortfall:\n  nothing missing\n"
+        );
+    }
+}
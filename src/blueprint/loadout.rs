@@ -0,0 +1,247 @@
+//! Blueprints that carry or request the items counted by [`count_entities`](super::count_entities),
+//! for bringing the materials for a build along.
+
+use serde_json::{Value, json};
+
+use super::{
+    Quality, count_entities::Counts, logistic_sections, migrate::MIGRATED_VERSION,
+    quality_ladder::QualityLadder,
+};
+use crate::prototypes::Prototypes;
+
+/// Inventory slots in a cargo wagon.
+const CARGO_WAGON_SLOTS: u64 = 40;
+/// Stack size for items the prototype dump doesn't know, or without a dump. It's the most common
+/// stack size in the base game.
+const DEFAULT_STACK_SIZE: u64 = 50;
+
+/// What to carry or request the items with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Loadout {
+    /// Cargo wagons, one behind the other, with an inventory slot filtered for each stack of
+    /// items. Stack sizes come from the prototype dump. Paste them onto a north-south track.
+    CargoWagons,
+    /// A requester chest requesting every item.
+    RequesterChest,
+    /// A space platform hub requesting every item from the planet below.
+    SpacePlatformHub,
+}
+
+/// Makes a blueprint for `loadout` carrying or requesting `counts`.
+///
+/// With `by_quality`, requests are grouped in one logistic section per quality, in the order of
+/// `ladder`.
+pub fn make_loadout_json(
+    counts: &Counts,
+    loadout: Loadout,
+    prototypes: Option<&Prototypes>,
+    by_quality: bool,
+    ladder: &QualityLadder,
+) -> Value {
+    let mut signals = counts
+        .iter()
+        .map(|(key, count)| (key.clone(), i64::try_from(*count).unwrap_or(i64::MAX)))
+        .collect::<Vec<_>>();
+    signals.sort_by_key(|(_, count)| -count);
+    let entities = match loadout {
+        Loadout::CargoWagons => cargo_wagons(signals, prototypes),
+        Loadout::RequesterChest | Loadout::SpacePlatformHub => {
+            let (name, position) = match loadout {
+                Loadout::RequesterChest => ("requester-chest", json!({"x": 0.5, "y": 0.5})),
+                _ => ("space-platform-hub", json!({"x": 0, "y": 0})),
+            };
+            let sections = logistic_sections(signals, by_quality, ladder, usize::MAX)
+                .pop()
+                .unwrap_or_default();
+            vec![json!({
+                "entity_number": 1,
+                "name": name,
+                "position": position,
+                "request_filters": {"sections": sections},
+            })]
+        }
+    };
+    let icon = match loadout {
+        Loadout::CargoWagons => "cargo-wagon",
+        Loadout::RequesterChest => "requester-chest",
+        Loadout::SpacePlatformHub => "space-platform-hub",
+    };
+    json!({
+        "blueprint": {
+            "item": "blueprint",
+            "icons": [{"index": 1, "signal": {"name": icon}}],
+            "entities": entities,
+            "version": MIGRATED_VERSION.to_packed(),
+        }
+    })
+}
+
+fn cargo_wagons(
+    signals: Vec<((String, Quality), i64)>,
+    prototypes: Option<&Prototypes>,
+) -> Vec<Value> {
+    let mut wagons = Vec::<Vec<Value>>::new();
+    let mut slots = 0;
+    for ((name, quality), count) in signals {
+        let stack_size = prototypes
+            .and_then(|prototypes| prototypes.stack_size(&name))
+            .unwrap_or(DEFAULT_STACK_SIZE)
+            .max(1);
+        let stacks = u64::try_from(count)
+            .unwrap_or_default()
+            .div_ceil(stack_size);
+        for _ in 0..stacks {
+            if slots % CARGO_WAGON_SLOTS == 0 {
+                wagons.push(Vec::new());
+            }
+            slots += 1;
+            let filters = wagons.last_mut().expect("pushed above");
+            filters.push(json!({
+                "index": filters.len() + 1,
+                "name": name,
+                "quality": quality.0.as_deref().unwrap_or("normal"),
+                "comparator": "=",
+            }));
+        }
+    }
+    wagons
+        .into_iter()
+        .enumerate()
+        .map(|(i, filters)| {
+            json!({
+                "entity_number": i + 1,
+                "name": "cargo-wagon",
+                // Wagons are 7 tiles apart when coupled.
+                "position": {"x": 1, "y": 3.5 + 7.0 * i as f64},
+                "orientation": 0,
+                "inventory": {"filters": filters},
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts() -> Counts {
+        [
+            (("rail".to_owned(), Quality(None)), 250),
+            (
+                ("small-lamp".to_owned(), Quality(Some("rare".to_owned()))),
+                19,
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_cargo_wagons() {
+        let dump = r#"{"rail-planner": {"rail": {"stack_size": 100}}}"#;
+        let prototypes = Prototypes::from_dump(dump).unwrap();
+        let json = make_loadout_json(
+            &counts(),
+            Loadout::CargoWagons,
+            Some(&prototypes),
+            false,
+            &QualityLadder::default(),
+        );
+        let wagons = json["blueprint"]["entities"].as_array().unwrap();
+        assert_eq!(wagons.len(), 1);
+        let filters = wagons[0]["inventory"]["filters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|filter| format!("{} {}", filter["name"], filter["quality"]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filters,
+            [
+                r#""rail" "normal""#,
+                r#""rail" "normal""#,
+                r#""rail" "normal""#,
+                r#""small-lamp" "rare""#,
+            ]
+        );
+
+        let many = [(("stone-brick".to_owned(), Quality(None)), 50 * 41)]
+            .into_iter()
+            .collect();
+        let json = make_loadout_json(
+            &many,
+            Loadout::CargoWagons,
+            None,
+            false,
+            &QualityLadder::default(),
+        );
+        let wagons = json["blueprint"]["entities"].as_array().unwrap();
+        assert_eq!(wagons.len(), 2);
+        assert_eq!(wagons[1]["position"], json!({"x": 1, "y": 10.5}));
+        assert_eq!(
+            wagons[1]["inventory"]["filters"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_requests() {
+        let json = make_loadout_json(
+            &counts(),
+            Loadout::RequesterChest,
+            None,
+            true,
+            &QualityLadder::default(),
+        );
+        assert_eq!(
+            json["blueprint"]["entities"],
+            json!([{
+                "entity_number": 1,
+                "name": "requester-chest",
+                "position": {"x": 0.5, "y": 0.5},
+                "request_filters": {"sections": [
+                    {"index": 1, "filters": [
+                        {"index": 1, "name": "rail", "quality": "normal", "comparator": "=", "count": 250},
+                    ]},
+                    {"index": 2, "filters": [
+                        {"index": 1, "name": "small-lamp", "quality": "rare", "comparator": "=", "count": 19},
+                    ]},
+                ]},
+            }])
+        );
+        // Qualities from a mod, in the mod's order.
+        let mut modded = counts();
+        modded.insert(("rail".to_owned(), Quality(Some("shiny".to_owned()))), 5);
+        let ladder = QualityLadder::new(vec![
+            "normal".to_owned(),
+            "shiny".to_owned(),
+            "rare".to_owned(),
+        ])
+        .unwrap();
+        let json = make_loadout_json(&modded, Loadout::RequesterChest, None, true, &ladder);
+        let qualities = json["blueprint"]["entities"][0]["request_filters"]["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|section| section["filters"][0]["quality"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(qualities, ["normal", "shiny", "rare"]);
+
+        let json = make_loadout_json(
+            &counts(),
+            Loadout::SpacePlatformHub,
+            None,
+            false,
+            &QualityLadder::default(),
+        );
+        let hub = &json["blueprint"]["entities"][0];
+        assert_eq!(hub["name"], "space-platform-hub");
+        assert_eq!(
+            hub["request_filters"]["sections"][0]["filters"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
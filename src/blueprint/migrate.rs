@@ -0,0 +1,801 @@
+//! Converting Factorio 1.1 blueprints to the 2.0 format.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use serde_json::{Map, Value, json};
+
+use super::{BlueprintType, Version};
+use crate::error::{Error, Result};
+
+/// The version written into migrated blueprints.
+pub const MIGRATED_VERSION: Version = Version {
+    major: 2,
+    minor: 0,
+    patch: 0,
+    build: 0,
+};
+
+/// Entities that were renamed or merged in 2.0, and whether the new entity needs `use_filters`
+/// set to keep behaving the same.
+#[rustfmt::skip]
+const RENAMES: &[(&str, &str, bool)] = &[
+    ("curved-rail", "legacy-curved-rail", false),
+    ("filter-inserter", "fast-inserter", true),
+    ("stack-filter-inserter", "bulk-inserter", true),
+    ("stack-inserter", "bulk-inserter", false),
+    ("logistic-chest-active-provider", "active-provider-chest", false),
+    ("logistic-chest-passive-provider", "passive-provider-chest", false),
+    ("logistic-chest-storage", "storage-chest", false),
+    ("logistic-chest-buffer", "buffer-chest", false),
+    ("logistic-chest-requester", "requester-chest", false),
+];
+
+/// Items that were renamed in 2.0.
+const ITEM_RENAMES: &[(&str, &str)] = &[
+    ("effectivity-module", "efficiency-module"),
+    ("effectivity-module-2", "efficiency-module-2"),
+    ("effectivity-module-3", "efficiency-module-3"),
+];
+
+/// The result of [`migrate`].
+#[derive(Debug)]
+pub struct Migrated {
+    pub json: Value,
+    /// Blueprints that were left in the 1.x format.
+    pub unmigrated: Vec<Unmigrated>,
+}
+
+/// A 1.x blueprint that [`migrate`] couldn't convert.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unmigrated {
+    /// The blueprint's label, after those of the books it's in, e.g. `[2] Smelting > [0] Iron`.
+    pub path: String,
+    /// The entity with item requests we don't know where to put.
+    pub entity: String,
+}
+
+impl Display for Unmigrated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: left as 1.1 for the game to migrate, since it's not known which inventory {} keeps \
+             its items in",
+            self.path, self.entity
+        )
+    }
+}
+
+/// Rewrites a 1.x blueprint (or book of them) into the 2.0 format.
+///
+/// Blueprints that are already 2.0 or later are left alone, as are 1.x blueprints with item
+/// requests for entities we don't know the inventories of: those keep their old version, so
+/// the game migrates them itself when they're imported, and are listed in
+/// [`Migrated::unmigrated`].
+pub fn migrate(mut json: Value) -> Result<Migrated> {
+    let mut unmigrated = Vec::new();
+    migrate_entry(&mut json, &mut Vec::new(), &mut unmigrated)?;
+    Ok(Migrated { json, unmigrated })
+}
+
+/// Returns whether everything in `json` is now in the 2.0 format. `path` is the labels of the
+/// books `json` is in.
+fn migrate_entry(
+    json: &mut Value,
+    path: &mut Vec<String>,
+    unmigrated: &mut Vec<Unmigrated>,
+) -> Result<bool> {
+    let index = json.get("index").and_then(Value::as_u64);
+    let mut bp = BlueprintType::<&mut Value>::new(json)?;
+    let label = bp.as_ref().label().unwrap_or("(no label)").to_owned();
+    path.push(match index {
+        Some(index) => format!("[{index}] {label}"),
+        None => label,
+    });
+    let version = bp
+        .as_ref()
+        .any()
+        .get("version")
+        .and_then(Value::as_u64)
+        .map(Version::from_packed);
+    let needs_migration = version.is_none_or(|version| version.major < 2);
+    let mut migrated = true;
+    match &mut bp {
+        BlueprintType::Blueprint(blueprint) => {
+            if needs_migration {
+                let mut new_blueprint = blueprint.clone();
+                match migrate_blueprint(&mut new_blueprint)? {
+                    None => **blueprint = new_blueprint,
+                    Some(entity) => {
+                        migrated = false;
+                        unmigrated.push(Unmigrated {
+                            path: path.join(" > "),
+                            entity,
+                        });
+                    }
+                }
+            }
+        }
+        BlueprintType::BlueprintBook(book) => {
+            if let Some(blueprints) = book.get_mut("blueprints").and_then(Value::as_array_mut) {
+                for entry in blueprints {
+                    migrated &= migrate_entry(entry, path, unmigrated)?;
+                }
+            }
+        }
+        BlueprintType::UpgradePlanner(_) | BlueprintType::DeconstructionPlanner(_) => {}
+    }
+    if needs_migration
+        && migrated
+        && let Some(object) = bp.any_mut().as_object_mut()
+    {
+        object.insert("version".to_owned(), json!(MIGRATED_VERSION.to_packed()));
+    }
+    path.pop();
+    Ok(migrated)
+}
+
+/// Returns the name of the entity that couldn't be converted, if any, in which case the blueprint
+/// is partly migrated and should be thrown away.
+fn migrate_blueprint(blueprint: &mut Value) -> Result<Option<String>> {
+    let Some(blueprint) = blueprint.as_object_mut() else {
+        return Err(Error::InvalidBlueprint(
+            "blueprint should be a json object".into(),
+        ));
+    };
+    let mut wires = BTreeSet::new();
+    if let Some(entities) = blueprint.get_mut("entities").and_then(Value::as_array_mut) {
+        for entity in entities {
+            let Some(entity) = entity.as_object_mut() else {
+                return Err(Error::InvalidBlueprint(format!(
+                    "entity should be a json object, got {entity}"
+                )));
+            };
+            if !migrate_entity(entity, &mut wires)? {
+                let name = entity.get("name").and_then(Value::as_str).unwrap_or("");
+                return Ok(Some(name.to_owned()));
+            }
+        }
+    }
+    if !wires.is_empty() {
+        let existing = blueprint
+            .entry("wires")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| Error::InvalidBlueprint("wires should be an array".into()))?;
+        existing.extend(
+            wires
+                .into_iter()
+                .map(|(a, a_connector, b, b_connector)| json!([a, a_connector, b, b_connector])),
+        );
+    }
+    // 1.1 schedules are just the list of records; 2.0 ones can have interrupts too.
+    for schedule in blueprint
+        .get_mut("schedules")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(schedule) = schedule.as_object_mut()
+            && let Some(records) = schedule.remove("schedule")
+        {
+            let records = match records {
+                Value::Array(_) => json!({"records": records}),
+                records => records,
+            };
+            schedule.insert("schedule".to_owned(), records);
+        }
+    }
+    Ok(None)
+}
+
+/// Returns `false` if the entity has item requests we can't convert.
+fn migrate_entity(
+    entity: &mut Map<String, Value>,
+    wires: &mut BTreeSet<(u64, u64, u64, u64)>,
+) -> Result<bool> {
+    let entity_number = entity
+        .get("entity_number")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| Error::InvalidBlueprint("entity missing entity_number".into()))?;
+
+    let direction = entity.get("direction").and_then(Value::as_u64).unwrap_or(0);
+    if let Some(Value::String(name)) = entity.get_mut("name") {
+        if name == "straight-rail" && direction % 2 == 1 {
+            *name = "legacy-straight-rail".to_owned();
+        } else if let Some(&(_, new_name, use_filters)) =
+            RENAMES.iter().find(|(old_name, _, _)| old_name == name)
+        {
+            *name = new_name.to_owned();
+            if use_filters {
+                entity.insert("use_filters".to_owned(), json!(true));
+            }
+        }
+    }
+    if entity.contains_key("direction") {
+        entity.insert("direction".to_owned(), json!(direction * 2));
+    }
+
+    if let Some(connections) = entity.remove("connections") {
+        collect_connections(entity_number, &connections, wires)?;
+    }
+    if let Some(neighbours) = entity.remove("neighbours") {
+        for neighbour in neighbours.as_array().into_iter().flatten() {
+            let neighbour = neighbour
+                .as_u64()
+                .ok_or_else(|| Error::InvalidBlueprint(format!("invalid neighbour {neighbour}")))?;
+            wires.insert(normalize_wire(
+                entity_number,
+                POLE_COPPER,
+                neighbour,
+                POLE_COPPER,
+            ));
+        }
+    }
+
+    if let Some(control_behavior) = entity
+        .get_mut("control_behavior")
+        .and_then(Value::as_object_mut)
+    {
+        if let Some(filters) = control_behavior.remove("filters") {
+            control_behavior.insert(
+                "sections".to_owned(),
+                json!({"sections": [{"index": 1, "filters": migrate_signal_filters(filters)}]}),
+            );
+        }
+        if let Some(Value::Object(conditions)) = control_behavior.get_mut("decider_conditions")
+            && !conditions.contains_key("conditions")
+        {
+            migrate_decider_conditions(conditions);
+        }
+    }
+
+    if let Some(filters) = entity.remove("request_filters") {
+        let filters = match filters {
+            Value::Array(filters) => {
+                let mut filters = json!({"sections": [
+                    {"index": 1, "filters": migrate_request_filters(filters)}
+                ]});
+                if let Some(from_buffers) = entity.remove("request_from_buffers") {
+                    filters["request_from_buffers"] = from_buffers;
+                }
+                filters
+            }
+            filters => filters,
+        };
+        entity.insert("request_filters".to_owned(), filters);
+    }
+
+    if let Some(Value::Object(items)) = entity.get("items") {
+        let name = entity.get("name").and_then(Value::as_str).unwrap_or("");
+        let Some(items) = migrate_items(name, items) else {
+            return Ok(false);
+        };
+        entity.insert("items".to_owned(), items);
+    }
+    Ok(true)
+}
+
+/// Converts 1.1 decider combinator settings, a single condition and output, to 2.0 lists of
+/// conditions and outputs.
+fn migrate_decider_conditions(conditions: &mut Map<String, Value>) {
+    let mut condition = Map::new();
+    for key in ["first_signal", "second_signal", "constant", "comparator"] {
+        if let Some(value) = conditions.remove(key) {
+            condition.insert(key.to_owned(), value);
+        }
+    }
+    let mut output = Map::new();
+    if let Some(signal) = conditions.remove("output_signal") {
+        output.insert("signal".to_owned(), signal);
+    }
+    if let Some(copy_count) = conditions.remove("copy_count_from_input") {
+        output.insert("copy_count_from_input".to_owned(), copy_count);
+    }
+    conditions.insert("conditions".to_owned(), json!([condition]));
+    conditions.insert("outputs".to_owned(), json!([output]));
+}
+
+/// The 2.0 name of the item `name`.
+fn item_name(name: &Value) -> Value {
+    match ITEM_RENAMES.iter().find(|(old_name, _)| name == old_name) {
+        Some((_, new_name)) => json!(new_name),
+        None => name.clone(),
+    }
+}
+
+/// `defines.inventory` for where a base game entity keeps its modules, or its fuel or ammo.
+fn inventory(entity: &str, modules: bool) -> Option<u64> {
+    match (entity, modules) {
+        ("beacon", true) => Some(1),
+        ("electric-mining-drill" | "pumpjack", true) => Some(2),
+        ("lab", true) => Some(3),
+        (
+            "assembling-machine-2"
+            | "assembling-machine-3"
+            | "chemical-plant"
+            | "oil-refinery"
+            | "centrifuge"
+            | "electric-furnace"
+            | "rocket-silo",
+            true,
+        ) => Some(4),
+        (
+            "stone-furnace"
+            | "steel-furnace"
+            | "boiler"
+            | "burner-mining-drill"
+            | "burner-inserter"
+            | "locomotive"
+            | "gun-turret"
+            | "artillery-turret",
+            false,
+        ) => Some(1),
+        _ => None,
+    }
+}
+
+/// Converts 1.1 item requests (`{name: count}`) to 2.0 ones, which say which inventory slots
+/// the items go in: one slot per module, and one per fuel or ammo item with the whole count in
+/// it. `None` if we don't know where `entity` keeps the items.
+fn migrate_items(entity: &str, items: &Map<String, Value>) -> Option<Value> {
+    let mut next_stack = [0, 0];
+    let mut requests = Vec::new();
+    for (name, count) in items {
+        let count = count.as_u64()?;
+        let modules = name.contains("-module");
+        let inventory = inventory(entity, modules)?;
+        let next_stack = &mut next_stack[usize::from(modules)];
+        let stacks = if modules {
+            (0..count)
+                .map(|_| {
+                    *next_stack += 1;
+                    json!({"inventory": inventory, "stack": *next_stack - 1})
+                })
+                .collect::<Vec<_>>()
+        } else {
+            *next_stack += 1;
+            vec![json!({"inventory": inventory, "stack": *next_stack - 1, "count": count})]
+        };
+        requests.push(json!({
+            "id": {"name": item_name(&json!(name))},
+            "items": {"in_inventory": stacks},
+        }));
+    }
+    Some(Value::Array(requests))
+}
+
+/// Converts 1.1 logistic chest requests (`{index, name, count}`) to 2.0 logistic filters.
+fn migrate_request_filters(filters: Vec<Value>) -> Value {
+    let filters = filters.into_iter().map(|filter| {
+        let mut new_filter = Map::new();
+        if let Some(index) = filter.get("index") {
+            new_filter.insert("index".to_owned(), index.clone());
+        }
+        if let Some(name) = filter.get("name") {
+            new_filter.insert("name".to_owned(), item_name(name));
+        }
+        new_filter.insert("quality".to_owned(), json!("normal"));
+        new_filter.insert("comparator".to_owned(), json!("="));
+        if let Some(count) = filter.get("count") {
+            new_filter.insert("count".to_owned(), count.clone());
+        }
+        Value::Object(new_filter)
+    });
+    Value::Array(filters.collect())
+}
+
+const POLE_COPPER: u64 = 5;
+
+/// `defines.wire_connector_id` for a 1.1 circuit connection point and wire color.
+fn circuit_connector_id(circuit_id: u64, color: &str) -> Option<u64> {
+    match (circuit_id, color) {
+        (1, "red") => Some(1),
+        (1, "green") => Some(2),
+        (2, "red") => Some(3),
+        (2, "green") => Some(4),
+        _ => None,
+    }
+}
+
+/// Copper wire connections on power switches are `Cu0` and `Cu1`, with `wire_id` 0 and 1.
+fn copper_connector_id(wire_id: u64) -> u64 {
+    POLE_COPPER + wire_id
+}
+
+fn collect_connections(
+    entity_number: u64,
+    connections: &Value,
+    wires: &mut BTreeSet<(u64, u64, u64, u64)>,
+) -> Result<()> {
+    let invalid = || Error::InvalidBlueprint(format!("invalid connections {connections}"));
+    let connections = connections.as_object().ok_or_else(invalid)?;
+    for (point, connection) in connections {
+        if let Some(wire_id) = point.strip_prefix("Cu") {
+            let wire_id = wire_id.parse::<u64>().map_err(|_| invalid())?;
+            for target in connection.as_array().ok_or_else(invalid)? {
+                let target_entity = target
+                    .get("entity_id")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(invalid)?;
+                let target_wire_id = target.get("wire_id").and_then(Value::as_u64).unwrap_or(0);
+                wires.insert(normalize_wire(
+                    entity_number,
+                    copper_connector_id(wire_id),
+                    target_entity,
+                    copper_connector_id(target_wire_id),
+                ));
+            }
+        } else {
+            let circuit_id = point.parse::<u64>().map_err(|_| invalid())?;
+            for (color, targets) in connection.as_object().ok_or_else(invalid)? {
+                let connector = circuit_connector_id(circuit_id, color).ok_or_else(invalid)?;
+                for target in targets.as_array().ok_or_else(invalid)? {
+                    let target_entity = target
+                        .get("entity_id")
+                        .and_then(Value::as_u64)
+                        .ok_or_else(invalid)?;
+                    let target_circuit_id = target
+                        .get("circuit_id")
+                        .and_then(Value::as_u64)
+                        .unwrap_or(1);
+                    let target_connector =
+                        circuit_connector_id(target_circuit_id, color).ok_or_else(invalid)?;
+                    wires.insert(normalize_wire(
+                        entity_number,
+                        connector,
+                        target_entity,
+                        target_connector,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Both ends of a connection list it in 1.1, so put the ends in a consistent order to dedupe.
+fn normalize_wire(a: u64, a_connector: u64, b: u64, b_connector: u64) -> (u64, u64, u64, u64) {
+    if (a, a_connector) <= (b, b_connector) {
+        (a, a_connector, b, b_connector)
+    } else {
+        (b, b_connector, a, a_connector)
+    }
+}
+
+/// Converts 1.1 constant combinator filters (`{signal, count, index}`) to 2.0 logistic filters.
+fn migrate_signal_filters(filters: Value) -> Value {
+    let filters = filters.as_array().into_iter().flatten().map(|filter| {
+        let mut new_filter = Map::new();
+        if let Some(index) = filter.get("index") {
+            new_filter.insert("index".to_owned(), index.clone());
+        }
+        if let Some(signal) = filter.get("signal") {
+            if let Some(typ) = signal.get("type")
+                && typ != "item"
+            {
+                new_filter.insert("type".to_owned(), typ.clone());
+            }
+            if let Some(name) = signal.get("name") {
+                new_filter.insert("name".to_owned(), item_name(name));
+            }
+        }
+        new_filter.insert("quality".to_owned(), json!("normal"));
+        new_filter.insert("comparator".to_owned(), json!("="));
+        if let Some(count) = filter.get("count") {
+            new_filter.insert("count".to_owned(), count.clone());
+        }
+        Value::Object(new_filter)
+    });
+    Value::Array(filters.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blueprint::model::{
+        BlueprintKind, ItemRequests, RequestFilters, Schedule, ScheduleLayout,
+    };
+
+    const V1_1: u64 = 281479275675648; // 1.1.61.0
+
+    #[test]
+    fn test_migrate_blueprint() {
+        let json = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [
+                    {
+                        "entity_number": 1,
+                        "name": "medium-electric-pole",
+                        "position": {"x": 0.5, "y": 0.5},
+                        "neighbours": [2],
+                        "connections": {"1": {"red": [{"entity_id": 2}]}},
+                    },
+                    {
+                        "entity_number": 2,
+                        "name": "constant-combinator",
+                        "position": {"x": 2.5, "y": 0.5},
+                        "direction": 2,
+                        "control_behavior": {
+                            "filters": [
+                                {"index": 1, "count": 5, "signal": {"type": "item", "name": "rail"}},
+                                {"index": 2, "count": 1, "signal": {"type": "virtual", "name": "signal-A"}},
+                            ]
+                        },
+                        "connections": {"1": {"red": [{"entity_id": 1}], "green": [{"entity_id": 3, "circuit_id": 2}]}},
+                    },
+                    {
+                        "entity_number": 3,
+                        "name": "arithmetic-combinator",
+                        "position": {"x": 4, "y": 0.5},
+                        "direction": 6,
+                        "connections": {"2": {"green": [{"entity_id": 2}]}},
+                    },
+                    {
+                        "entity_number": 4,
+                        "name": "straight-rail",
+                        "position": {"x": 9, "y": 9},
+                        "direction": 3,
+                    },
+                    {
+                        "entity_number": 5,
+                        "name": "curved-rail",
+                        "position": {"x": 13, "y": 9},
+                    },
+                    {
+                        "entity_number": 6,
+                        "name": "stack-filter-inserter",
+                        "position": {"x": 0.5, "y": 4.5},
+                        "direction": 4,
+                    },
+                    {
+                        "entity_number": 7,
+                        "name": "medium-electric-pole",
+                        "position": {"x": 6.5, "y": 0.5},
+                        "connections": {"Cu0": [{"entity_id": 1, "wire_id": 0}]},
+                    },
+                    {
+                        "entity_number": 8,
+                        "name": "assembling-machine-3",
+                        "position": {"x": 10.5, "y": 0.5},
+                        "items": {"effectivity-module": 1, "speed-module-3": 2},
+                    },
+                    {
+                        "entity_number": 9,
+                        "name": "logistic-chest-requester",
+                        "position": {"x": 0.5, "y": 6.5},
+                        "request_filters": [{"index": 1, "name": "iron-plate", "count": 100}],
+                        "request_from_buffers": true,
+                    },
+                    {
+                        "entity_number": 11,
+                        "name": "decider-combinator",
+                        "position": {"x": 6, "y": 4.5},
+                        "direction": 2,
+                        "control_behavior": {"decider_conditions": {
+                            "first_signal": {"type": "item", "name": "iron-plate"},
+                            "constant": 100,
+                            "comparator": "<",
+                            "output_signal": {"type": "virtual", "name": "signal-check"},
+                            "copy_count_from_input": false,
+                        }},
+                    },
+                    {
+                        "entity_number": 10,
+                        "name": "locomotive",
+                        "position": {"x": 20, "y": 3},
+                        "orientation": 0.5,
+                        "items": {"coal": 50},
+                    },
+                ],
+                "schedules": [{
+                    "locomotives": [10],
+                    "schedule": [{
+                        "station": "Iron",
+                        "wait_conditions": [{"type": "full", "compare_type": "or"}],
+                    }],
+                }],
+                "version": V1_1,
+            }
+        });
+        let migrated = migrate(json).unwrap().json;
+        let blueprint = &migrated["blueprint"];
+        assert_eq!(
+            blueprint["wires"],
+            json!([[1, 1, 2, 1], [1, 5, 2, 5], [1, 5, 7, 5], [2, 2, 3, 4]])
+        );
+        let entities = blueprint["entities"].as_array().unwrap();
+        assert!(entities.iter().all(
+            |entity| entity.get("connections").is_none() && entity.get("neighbours").is_none()
+        ));
+        let names_and_directions = entities
+            .iter()
+            .map(|entity| (entity["name"].as_str().unwrap(), entity.get("direction")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names_and_directions,
+            [
+                ("medium-electric-pole", None),
+                ("constant-combinator", Some(&json!(4))),
+                ("arithmetic-combinator", Some(&json!(12))),
+                ("legacy-straight-rail", Some(&json!(6))),
+                ("legacy-curved-rail", None),
+                ("bulk-inserter", Some(&json!(8))),
+                ("medium-electric-pole", None),
+                ("assembling-machine-3", None),
+                ("requester-chest", None),
+                ("decider-combinator", Some(&json!(4))),
+                ("locomotive", None),
+            ]
+        );
+        assert_eq!(entities[5]["use_filters"], json!(true));
+        assert_eq!(
+            entities[1]["control_behavior"],
+            json!({"sections": {"sections": [{"index": 1, "filters": [
+                {"index": 1, "name": "rail", "quality": "normal", "comparator": "=", "count": 5},
+                {"index": 2, "type": "virtual", "name": "signal-A", "quality": "normal", "comparator": "=", "count": 1},
+            ]}]}})
+        );
+        assert_eq!(
+            entities[7]["items"],
+            json!([
+                {"id": {"name": "efficiency-module"}, "items": {"in_inventory": [
+                    {"inventory": 4, "stack": 0},
+                ]}},
+                {"id": {"name": "speed-module-3"}, "items": {"in_inventory": [
+                    {"inventory": 4, "stack": 1},
+                    {"inventory": 4, "stack": 2},
+                ]}},
+            ])
+        );
+        assert_eq!(
+            entities[8]["request_filters"],
+            json!({"sections": [{"index": 1, "filters": [
+                {"index": 1, "name": "iron-plate", "quality": "normal", "comparator": "=", "count": 100},
+            ]}], "request_from_buffers": true})
+        );
+        assert_eq!(entities[8].get("request_from_buffers"), None);
+        assert_eq!(
+            entities[10]["items"],
+            json!([{"id": {"name": "coal"}, "items": {"in_inventory": [
+                {"inventory": 1, "stack": 0, "count": 50},
+            ]}}])
+        );
+        assert_eq!(
+            entities[9]["control_behavior"],
+            json!({"decider_conditions": {
+                "conditions": [{
+                    "first_signal": {"type": "item", "name": "iron-plate"},
+                    "constant": 100,
+                    "comparator": "<",
+                }],
+                "outputs": [{
+                    "signal": {"type": "virtual", "name": "signal-check"},
+                    "copy_count_from_input": false,
+                }],
+            }})
+        );
+        assert_eq!(
+            blueprint["schedules"],
+            json!([{"locomotives": [10], "schedule": {"records": [{
+                "station": "Iron",
+                "wait_conditions": [{"type": "full", "compare_type": "or"}],
+            }]}}])
+        );
+        assert_eq!(blueprint["version"], json!(MIGRATED_VERSION.to_packed()));
+        // The migrated blueprint should fit the 2.0 model, without falling back to the 1.1
+        // layouts it also accepts.
+        let typed =
+            serde_json::from_value::<crate::blueprint::model::BlueprintEntry>(migrated).unwrap();
+        let BlueprintKind::Blueprint(typed) = typed.kind else {
+            panic!("not a blueprint");
+        };
+        let entities = typed.entities();
+        assert!(matches!(entities[7].items, Some(ItemRequests::List(_))));
+        assert!(matches!(
+            entities[8].request_filters,
+            Some(RequestFilters::Sections(_))
+        ));
+        let decider = entities[9].control_behavior.as_ref().unwrap();
+        let decider = decider.decider_conditions.as_ref().unwrap();
+        assert_eq!(decider.conditions.as_ref().map(Vec::len), Some(1));
+        assert_eq!(decider.outputs.as_ref().map(Vec::len), Some(1));
+        assert!(matches!(
+            typed.schedules.as_deref(),
+            Some([Schedule {
+                schedule: ScheduleLayout::Current(_),
+                ..
+            }])
+        ));
+    }
+
+    #[test]
+    fn test_migrate_unknown_items() {
+        // We don't know which inventory a modded entity keeps its modules in, so leave the
+        // blueprint for the game to migrate.
+        let json = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [
+                    {"entity_number": 1, "name": "inserter", "position": {"x": 0.5, "y": 0.5}, "direction": 2},
+                    {"entity_number": 2, "name": "modded-assembler", "position": {"x": 3.5, "y": 0.5},
+                        "items": {"speed-module": 2}},
+                ],
+                "version": V1_1,
+            }
+        });
+        let migrated = migrate(json.clone()).unwrap();
+        assert_eq!(migrated.json, json);
+        assert_eq!(
+            migrated.unmigrated,
+            [Unmigrated {
+                path: "(no label)".to_owned(),
+                entity: "modded-assembler".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_migrate_book() {
+        let json = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "entities": [
+                        {"entity_number": 1, "name": "inserter", "position": {"x": 0.5, "y": 0.5}, "direction": 2},
+                    ], "version": V1_1}},
+                    {"index": 1, "blueprint": {"item": "blueprint", "entities": [
+                        {"entity_number": 1, "name": "inserter", "position": {"x": 0.5, "y": 0.5}, "direction": 4},
+                    ], "version": 562949957353472u64}},
+                ],
+                "version": V1_1,
+            }
+        });
+        let migrated = migrate(json.clone()).unwrap();
+        assert_eq!(migrated.unmigrated, []);
+        let migrated = migrated.json;
+        let book = &migrated["blueprint_book"];
+        assert_eq!(book["version"], json!(MIGRATED_VERSION.to_packed()));
+        assert_eq!(
+            book["blueprints"][0]["blueprint"]["entities"][0]["direction"],
+            json!(4)
+        );
+        // already 2.0, so left alone
+        assert_eq!(
+            book["blueprints"][1]["blueprint"]["entities"][0]["direction"],
+            json!(4)
+        );
+        assert_eq!(
+            book["blueprints"][1]["blueprint"]["version"],
+            json!(562949957353472u64)
+        );
+
+        // One blueprint that can't be migrated keeps the book at 1.1 too.
+        let mut json = json;
+        json["blueprint_book"]["label"] = json!("Mall");
+        json["blueprint_book"]["blueprints"].as_array_mut().unwrap().push(json!({"index": 2, "blueprint": {
+            "item": "blueprint",
+            "label": "Modded",
+            "entities": [{"entity_number": 1, "name": "modded-furnace", "position": {"x": 1, "y": 1},
+                "items": {"coal": 5}}],
+            "version": V1_1,
+        }}));
+        let migrated = migrate(json).unwrap();
+        assert_eq!(
+            migrated.unmigrated,
+            [Unmigrated {
+                path: "Mall > [2] Modded".to_owned(),
+                entity: "modded-furnace".to_owned(),
+            }]
+        );
+        let book = &migrated.json["blueprint_book"];
+        assert_eq!(book["version"], json!(V1_1));
+        assert_eq!(
+            book["blueprints"][0]["blueprint"]["version"],
+            json!(MIGRATED_VERSION.to_packed())
+        );
+    }
+}
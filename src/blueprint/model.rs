@@ -0,0 +1,672 @@
+//! Typed model of the Factorio 2.0 blueprint json.
+//!
+//! Only the fields that the tools in this crate care about are modelled. Every struct has a
+//! flattened `extra` map that catches everything else, so deserializing and re-serializing a
+//! blueprint doesn't lose anything: the result is the same json value. Optional arrays are
+//! `Option<Vec<_>>` rather than defaulting to empty so that a missing array and an empty one also
+//! survive the round trip.
+//!
+//! The round trip isn't byte-for-byte, though. Unknown fields keep their text and their order
+//! relative to each other, but each object's modelled fields are written first, in struct order.
+//! `unwrap` and `wrap` don't go through this model: they keep json as a [`Value`], which keeps
+//! keys in their original order, so wrapping unwrapped json encodes exactly the same text.
+//!
+//! Fields whose layout changed since 1.1 (item requests, requester chest filters and train
+//! schedules) accept the 1.1 layout too, so 1.1 blueprints can be read without migrating them.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// Fields that aren't modelled explicitly.
+pub type Extra = Map<String, Value>;
+
+/// The top level of a blueprint string, or an entry in a blueprint book.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlueprintEntry {
+    /// Position in the containing book. Only present for entries inside a book.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    #[serde(flatten)]
+    pub kind: BlueprintKind,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// The four kinds of blueprint item, keyed by their json key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlueprintKind {
+    Blueprint(Blueprint),
+    BlueprintBook(BlueprintBook),
+    UpgradePlanner(UpgradePlanner),
+    DeconstructionPlanner(DeconstructionPlanner),
+}
+
+impl BlueprintKind {
+    /// The kind as words, e.g. `"blueprint book"`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            BlueprintKind::Blueprint(_) => "blueprint",
+            BlueprintKind::BlueprintBook(_) => "blueprint book",
+            BlueprintKind::UpgradePlanner(_) => "upgrade planner",
+            BlueprintKind::DeconstructionPlanner(_) => "deconstruction planner",
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            BlueprintKind::Blueprint(bp) => bp.label.as_deref(),
+            BlueprintKind::BlueprintBook(book) => book.label.as_deref(),
+            BlueprintKind::UpgradePlanner(planner) => planner.label.as_deref(),
+            BlueprintKind::DeconstructionPlanner(planner) => planner.label.as_deref(),
+        }
+    }
+
+    pub fn version(&self) -> Option<u64> {
+        match self {
+            BlueprintKind::Blueprint(bp) => bp.version,
+            BlueprintKind::BlueprintBook(book) => book.version,
+            BlueprintKind::UpgradePlanner(planner) => planner.version,
+            BlueprintKind::DeconstructionPlanner(planner) => planner.version,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entities: Option<Vec<Entity>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tiles: Option<Vec<Tile>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wires: Option<Vec<Wire>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedules: Option<Vec<Schedule>>,
+    /// Parameters of a parameterized blueprint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Vec<Value>>,
+    #[serde(
+        rename = "snap-to-grid",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub snap_to_grid: Option<Position>,
+    #[serde(
+        rename = "absolute-snapping",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub absolute_snapping: Option<bool>,
+    #[serde(
+        rename = "position-relative-to-grid",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub position_relative_to_grid: Option<Position>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Blueprint {
+    pub fn entities(&self) -> &[Entity] {
+        self.entities.as_deref().unwrap_or_default()
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        self.tiles.as_deref().unwrap_or_default()
+    }
+
+    pub fn wires(&self) -> &[Wire] {
+        self.wires.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlueprintBook {
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blueprints: Option<Vec<BlueprintEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_index: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl BlueprintBook {
+    pub fn blueprints(&self) -> &[BlueprintEntry] {
+        self.blueprints.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradePlanner {
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<UpgradeSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mappers: Option<Vec<UpgradeMapper>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeMapper {
+    pub index: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<MapperTarget>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<MapperTarget>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// One side of an upgrade planner mapping.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapperTarget {
+    /// `"entity"` or `"item"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeconstructionPlanner {
+    pub item: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<DeconstructionSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeconstructionSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<Icon>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_filters: Option<Vec<DeconstructionFilter>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tile_filters: Option<Vec<DeconstructionFilter>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeconstructionFilter {
+    pub index: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A position in tiles.
+///
+/// The coordinates are kept as json numbers so that `1` doesn't turn into `1.0` on the way back
+/// out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: Number,
+    pub y: Number,
+}
+
+impl Position {
+    pub fn x(&self) -> f64 {
+        self.x.as_f64().unwrap_or_default()
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y.as_f64().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Icon {
+    pub index: u64,
+    pub signal: SignalID,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A signal, as used in icons, conditions and filters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignalID {
+    /// `"item"` if not present.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub entity_number: u64,
+    pub name: String,
+    pub position: Position,
+    /// On the 16-way scale: 0 is north, 4 is east.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe_quality: Option<String>,
+    /// Modules, fuel, ammo etc. requested to be inserted into the entity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<ItemRequests>,
+    /// Requester and buffer chest requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_filters: Option<RequestFilters>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_behavior: Option<ControlBehavior>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tile {
+    pub name: String,
+    pub position: Position,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A wire between two entities: `[entity_number, connector, entity_number, connector]`.
+///
+/// The connector ids are `defines.wire_connector_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Wire(pub u64, pub u8, pub u64, pub u8);
+
+/// The items requested for an entity, in either layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ItemRequests {
+    /// 2.0: which inventory slots each item goes into.
+    List(Vec<ItemRequest>),
+    /// 1.1: how many of each item, like `{"speed-module": 2}`.
+    Legacy(BTreeMap<String, u64>),
+}
+
+impl ItemRequests {
+    /// Each item's name, quality and how many are requested.
+    pub fn counts(&self) -> Vec<(&str, Option<&str>, u64)> {
+        match self {
+            ItemRequests::List(requests) => requests
+                .iter()
+                .map(|request| {
+                    let id = &request.id;
+                    (&id.name[..], id.quality.as_deref(), request.count())
+                })
+                .collect(),
+            ItemRequests::Legacy(counts) => counts
+                .iter()
+                .map(|(name, count)| (&name[..], None, *count))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemRequest {
+    pub id: ItemId,
+    pub items: ItemRequestItems,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl ItemRequest {
+    /// How many of the item go into the entity, counting its equipment grid.
+    pub fn count(&self) -> u64 {
+        self.items
+            .in_inventory
+            .iter()
+            .flatten()
+            .map(|position| position.count.unwrap_or(1))
+            .chain(self.items.grid_count)
+            .fold(0u64, u64::saturating_add)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemId {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemRequestItems {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_inventory: Option<Vec<InventoryPosition>>,
+    /// Number of items going into the entity's equipment grid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid_count: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryPosition {
+    pub inventory: u64,
+    pub stack: u64,
+    /// 1 if not present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub locomotives: Vec<u64>,
+    pub schedule: ScheduleLayout,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A train schedule, in either layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScheduleLayout {
+    /// 2.0: records and interrupts.
+    Current(ScheduleData),
+    /// 1.1: just the records.
+    Legacy(Vec<ScheduleRecord>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub records: Option<Vec<ScheduleRecord>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrupts: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_conditions: Option<Vec<WaitCondition>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaitCondition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compare_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<CircuitCondition>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// The circuit network settings of an entity.
+///
+/// Different entities use different subsets of these fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlBehavior {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_condition: Option<CircuitCondition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logistic_condition: Option<CircuitCondition>,
+    /// Constant combinator signals.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sections: Option<LogisticSections>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decider_conditions: Option<DeciderConditions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arithmetic_conditions: Option<ArithmeticConditions>,
+    /// Selector combinator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_signal: Option<SignalID>,
+    /// Selector combinator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count_signal: Option<SignalID>,
+    /// Selector combinator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality_destination_signal: Option<SignalID>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_signal: Option<SignalID>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub second_signal: Option<SignalID>,
+    /// Kept as a json number; older and modded blueprints can have non-integer constants.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constant: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Requester and buffer chest requests, in either layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestFilters {
+    /// 2.0: logistic sections.
+    Sections(LogisticSections),
+    /// 1.1: a single list of filters.
+    Legacy(Vec<LogisticFilter>),
+}
+
+/// Logistic sections, used both by constant combinators and by requester chests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogisticSections {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sections: Option<Vec<LogisticSection>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogisticSection {
+    pub index: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<Vec<LogisticFilter>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogisticFilter {
+    pub index: u64,
+    /// `"item"` if not present.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comparator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeciderConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<CircuitCondition>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<DeciderOutput>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeciderOutput {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<SignalID>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy_count_from_input: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constant: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArithmeticConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_signal: Option<SignalID>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub second_signal: Option<SignalID>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_constant: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub second_constant: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_signal: Option<SignalID>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that parsing `bp` into the model and writing it back gives the same json value.
+    #[track_caller]
+    fn assert_round_trips(bp: &str) -> BlueprintEntry {
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let value: Value = serde_json::from_str(&json).unwrap();
+        let typed: BlueprintEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_value(&typed).unwrap(), value);
+        // Without the model, the text itself comes back unchanged.
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        typed
+    }
+
+    #[test]
+    fn test_round_trip_blueprint() {
+        // selector combinators, with lots of control behavior
+        let typed = assert_round_trips(
+            "0eNqtlN1ugzAMhd/F12Eaf+uK1CeZJhSCu0WChDmhWlXl3Wd+1G6l6wXbFcE6Pv4OSThB1fTYkTYeihNoZY2D4uUETr8Z2Qw1I1uEAhw2qLylSNm20kbyEoIAbWr8hCIOrwLQeO01Tgbjy7E0fVshsUDcMxLQWce91gwT2S+NHwUcoYiS+Pkh5zm1Ju4aBZkAxvRkm7LCd3nQbMBdtkOSk2KewbbTomwle3rqcQYuF/E0WRNZQu756GXD6FzETisIIYhFnGR1nM2KOMr2ZkgzPpfsynYsXtCT5MJN+nQ1fbKCfiaKPEnj9kgXyNLZnhSWzrNWfUv0O3q2Gj39A/peN/4H+Fxg+dX3HjzbTtKIU8Dudop8dYr4fzZgvhfnOGTb87ma7sn1Ft05dOGirtH5IQUPXrbI+iCNwjpSmlSv/c27xv8R7bHlyuXPJOCA5MYw+VOyzbbbfJPmabZJQvgCXQumXg==",
+        );
+        let BlueprintKind::Blueprint(bp) = typed.kind else {
+            panic!("expected a blueprint, got {typed:?}");
+        };
+        assert!(!bp.entities().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_book() {
+        let typed = assert_round_trips(
+            "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D",
+        );
+        let BlueprintKind::BlueprintBook(book) = typed.kind else {
+            panic!("expected a book, got {typed:?}");
+        };
+        let indexes: Vec<_> = book.blueprints().iter().map(|bp| bp.index).collect();
+        assert_eq!(indexes, [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn test_round_trip_planners() {
+        // deconstruction planner
+        assert_round_trips(
+            "0eNptj90KwjAMhd8l1xvofhwr+CQio65Rim0620yU0Xc3ol4MvEu+E87JWcDgGChxnEe2gYbJaSKMoBZIyGzpkt4zElt+DmfrGKOQwwKkPYKC0+yupaWEURQo4DZrJ6eiUIheO0Fj8JOOmoPYwl6AJYMPUJt8LICtwyGhw0++D0Zct1/+J0/+M4LdyibLwuhFXtcpf3UKuIuPEFDtruqbvm+7uq2brsr5Baj6WmY=",
+        );
+        // upgrade planner
+        assert_round_trips(
+            "0eNqtkdFqwzAMRf9FzwmUNmkbQ7+klKGuagjEsisroyX43ycz1sIGWx/6ZOkiXx10Z5hiL3iitzgiMwm4GRKpDtynUnuMkcTK/QxnCb5oeosEDoh10BtUwOhLr4KcYhCtjzSq6ZcJxzLhgIN4HE16Dz6ioAZbBDvIFWj4w/KMSesfvvZn4BNdwS1y9S/UZKPSS7D3xVi/nB9gzRNgKRqE2sFfBnR3fIBs86E0Sr6c4ivp+jvpCj4s2SEwuHa97JquazerdtVsljl/Ag1ntSg=",
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_preserved() {
+        let value = serde_json::json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [{
+                    "entity_number": 1,
+                    "name": "modded-thing",
+                    "position": {"x": 1, "y": 0.5},
+                    "modded_setting": {"nested": [1, 2.5, "three"]},
+                }],
+                "some_new_field": true,
+                "version": 562949957353472u64,
+            },
+            "unexpected_top_level": null,
+        });
+        let typed: BlueprintEntry = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&typed).unwrap(), value);
+    }
+}
@@ -0,0 +1,211 @@
+//! The order of quality tiers, worst first, for the base game or for mods that add or rename
+//! qualities.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use super::{QUALITY_TIERS, Quality};
+use crate::error::{Error, Result};
+
+/// Qualities from worst to best.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QualityLadder {
+    tiers: Vec<String>,
+}
+
+/// The fields of a quality prototype that we look at.
+#[derive(Deserialize)]
+pub(crate) struct RawQuality {
+    #[serde(default)]
+    next: Option<String>,
+    #[serde(default)]
+    hidden: bool,
+}
+
+/// What a quality ladder file can contain.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLadder {
+    /// Quality names, worst first.
+    List(Vec<String>),
+    /// A whole `data-raw-dump.json`.
+    Dump {
+        quality: HashMap<String, RawQuality>,
+    },
+    /// Just the `quality` section of a dump.
+    Qualities(HashMap<String, RawQuality>),
+}
+
+impl Default for QualityLadder {
+    /// The base game's five qualities.
+    fn default() -> Self {
+        QualityLadder {
+            tiers: QUALITY_TIERS.map(str::to_owned).to_vec(),
+        }
+    }
+}
+
+impl QualityLadder {
+    /// A ladder with these qualities, worst first.
+    pub fn new(tiers: Vec<String>) -> Result<QualityLadder> {
+        if tiers.is_empty() {
+            return Err(Error::InvalidQualities("there are no qualities".to_owned()));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = tiers.iter().find(|tier| !seen.insert(*tier)) {
+            return Err(Error::InvalidQualities(format!(
+                "{duplicate:?} is listed more than once"
+            )));
+        }
+        Ok(QualityLadder { tiers })
+    }
+
+    /// Reads a quality ladder file, see [`QualityLadder::from_json`].
+    pub fn load(path: &Path) -> Result<QualityLadder> {
+        let json = fs::read_to_string(path).map_err(Error::io(path))?;
+        QualityLadder::from_json(&json).map_err(|e| match e {
+            Error::InvalidJson { path: None, source } => Error::InvalidJson {
+                path: Some(path.to_owned()),
+                source,
+            },
+            e => e,
+        })
+    }
+
+    /// Parses a json list of qualities, worst first, or the quality prototypes of a
+    /// `data-raw-dump.json` (the whole dump, or just its `quality` section), following each
+    /// quality's `next`.
+    pub fn from_json(json: &str) -> Result<QualityLadder> {
+        match serde_json::from_str::<RawLadder>(json)? {
+            RawLadder::List(tiers) => QualityLadder::new(tiers),
+            RawLadder::Dump { quality } | RawLadder::Qualities(quality) => {
+                QualityLadder::from_prototypes(&quality)
+            }
+        }
+    }
+
+    /// Chains quality prototypes together by their `next`, skipping hidden ones like
+    /// `quality-unknown`.
+    pub(crate) fn from_prototypes(
+        qualities: &HashMap<String, RawQuality>,
+    ) -> Result<QualityLadder> {
+        let next = qualities
+            .iter()
+            .filter(|(_, quality)| !quality.hidden)
+            .map(|(name, quality)| (&name[..], quality.next.as_deref()))
+            .collect::<BTreeMap<_, _>>();
+        let has_previous = next.values().flatten().copied().collect::<HashSet<_>>();
+        let mut worst = next.keys().filter(|name| !has_previous.contains(*name));
+        let mut tier = match (worst.next(), worst.next()) {
+            (Some(worst), None) => Some(*worst),
+            (Some(a), Some(b)) => {
+                return Err(Error::InvalidQualities(format!(
+                    "{a:?} and {b:?} both look like the worst quality"
+                )));
+            }
+            (None, _) if next.is_empty() => None,
+            (None, _) => {
+                return Err(Error::InvalidQualities(
+                    "the qualities go round in a loop".to_owned(),
+                ));
+            }
+        };
+        let mut tiers = Vec::new();
+        while let Some(name) = tier {
+            if tiers.iter().any(|tier| tier == name) {
+                return Err(Error::InvalidQualities(format!(
+                    "the qualities loop back round to {name:?}"
+                )));
+            }
+            tiers.push(name.to_owned());
+            tier = match next.get(name) {
+                Some(next) => *next,
+                None => {
+                    return Err(Error::InvalidQualities(format!(
+                        "{name:?} comes after {:?}, but isn't a quality",
+                        tiers[tiers.len() - 2]
+                    )));
+                }
+            };
+        }
+        if let Some(missing) = next
+            .keys()
+            .find(|name| !tiers.iter().any(|tier| tier == *name))
+        {
+            return Err(Error::InvalidQualities(format!(
+                "{missing:?} isn't reachable from {:?}",
+                tiers[0]
+            )));
+        }
+        QualityLadder::new(tiers)
+    }
+
+    /// The qualities, worst first.
+    pub fn tiers(&self) -> &[String] {
+        &self.tiers
+    }
+
+    /// Position of the quality `name`, if it's on the ladder.
+    pub fn tier(&self, name: &str) -> Option<usize> {
+        self.tiers.iter().position(|tier| tier == name)
+    }
+
+    /// Position of `quality` for sorting: unspecified is the worst quality, and qualities that
+    /// aren't on the ladder come after all of those that are.
+    pub fn rank(&self, quality: &Quality) -> usize {
+        match &quality.0 {
+            None => 0,
+            Some(name) => self.tier(name).unwrap_or(self.tiers.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        assert_eq!(
+            QualityLadder::from_json(r#"["normal", "uncommon", "rare", "epic", "legendary"]"#)
+                .unwrap(),
+            QualityLadder::default()
+        );
+
+        let dump = r#"{
+            "item": {"rail": {"name": "rail"}},
+            "quality": {
+                "quality-unknown": {"name": "quality-unknown", "level": 0, "hidden": true},
+                "shiny": {"name": "shiny", "level": 1},
+                "normal": {"name": "normal", "level": 0, "next": "dull"},
+                "dull": {"name": "dull", "level": 1, "next": "shiny"}
+            }
+        }"#;
+        let ladder = QualityLadder::from_json(dump).unwrap();
+        assert_eq!(ladder.tiers(), ["normal", "dull", "shiny"]);
+        assert_eq!(ladder.rank(&Quality(None)), 0);
+        assert_eq!(ladder.rank(&Quality(Some("shiny".to_owned()))), 2);
+        assert_eq!(ladder.rank(&Quality(Some("legendary".to_owned()))), 3);
+
+        let errors = [
+            r#"[]"#,
+            r#"["normal", "rare", "normal"]"#,
+            r#"{"normal": {"next": "rare"}, "uncommon": {"next": "rare"}, "rare": {}}"#,
+            r#"{"normal": {"next": "uncommon"}, "uncommon": {"next": "normal"}}"#,
+            r#"{"normal": {"next": "uncommon"}}"#,
+            r#"{"normal": {}, "rare": {"next": "epic"}, "epic": {"next": "rare"}}"#,
+        ];
+        for json in errors {
+            let err = QualityLadder::from_json(json).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidQualities(_)),
+                "{json}: unexpected error {err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Expanding counted items into the raw resources they're made from, using the recipes in a
+//! prototype dump.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+};
+
+use serde::Serialize;
+
+use super::count_entities::Counts;
+use crate::{
+    prototypes::Prototypes,
+    table::{TableFormat, format_table},
+};
+
+/// An item (or fluid), how much of it is needed, and what it's made from.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Node {
+    pub name: String,
+    pub amount: f64,
+    /// The recipe used to make it; `None` for raw resources, available items, and anything
+    /// without an obvious recipe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<Node>,
+}
+
+/// The raw cost of a set of items.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RawMaterials {
+    /// How much of each raw resource or available item is needed in total.
+    pub totals: BTreeMap<String, f64>,
+    /// How each counted item breaks down, most needed first.
+    pub items: Vec<Node>,
+}
+
+/// Expands `counts` through their recipes down to raw resources, or to the items in `available`.
+///
+/// Quality is ignored: a legendary module costs the same ingredients as a normal one.
+pub fn expand(
+    counts: &Counts,
+    prototypes: &Prototypes,
+    available: &BTreeSet<String>,
+) -> RawMaterials {
+    let mut by_name = BTreeMap::<&str, f64>::new();
+    for ((name, _quality), count) in counts {
+        *by_name.entry(name).or_default() += *count as f64;
+    }
+    let mut raw = RawMaterials::default();
+    let mut stack = Vec::new();
+    for (name, amount) in by_name {
+        let node = expand_item(
+            name,
+            amount,
+            prototypes,
+            available,
+            &mut stack,
+            &mut raw.totals,
+        );
+        raw.items.push(node);
+    }
+    raw.items.sort_by(|a, b| {
+        b.amount
+            .total_cmp(&a.amount)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    raw
+}
+
+fn expand_item<'a>(
+    name: &'a str,
+    amount: f64,
+    prototypes: &'a Prototypes,
+    available: &BTreeSet<String>,
+    stack: &mut Vec<&'a str>,
+    totals: &mut BTreeMap<String, f64>,
+) -> Node {
+    let recipe = prototypes
+        .recipe_for(name)
+        // Stop at loops, like barrelling, rather than going round forever.
+        .filter(|_| !available.contains(name) && !stack.contains(&name));
+    let Some(recipe) = recipe else {
+        *totals.entry(name.to_owned()).or_default() += amount;
+        return Node {
+            name: name.to_owned(),
+            amount,
+            recipe: None,
+            inputs: Vec::new(),
+        };
+    };
+    let crafts = amount / recipe.amount;
+    stack.push(name);
+    let inputs = recipe
+        .ingredients
+        .iter()
+        .map(|ingredient| {
+            let amount = crafts * ingredient.amount;
+            expand_item(
+                &ingredient.name,
+                amount,
+                prototypes,
+                available,
+                stack,
+                totals,
+            )
+        })
+        .collect();
+    stack.pop();
+    Node {
+        name: name.to_owned(),
+        amount,
+        recipe: Some(recipe.name.clone()),
+        inputs,
+    }
+}
+
+/// Formats an amount as a whole number if it is one, otherwise with up to two decimals.
+pub fn format_amount(amount: f64) -> String {
+    if (amount - amount.round()).abs() < 1e-9 {
+        format!("{}", amount.round())
+    } else {
+        let formatted = format!("{amount:.2}");
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_owned()
+    }
+}
+
+impl Node {
+    fn fmt_tree(&self, depth: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{} {}",
+            "",
+            format_amount(self.amount),
+            self.name,
+            indent = depth * 2
+        )?;
+        for input in &self.inputs {
+            input.fmt_tree(depth + 1, f)?;
+        }
+        Ok(())
+    }
+}
+
+impl RawMaterials {
+    /// The totals, most needed first.
+    fn sorted_totals(&self) -> Vec<(&String, &f64)> {
+        let mut totals = self.totals.iter().collect::<Vec<_>>();
+        totals.sort_by(|a, b| b.1.total_cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        totals
+    }
+
+    /// Formats the totals as a table with name and amount columns.
+    pub fn to_table(&self, format: TableFormat) -> String {
+        let rows = self
+            .sorted_totals()
+            .into_iter()
+            .map(|(name, amount)| vec![name.clone(), format_amount(*amount)])
+            .collect::<Vec<_>>();
+        format_table(format, &["name", "amount"], &rows)
+    }
+}
+
+impl Display for RawMaterials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Raw materials:")?;
+        for (name, amount) in self.sorted_totals() {
+            writeln!(f, "  {} {name}", format_amount(*amount))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Breakdown:")?;
+        for item in &self.items {
+            item.fmt_tree(1, f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blueprint::Quality;
+
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let dump = r#"{
+            "recipe": {
+                "copper-cable": {
+                    "ingredients": [{"type": "item", "name": "copper-plate", "amount": 1}],
+                    "results": [{"type": "item", "name": "copper-cable", "amount": 2}]
+                },
+                "copper-plate": {
+                    "ingredients": [{"type": "item", "name": "copper-ore", "amount": 1}],
+                    "results": [{"type": "item", "name": "copper-plate", "amount": 1}]
+                },
+                "iron-plate": {
+                    "ingredients": [{"type": "item", "name": "iron-ore", "amount": 1}],
+                    "results": [{"type": "item", "name": "iron-plate", "amount": 1}]
+                },
+                "electronic-circuit": {
+                    "ingredients": [
+                        {"type": "item", "name": "iron-plate", "amount": 1},
+                        {"type": "item", "name": "copper-cable", "amount": 3}
+                    ],
+                    "results": [{"type": "item", "name": "electronic-circuit", "amount": 1}]
+                },
+                "small-lamp": {
+                    "ingredients": [
+                        {"type": "item", "name": "electronic-circuit", "amount": 1},
+                        {"type": "item", "name": "copper-cable", "amount": 3},
+                        {"type": "item", "name": "iron-plate", "amount": 1}
+                    ],
+                    "results": [{"type": "item", "name": "small-lamp", "amount": 1}]
+                }
+            },
+            "resource": {
+                "copper-ore": {"minable": {"result": "copper-ore"}},
+                "iron-ore": {"minable": {"result": "iron-ore"}}
+            }
+        }"#;
+        let prototypes = Prototypes::from_dump(dump).unwrap();
+        let counts = [
+            (("small-lamp".to_owned(), Quality(None)), 2),
+            (
+                ("small-lamp".to_owned(), Quality(Some("rare".to_owned()))),
+                1,
+            ),
+            (("modded-thing".to_owned(), Quality(None)), 1),
+        ]
+        .into_iter()
+        .collect();
+
+        let raw = expand(&counts, &prototypes, &BTreeSet::new());
+        assert_eq!(
+            raw.to_string(),
+            "Raw materials:
+  9 copper-ore
+  6 iron-ore
+  1 modded-thing
+
+Breakdown:
+  3 small-lamp
+    3 electronic-circuit
+      3 iron-plate
+        3 iron-ore
+      9 copper-cable
+        4.5 copper-plate
+          4.5 copper-ore
+    9 copper-cable
+      4.5 copper-plate
+        4.5 copper-ore
+    3 iron-plate
+      3 iron-ore
+  1 modded-thing
+"
+        );
+
+        let available = BTreeSet::from(["electronic-circuit".to_owned(), "iron-plate".to_owned()]);
+        let raw = expand(&counts, &prototypes, &available);
+        let totals = raw
+            .totals
+            .iter()
+            .map(|(name, amount)| (&name[..], *amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            totals,
+            [
+                ("copper-ore", 4.5),
+                ("electronic-circuit", 3.0),
+                ("iron-plate", 3.0),
+                ("modded-thing", 1.0)
+            ]
+        );
+    }
+}
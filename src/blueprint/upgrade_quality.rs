@@ -1,20 +1,227 @@
-use crate::json_walk::{WalkAction, walk_json};
-
-const QUALITIES: [&str; 6] = [
-    "normal",
-    "uncommon",
-    "rare",
-    "epic",
-    "legendary",
-    "legendary",
-];
+//! Upgrading the quality of recipes, filters and signals in a blueprint.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::quality_ladder::QualityLadder;
+use crate::{
+    error::{Error, Result},
+    json_walk::{WalkAction, walk_json},
+};
+
+/// How far to move each quality, and which ones to touch.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct UpgradeOptions {
+    /// Sets every quality to this one, instead of moving it by `steps`.
+    pub to: Option<String>,
+    /// How many tiers to move each quality. Qualities stop at the best (or worst) tier.
+    pub steps: usize,
+    /// Moves qualities down instead of up.
+    pub down: bool,
+    /// Only touches qualities that are currently this one.
+    pub from: Option<String>,
+    /// The qualities to move along.
+    pub ladder: QualityLadder,
+    /// Upgrades recipes, filters and signals, and planner mappings and filters. On by default.
+    pub recipes: bool,
+    /// Upgrades the entities themselves.
+    pub entities: bool,
+    /// Upgrades modules, and any other items requested for entities.
+    pub modules: bool,
+    /// Upgrades the icons of blueprints, books and planners.
+    pub icons: bool,
+    /// Paths to handle on top of the ones this module knows about.
+    pub rules: QualityRules,
+}
+
+impl Default for UpgradeOptions {
+    fn default() -> Self {
+        UpgradeOptions {
+            to: None,
+            steps: 1,
+            down: false,
+            from: None,
+            ladder: QualityLadder::default(),
+            recipes: true,
+            entities: false,
+            modules: false,
+            icons: false,
+            rules: QualityRules::default(),
+        }
+    }
+}
+
+impl UpgradeOptions {
+    /// Upgrades everything: recipes, filters, entities, modules and icons.
+    pub fn everything() -> Self {
+        UpgradeOptions {
+            entities: true,
+            modules: true,
+            icons: true,
+            ..UpgradeOptions::default()
+        }
+    }
+}
+
+/// Qualities changed from one to another at a path, and how many times.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Change {
+    /// Where in the json, like `blueprint.entities[].filters[].quality`.
+    pub path: String,
+    pub from: String,
+    pub to: String,
+    pub count: usize,
+}
+
+/// Everything an upgrade changed, by path.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub changes: Vec<Change>,
+}
+
+/// Extra paths for [`upgrade_with_options`], for qualities it doesn't know about yet, like ones
+/// on new or modded entities.
+///
+/// Paths are written like in the [`Summary`], e.g. `blueprint.entities[].some_signal.quality`,
+/// and `*` matches any one key. They apply inside books too.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityRules {
+    /// Qualities to upgrade, like recipes and filters.
+    #[serde(default)]
+    pub upgrade: Vec<String>,
+    /// Keys that look like qualities but aren't; what's inside them is still checked.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Qualities to leave alone.
+    #[serde(default)]
+    pub no_upgrade: Vec<String>,
+}
+
+/// What to do with the quality at a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    Upgrade,
+    Ignore,
+    NoUpgrade,
+}
 
-fn upgrade_quality(quality: &mut String) {
-    let new_quality = QUALITIES
-        .windows(2)
-        .find(|pair| pair[0] == quality)
-        .unwrap_or_else(|| panic!("can't find quality {quality}"))[1];
-    *quality = new_quality.to_owned();
+impl QualityRules {
+    /// Reads a rules file.
+    pub fn load(path: &Path) -> Result<QualityRules> {
+        let json = fs::read_to_string(path).map_err(Error::io(path))?;
+        serde_json::from_str(&json).map_err(|source| Error::InvalidJson {
+            path: Some(path.to_owned()),
+            source,
+        })
+    }
+
+    /// Writes the rules to a file, replacing it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n").map_err(Error::io(path))
+    }
+
+    /// Adds `pattern` to the paths handled by `rule`.
+    pub fn add(&mut self, rule: Rule, pattern: String) {
+        let patterns = match rule {
+            Rule::Upgrade => &mut self.upgrade,
+            Rule::Ignore => &mut self.ignore,
+            Rule::NoUpgrade => &mut self.no_upgrade,
+        };
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+
+    /// The rule for `path`, if any pattern matches it. `path` has its books stripped.
+    fn rule(&self, path: &[&str]) -> Option<Rule> {
+        [
+            (&self.upgrade, Rule::Upgrade),
+            (&self.ignore, Rule::Ignore),
+            (&self.no_upgrade, Rule::NoUpgrade),
+        ]
+        .into_iter()
+        .find(|(patterns, _)| patterns.iter().any(|pattern| matches(pattern, path)))
+        .map(|(_, rule)| rule)
+    }
+}
+
+/// The segments of a rules pattern: `a.b[].c` is `a`, `b`, `[]`, `c`.
+fn pattern_segments(pattern: &str) -> impl Iterator<Item = &str> {
+    pattern.split('.').flat_map(|part| {
+        let key = part.trim_end_matches("[]");
+        let arrays = (part.len() - key.len()) / 2;
+        (!key.is_empty())
+            .then_some(key)
+            .into_iter()
+            .chain(std::iter::repeat_n("[]", arrays))
+    })
+}
+
+fn matches(pattern: &str, path: &[&str]) -> bool {
+    let mut segments = pattern_segments(pattern);
+    let mut path = path.iter();
+    loop {
+        match (segments.next(), path.next()) {
+            (None, None) => return true,
+            (Some(segment), Some(key)) if segment == "*" || segment == *key => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Writes an unhandled quality's path (from [`Error::UnhandledQuality`]) as a rules pattern.
+pub fn rule_pattern(path: &[String]) -> String {
+    let path = path.iter().map(String::as_str).collect::<Vec<_>>();
+    format_path(within_book(&path))
+}
+
+fn tier(ladder: &QualityLadder, quality: &str) -> Result<usize> {
+    ladder
+        .tier(quality)
+        .ok_or_else(|| Error::UnknownQuality(quality.to_owned()))
+}
+
+/// The quality `quality` becomes, or `None` if `options` leave it alone.
+fn upgrade_quality<'a>(quality: &str, options: &'a UpgradeOptions) -> Result<Option<&'a str>> {
+    if options.from.as_ref().is_some_and(|from| from != quality) {
+        return Ok(None);
+    }
+    let tiers = options.ladder.tiers();
+    let current = tier(&options.ladder, quality)?;
+    let new = match &options.to {
+        Some(to) => tier(&options.ladder, to)?,
+        None if options.down => current.saturating_sub(options.steps),
+        None => current.saturating_add(options.steps).min(tiers.len() - 1),
+    };
+    Ok((new != current).then_some(&tiers[new]))
+}
+
+/// Strips the books from a path, so the paths above match blueprints and planners in books.
+fn within_book<'a, 'b>(mut path: &'a [&'b str]) -> &'a [&'b str] {
+    while let ["blueprint_book", "blueprints", "[]", rest @ ..] = path {
+        path = rest;
+    }
+    path
+}
+
+fn format_path(path: &[&str]) -> String {
+    let mut formatted = String::new();
+    for segment in path {
+        if !formatted.is_empty() && *segment != "[]" {
+            formatted.push('.');
+        }
+        formatted.push_str(segment);
+    }
+    formatted
 }
 
 #[rustfmt::skip]
@@ -50,6 +257,12 @@ const UPGRADE_PATHS: &[&[&str]] = &[
     // selector combinator quality filter destination signal
     &["blueprint", "entities", "[]", "control_behavior", "quality_destination_signal", "quality"],
 
+    // upgrade planner mappings, so a planner for one tier becomes one for the next
+    &["upgrade_planner", "settings", "mappers", "[]", "from", "quality"],
+    &["upgrade_planner", "settings", "mappers", "[]", "to", "quality"],
+    // deconstruction planner filters
+    &["deconstruction_planner", "settings", "entity_filters", "[]", "quality"],
+
 
 ];
 
@@ -65,42 +278,185 @@ const IGNORE_PATHS: &[&[&str]] = &[
     &["blueprint", "entities", "[]", "control_behavior", "quality_source_signal"],
 ];
 
+/// Parts of a blueprint that are only upgraded when asked for.
+#[derive(Clone, Copy)]
+enum Part {
+    Entities,
+    Modules,
+    Icons,
+}
+
+impl Part {
+    fn enabled(self, options: &UpgradeOptions) -> bool {
+        match self {
+            Part::Entities => options.entities,
+            Part::Modules => options.modules,
+            Part::Icons => options.icons,
+        }
+    }
+}
+
+/// Objects whose `quality` is upgraded from the object rather than from the quality itself,
+/// because normal quality is left out.
+#[rustfmt::skip]
+const QUALITY_OBJECT_PATHS: &[(Part, &[&str])] = &[
+    (Part::Entities, &["blueprint", "entities", "[]"]),
+    (Part::Modules, &["blueprint", "entities", "[]", "items", "[]", "id"]),
+    // Usually icons match entities not recipes, although there's really no way to know
+    (Part::Icons, &["blueprint", "icons", "[]", "signal"]),
+    (Part::Icons, &["blueprint_book", "icons", "[]", "signal"]),
+    (Part::Icons, &["upgrade_planner", "settings", "icons", "[]", "signal"]),
+    (Part::Icons, &["deconstruction_planner", "settings", "icons", "[]", "signal"]),
+];
+
 #[rustfmt::skip]
 const NO_UPGRADE_PATHS: &[&[&str]] = &[
-    // Don't upgrade modules
-    &["blueprint", "entities", "[]", "items", "[]", "id", "quality"],
-    // Don't upgrade entities
-    &["blueprint", "entities", "[]", "quality"],
-    // Usually icons match entities not recipes, although there's really no way to know
-    &["blueprint", "icons", "[]", "signal", "quality"],
     // Just a boolean picking between quality_source_signal and quality_source_static
     &["blueprint", "entities", "[]", "control_behavior", "select_quality_from_signal"],
 ];
 
-pub(crate) fn upgrade(mut json: serde_json::Value) -> serde_json::Value {
-    walk_json(&mut json, &mut |path, value| {
-        if UPGRADE_PATHS.contains(&path) {
+type Changes<'a> = BTreeMap<(String, String, &'a str), usize>;
+
+/// Upgrades the `quality` of an object from [`QUALITY_OBJECT_PATHS`], treating a missing one as
+/// normal.
+fn upgrade_object<'a>(
+    value: &mut serde_json::Value,
+    path: &[&str],
+    options: &'a UpgradeOptions,
+    changes: &mut Changes<'a>,
+) -> Result<()> {
+    let serde_json::Value::Object(object) = value else {
+        return Err(Error::InvalidBlueprint(format!(
+            "expected an object at {path:?}, got {value}"
+        )));
+    };
+    let quality = match object.get("quality") {
+        None => "normal",
+        Some(serde_json::Value::String(quality)) => quality,
+        Some(other) => {
+            return Err(Error::InvalidBlueprint(format!(
+                "can't upgrade quality at {path:?}, expected string, got {other}"
+            )));
+        }
+    };
+    if let Some(new_quality) = upgrade_quality(quality, options)? {
+        let key = (
+            format!("{}.quality", format_path(path)),
+            quality.to_owned(),
+            new_quality,
+        );
+        *changes.entry(key).or_default() += 1;
+        object.insert("quality".to_owned(), new_quality.into());
+    }
+    Ok(())
+}
+
+/// Bumps the quality of recipes, filters and signals in a blueprint by one tier.
+///
+/// Books are upgraded all the way through, along with the mappings in upgrade planners and the
+/// filters in deconstruction planners. Entities, modules and icons are left alone. Errors if it
+/// finds something that looks like a quality but isn't in one of the known paths, or in the
+/// [`QualityRules`] of [`upgrade_with_options`].
+pub fn upgrade(json: serde_json::Value) -> Result<serde_json::Value> {
+    upgrade_with_options(json, &UpgradeOptions::default()).map(|(json, _summary)| json)
+}
+
+/// Like [`upgrade`], but moves qualities as `options` say, and reports what it changed.
+pub fn upgrade_with_options(
+    mut json: serde_json::Value,
+    options: &UpgradeOptions,
+) -> Result<(serde_json::Value, Summary)> {
+    for quality in options.to.iter().chain(&options.from) {
+        tier(&options.ladder, quality)?;
+    }
+    let mut error = None;
+    let mut changes = Changes::new();
+    walk_json(&mut json, &mut |full_path, value| {
+        let path = within_book(full_path);
+        let object_part = QUALITY_OBJECT_PATHS
+            .iter()
+            .find(|(_, object_path)| *object_path == path)
+            .map(|(part, _)| *part);
+        let rule = || options.rules.rule(path);
+        if error.is_some() {
+            WalkAction::Break
+        } else if let Some(part) = object_part {
+            if part.enabled(options) {
+                error = upgrade_object(value, full_path, options, &mut changes).err();
+            }
+            WalkAction::Enter
+        } else if let Some((&"quality", object_path)) = path.split_last()
+            && QUALITY_OBJECT_PATHS
+                .iter()
+                .any(|(_, path)| *path == object_path)
+        {
+            // Handled with the object, above.
+            WalkAction::Break
+        } else if UPGRADE_PATHS.contains(&path) || rule() == Some(Rule::Upgrade) {
+            if !options.recipes {
+                return WalkAction::Break;
+            }
             let serde_json::Value::String(s) = value else {
-                panic!("can't upgrade quality at {path:?}, expected string, got {value}");
+                error = Some(Error::InvalidBlueprint(format!(
+                    "can't upgrade quality at {path:?}, expected string, got {value}"
+                )));
+                return WalkAction::Break;
             };
-            upgrade_quality(s);
+            match upgrade_quality(s, options) {
+                Ok(Some(new_quality)) => {
+                    let from = std::mem::replace(s, new_quality.to_owned());
+                    *changes
+                        .entry((format_path(full_path), from, new_quality))
+                        .or_default() += 1;
+                }
+                Ok(None) => {}
+                Err(e) => error = Some(e),
+            }
             WalkAction::Enter
-        } else if IGNORE_PATHS.contains(&path) {
+        } else if IGNORE_PATHS.contains(&path) || rule() == Some(Rule::Ignore) {
             WalkAction::Enter
-        } else if NO_UPGRADE_PATHS.contains(&path) {
+        } else if NO_UPGRADE_PATHS.contains(&path) || rule() == Some(Rule::NoUpgrade) {
             WalkAction::Break
         } else if let Some(last) = path.last()
             && last.contains("quality")
         {
-            panic!(
-                "Unhandled quality in blueprint; not sure whether we should upgrade:\n &{path:?},"
-            );
+            error = Some(Error::UnhandledQuality(
+                full_path.iter().map(|&s| s.to_owned()).collect(),
+            ));
+            WalkAction::Break
         } else {
             WalkAction::Enter
         }
     });
-    // upgrade_old(json)
-    json
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let changes = changes
+        .into_iter()
+        .map(|((path, from, to), count)| Change {
+            path,
+            from,
+            to: to.to_owned(),
+            count,
+        })
+        .collect();
+    Ok((json, Summary { changes }))
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no qualities changed");
+        }
+        for change in &self.changes {
+            writeln!(
+                f,
+                "{}: {} -> {} ({})",
+                change.path, change.from, change.to, change.count
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -111,7 +467,7 @@ mod tests {
 
     macro_rules! test_bp {
         {bp: $bp: literal, $json: expr} => {{
-            let json = crate::blueprint::blueprint_to_json($bp);
+            let json = crate::blueprint::blueprint_to_json($bp).unwrap();
             let json = serde_json::Value::from_str(&json).unwrap();
             let expected: serde_json::Value = $json;
             if json != expected {
@@ -221,7 +577,7 @@ mod tests {
               }
             })
         };
-        let upgraded = upgrade(json);
+        let upgraded = upgrade(json).unwrap();
         let entity = jaq_one(".blueprint.entities[]", upgraded);
         assert_eq!(
             jaq_one("[.filters[].quality]", entity),
@@ -295,7 +651,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let inserter = jaq_one(
             r#".blueprint.entities[] | select(.name == "bulk-inserter")"#,
             upgraded,
@@ -360,7 +716,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let inserter = jaq_one(r#".blueprint.entities[]"#, upgraded);
         assert_eq!(
             jaq_one(".control_behavior.logistic_condition", inserter),
@@ -413,7 +769,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let splitter = jaq_one(r#".blueprint.entities[]"#, upgraded);
         assert_eq!(jaq_one(".filter.quality", splitter), json!("epic"));
     }
@@ -452,7 +808,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let splitter = jaq_one(r#".blueprint.entities[]"#, upgraded);
         assert_eq!(jaq_one(".filter.quality", splitter), json!("epic"));
     }
@@ -490,7 +846,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let assembler = jaq_one(r#".blueprint.entities[]"#, upgraded);
         // assembler quality should stay the same
         assert_eq!(jaq_one(".quality", assembler.clone()), json!("uncommon"));
@@ -543,7 +899,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let chest = jaq_one(r#".blueprint.entities[]"#, upgraded);
         assert_eq!(
             jaq_one(".request_filters.sections[].filters[].quality", chest),
@@ -688,7 +1044,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let serde_json::Value::Array(chests) = jaq_one(r#"[.blueprint.entities[]]"#, upgraded)
         else {
             panic!("expected array")
@@ -783,7 +1139,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let constant_combinator = jaq_one(r#".blueprint.entities[]"#, upgraded);
         eprintln!("{constant_combinator:#}");
         assert_eq!(
@@ -843,7 +1199,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let combinator = jaq_one(r#".blueprint.entities[]"#, upgraded);
         let signals = jaq_one(
             ".control_behavior.arithmetic_conditions | {first_signal, second_signal, output_signal}",
@@ -951,7 +1307,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let combinator = jaq_one(r#".blueprint.entities[]"#, upgraded);
         let conditions = jaq_one(
             ".control_behavior.decider_conditions.conditions",
@@ -1078,7 +1434,7 @@ mod tests {
               }
             })
         );
-        let upgraded = upgrade(bp);
+        let upgraded = upgrade(bp).unwrap();
         let select_combinator = jaq_one(
             r#".blueprint.entities[] | select(.control_behavior.operation == "select")"#,
             upgraded.clone(),
@@ -1130,13 +1486,279 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unhandled_quality() {
+        let bp = json!({
+            "blueprint": {
+                "entities": [{"name": "mystery", "mystery_quality": "rare"}],
+            }
+        });
+        let err = upgrade(bp).unwrap_err();
+        assert!(
+            matches!(&err, Error::UnhandledQuality(path) if path == &["blueprint", "entities", "[]", "mystery_quality"]),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let bp = || {
+            json!({
+                "blueprint": {
+                    "entities": [
+                        {"name": "assembling-machine-2", "recipe": "gear", "recipe_quality": "rare"},
+                        {"name": "filter-inserter", "filters": [
+                            {"index": 1, "name": "iron-plate", "quality": "normal"},
+                            {"index": 2, "name": "iron-plate", "quality": "legendary"},
+                        ]},
+                    ],
+                }
+            })
+        };
+        let qualities = |json: &serde_json::Value| {
+            jaq_one(
+                "[.blueprint.entities[0].recipe_quality, .blueprint.entities[1].filters[].quality]",
+                json.clone(),
+            )
+        };
+
+        let options = UpgradeOptions {
+            steps: 2,
+            ..Default::default()
+        };
+        let (upgraded, summary) = upgrade_with_options(bp(), &options).unwrap();
+        assert_eq!(
+            qualities(&upgraded),
+            json!(["legendary", "rare", "legendary"])
+        );
+        assert_eq!(
+            summary.to_string(),
+            "\
+blueprint.entities[].filters[].quality: normal -> rare (1)
+blueprint.entities[].recipe_quality: rare -> legendary (1)
+"
+        );
+
+        let options = UpgradeOptions {
+            down: true,
+            ..Default::default()
+        };
+        let (downgraded, _) = upgrade_with_options(bp(), &options).unwrap();
+        assert_eq!(
+            qualities(&downgraded),
+            json!(["uncommon", "normal", "epic"])
+        );
+
+        let options = UpgradeOptions {
+            to: Some("rare".to_owned()),
+            from: Some("legendary".to_owned()),
+            ..Default::default()
+        };
+        let (changed, summary) = upgrade_with_options(bp(), &options).unwrap();
+        assert_eq!(qualities(&changed), json!(["rare", "normal", "rare"]));
+        assert_eq!(summary.changes.len(), 1);
+
+        let options = UpgradeOptions {
+            ladder: QualityLadder::new(
+                ["normal", "rare", "mythic", "legendary"]
+                    .map(str::to_owned)
+                    .to_vec(),
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+        let (upgraded, _) = upgrade_with_options(bp(), &options).unwrap();
+        assert_eq!(qualities(&upgraded), json!(["mythic", "rare", "legendary"]));
+
+        let options = UpgradeOptions {
+            to: Some("mythic".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            upgrade_with_options(bp(), &options),
+            Err(Error::UnknownQuality(quality)) if quality == "mythic"
+        ));
+    }
+
+    #[test]
+    fn test_book_and_planners() {
+        let book = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "icons": [{"index": 1, "signal": {"name": "assembling-machine-2", "quality": "rare"}}],
+                "blueprints": [
+                    {"index": 0, "blueprint": {
+                        "item": "blueprint",
+                        "entities": [{"name": "assembling-machine-2", "recipe": "gear", "recipe_quality": "rare"}],
+                    }},
+                    {"index": 1, "blueprint_book": {
+                        "item": "blueprint-book",
+                        "blueprints": [
+                            {"index": 0, "upgrade_planner": {
+                                "item": "upgrade-planner",
+                                "settings": {"mappers": [{
+                                    "from": {"type": "entity", "name": "transport-belt", "quality": "rare", "comparator": "="},
+                                    "to": {"type": "entity", "name": "fast-transport-belt", "quality": "rare"},
+                                    "index": 0,
+                                }]},
+                            }},
+                            {"index": 1, "deconstruction_planner": {
+                                "item": "deconstruction-planner",
+                                "settings": {
+                                    "entity_filters": [{"name": "bulk-inserter", "quality": "rare", "comparator": "=", "index": 0}],
+                                    "tile_filters": [{"name": "landfill", "index": 0}],
+                                },
+                            }},
+                        ],
+                    }},
+                ],
+            }
+        });
+        let (upgraded, summary) = upgrade_with_options(book, &UpgradeOptions::default()).unwrap();
+        assert_eq!(
+            jaq_one(
+                "[.. | objects | .quality?, .recipe_quality? | strings]",
+                upgraded
+            ),
+            json!(["rare", "epic", "epic", "epic", "epic"])
+        );
+        assert_eq!(
+            summary.to_string(),
+            "\
+blueprint_book.blueprints[].blueprint.entities[].recipe_quality: rare -> epic (1)
+blueprint_book.blueprints[].blueprint_book.blueprints[].deconstruction_planner.settings.entity_filters[].quality: rare -> epic (1)
+blueprint_book.blueprints[].blueprint_book.blueprints[].upgrade_planner.settings.mappers[].from.quality: rare -> epic (1)
+blueprint_book.blueprints[].blueprint_book.blueprints[].upgrade_planner.settings.mappers[].to.quality: rare -> epic (1)
+"
+        );
+
+        let book = json!({
+            "blueprint_book": {
+                "blueprints": [{"index": 0, "blueprint": {"entities": [{"mystery_quality": "rare"}]}}],
+            }
+        });
+        assert!(matches!(
+            upgrade(book),
+            Err(Error::UnhandledQuality(path)) if path.len() == 7
+        ));
+    }
+
+    #[test]
+    fn test_parts() {
+        let bp = || {
+            json!({
+                "blueprint": {
+                    "icons": [{"index": 1, "signal": {"name": "assembling-machine-3"}}],
+                    "entities": [
+                        {
+                            "name": "assembling-machine-3",
+                            "recipe": "gear",
+                            "recipe_quality": "uncommon",
+                            "items": [{"id": {"name": "speed-module"}, "items": {"in_inventory": [{"inventory": 4, "stack": 0}]}}],
+                        },
+                        {"name": "assembling-machine-3", "quality": "rare"},
+                    ],
+                }
+            })
+        };
+        let qualities = |json: serde_json::Value| {
+            jaq_one(
+                "[.blueprint.icons[0].signal.quality, .blueprint.entities[0].recipe_quality,
+                  (.blueprint.entities[] | .quality), .blueprint.entities[0].items[0].id.quality]",
+                json,
+            )
+        };
+
+        let (upgraded, _) = upgrade_with_options(bp(), &UpgradeOptions::default()).unwrap();
+        assert_eq!(
+            qualities(upgraded),
+            json!([null, "rare", null, "rare", null])
+        );
+
+        let (upgraded, summary) =
+            upgrade_with_options(bp(), &UpgradeOptions::everything()).unwrap();
+        assert_eq!(
+            qualities(upgraded),
+            json!(["uncommon", "rare", "uncommon", "epic", "uncommon"])
+        );
+        assert_eq!(
+            summary.to_string(),
+            "\
+blueprint.entities[].items[].id.quality: normal -> uncommon (1)
+blueprint.entities[].quality: normal -> uncommon (1)
+blueprint.entities[].quality: rare -> epic (1)
+blueprint.entities[].recipe_quality: uncommon -> rare (1)
+blueprint.icons[].signal.quality: normal -> uncommon (1)
+"
+        );
+
+        let mut options = UpgradeOptions::everything();
+        options.recipes = false;
+        options.modules = false;
+        options.from = Some("rare".to_owned());
+        let (upgraded, _) = upgrade_with_options(bp(), &options).unwrap();
+        assert_eq!(
+            qualities(upgraded),
+            json!([null, "uncommon", null, "epic", null])
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let book = || {
+            json!({
+                "blueprint_book": {
+                    "blueprints": [{"index": 0, "blueprint": {"entities": [{
+                        "name": "modded-machine",
+                        "mystery_quality": "rare",
+                        "quality_settings": {"fuel": {"quality": "rare"}},
+                    }]}}],
+                }
+            })
+        };
+        let Err(Error::UnhandledQuality(path)) = upgrade(book()) else {
+            panic!("expected an unhandled quality");
+        };
+        assert_eq!(rule_pattern(&path), "blueprint.entities[].mystery_quality");
+
+        let mut options = UpgradeOptions {
+            rules: serde_json::from_value(json!({
+                "upgrade": ["blueprint.entities[].mystery_quality"],
+                "ignore": ["blueprint.entities[].quality_settings"],
+            }))
+            .unwrap(),
+            ..Default::default()
+        };
+        options.rules.add(
+            Rule::NoUpgrade,
+            "blueprint.entities[].quality_settings.*.quality".to_owned(),
+        );
+        let (upgraded, _) = upgrade_with_options(book(), &options).unwrap();
+        assert_eq!(
+            jaq_one(
+                ".blueprint_book.blueprints[0].blueprint.entities[0] | [.mystery_quality, .quality_settings.fuel.quality]",
+                upgraded
+            ),
+            json!(["epic", "rare"])
+        );
+        assert_eq!(
+            serde_json::to_value(&options.rules).unwrap(),
+            json!({
+                "upgrade": ["blueprint.entities[].mystery_quality"],
+                "ignore": ["blueprint.entities[].quality_settings"],
+                "no_upgrade": ["blueprint.entities[].quality_settings.*.quality"],
+            })
+        );
+        assert!(serde_json::from_value::<QualityRules>(json!({"no-upgrade": []})).is_err());
+    }
+
     // #[test]
     // fn test_template() {
     //     let bp = test_bp!(
     //         bp: "0eAGrrgUAAXUA+Q==",
     //         json!({})
     //     );
-    //     let upgraded = upgrade(bp);
+    //     let upgraded = upgrade(bp).unwrap();
     //     let entity = jaq_one(r#".blueprint.entities[]"#, upgraded);
     // }
 }
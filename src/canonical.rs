@@ -0,0 +1,390 @@
+//! A canonical, diff-friendly form of blueprint json.
+//!
+//! Canonical json has:
+//! - object keys sorted,
+//! - entities ordered by position, with `entity_number` and everything that refers to it (wires,
+//!   1.1 `neighbours` and `connections`, train schedules and `stock_connections`) renumbered to
+//!   match,
+//! - tiles ordered by position,
+//! - whole-number floats written as integers,
+//! - one line per entity, tile and wire.
+//!
+//! so that a small change in game makes a small change in the json.
+
+use std::{cmp::Ordering, collections::HashMap, fmt::Write};
+
+use serde_json::{Number, Value};
+
+use crate::{
+    blueprint::BlueprintType,
+    error::{Error, Result},
+};
+
+/// Arrays whose elements are written one per line.
+const ONE_PER_LINE: &[&str] = &["entities", "tiles", "wires"];
+
+/// Puts a blueprint (or every blueprint in a book) into canonical form.
+pub fn canonicalize(json: &mut Value) -> Result<()> {
+    normalize_numbers(json);
+    canonicalize_entry(json)?;
+    json.sort_all_objects();
+    Ok(())
+}
+
+/// Canonicalizes `json` and formats it in the canonical layout.
+pub fn to_canonical_string(mut json: Value) -> Result<String> {
+    canonicalize(&mut json)?;
+    Ok(format_canonical(&json))
+}
+
+fn canonicalize_entry(json: &mut Value) -> Result<()> {
+    match BlueprintType::<&mut Value>::new(json)? {
+        BlueprintType::Blueprint(blueprint) => sort_blueprint(blueprint),
+        BlueprintType::BlueprintBook(book) => {
+            if let Some(blueprints) = book.get_mut("blueprints").and_then(Value::as_array_mut) {
+                for entry in blueprints {
+                    canonicalize_entry(entry)?;
+                }
+            }
+            Ok(())
+        }
+        BlueprintType::UpgradePlanner(_) | BlueprintType::DeconstructionPlanner(_) => Ok(()),
+    }
+}
+
+fn position(json: &Value) -> (f64, f64) {
+    let coordinate = |key| {
+        json.get("position")
+            .and_then(|position| position.get(key))
+            .and_then(Value::as_f64)
+            .unwrap_or_default()
+    };
+    (coordinate("y"), coordinate("x"))
+}
+
+/// Orders by position (top to bottom, then left to right), then by name.
+fn compare_placed(a: &Value, b: &Value) -> Ordering {
+    let (a_y, a_x) = position(a);
+    let (b_y, b_x) = position(b);
+    a_y.total_cmp(&b_y).then(a_x.total_cmp(&b_x)).then_with(|| {
+        let name = |json: &Value| json.get("name").and_then(Value::as_str).map(str::to_owned);
+        name(a).cmp(&name(b))
+    })
+}
+
+fn sort_blueprint(blueprint: &mut Value) -> Result<()> {
+    let invalid = |reason: &str| Error::InvalidBlueprint(reason.to_owned());
+    if let Some(tiles) = blueprint.get_mut("tiles").and_then(Value::as_array_mut) {
+        tiles.sort_by(compare_placed);
+    }
+    let Some(entities) = blueprint.get_mut("entities").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    // sort_by is stable, so entities at the same position with the same name keep their order.
+    entities.sort_by(compare_placed);
+    let mut renumber = HashMap::new();
+    for (new_number, entity) in (1u64..).zip(entities.iter_mut()) {
+        let old_number = entity
+            .get("entity_number")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid("entity missing entity_number"))?;
+        renumber.insert(old_number, new_number);
+        entity["entity_number"] = Value::from(new_number);
+    }
+    let renumbered = |old: &Value| -> Result<Value> {
+        old.as_u64()
+            .and_then(|old| renumber.get(&old))
+            .map(|&new| Value::from(new))
+            .ok_or_else(|| Error::InvalidBlueprint(format!("reference to unknown entity {old}")))
+    };
+
+    for entity in entities.iter_mut() {
+        // 1.1 copper wires
+        if let Some(neighbours) = entity.get_mut("neighbours").and_then(Value::as_array_mut) {
+            for neighbour in neighbours {
+                *neighbour = renumbered(neighbour)?;
+            }
+        }
+        // 1.1 circuit and power switch wires: `{"1": {"red": [{"entity_id": 2}]}}` and
+        // `{"Cu0": [{"entity_id": 2, "wire_id": 0}]}`
+        if let Some(connections) = entity.get_mut("connections").and_then(Value::as_object_mut) {
+            for connection in connections.values_mut() {
+                let targets = match connection {
+                    Value::Array(targets) => vec![targets],
+                    Value::Object(colors) => colors
+                        .values_mut()
+                        .filter_map(Value::as_array_mut)
+                        .collect(),
+                    _ => vec![],
+                };
+                for target in targets.into_iter().flatten() {
+                    if let Some(entity_id) = target.get_mut("entity_id") {
+                        *entity_id = renumbered(entity_id)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(wires) = blueprint.get_mut("wires").and_then(Value::as_array_mut) {
+        for wire in wires.iter_mut() {
+            let Some([a, a_connector, b, b_connector]) = wire
+                .as_array_mut()
+                .and_then(|wire| <&mut [Value; 4]>::try_from(&mut wire[..]).ok())
+            else {
+                return Err(Error::InvalidBlueprint(format!("invalid wire {wire}")));
+            };
+            *a = renumbered(a)?;
+            *b = renumbered(b)?;
+            // Wires aren't directional, so put the lower end first.
+            let key = |entity: &Value, connector: &Value| (entity.as_u64(), connector.as_u64());
+            if key(a, a_connector) > key(b, b_connector) {
+                std::mem::swap(a, b);
+                std::mem::swap(a_connector, b_connector);
+            }
+        }
+        let wire_key = |wire: &Value| {
+            wire.as_array()
+                .map(|wire| wire.iter().map(Value::as_u64).collect::<Vec<_>>())
+        };
+        wires.sort_by_key(wire_key);
+    }
+
+    if let Some(schedules) = blueprint.get_mut("schedules").and_then(Value::as_array_mut) {
+        for schedule in schedules {
+            if let Some(locomotives) = schedule
+                .get_mut("locomotives")
+                .and_then(Value::as_array_mut)
+            {
+                for locomotive in locomotives {
+                    *locomotive = renumbered(locomotive)?;
+                }
+            }
+        }
+    }
+
+    if let Some(stock_connections) = blueprint
+        .get_mut("stock_connections")
+        .and_then(Value::as_array_mut)
+    {
+        for connection in stock_connections.iter_mut() {
+            for key in ["stock", "front", "back"] {
+                if let Some(stock) = connection.get_mut(key) {
+                    *stock = renumbered(stock)?;
+                }
+            }
+        }
+        let stock = |connection: &Value| connection.get("stock").and_then(Value::as_u64);
+        stock_connections.sort_by_key(stock);
+    }
+    Ok(())
+}
+
+/// Writes whole-number floats as integers, so that `2.0` and `2` compare equal.
+pub(crate) fn normalize_numbers(json: &mut Value) {
+    match json {
+        Value::Number(number) => {
+            if let Some(float) = number.as_f64()
+                && number.is_f64()
+                && float.fract() == 0.0
+                && float.abs() < 2f64.powi(53)
+            {
+                *number = Number::from(float as i64);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(normalize_numbers),
+        Value::Object(map) => map.values_mut().for_each(normalize_numbers),
+        Value::Null | Value::Bool(_) | Value::String(_) => {}
+    }
+}
+
+/// Our and their versions of a field or array element that conflicted in a merge; `None` for a
+/// side that removed it.
+pub(crate) type Sides = (Option<Value>, Option<Value>);
+
+/// Looks up whether the field or array element at `path` conflicted, given the keys leading to
+/// it (`"[]"` for an array element) and its merged value.
+pub(crate) type FindConflict<'a> = &'a dyn Fn(&[String], &Value) -> Option<Sides>;
+
+/// Formats like `serde_json::to_string_pretty`, except that the elements of `entities`, `tiles`
+/// and `wires` arrays are each written compactly on a single line.
+pub fn format_canonical(json: &Value) -> String {
+    format_canonical_with_conflicts(json, &|_, _| None)
+}
+
+/// Formats like [`format_canonical`], but writes each field or array element that `conflict`
+/// finds as git-style conflict markers around our and their versions of it.
+pub(crate) fn format_canonical_with_conflicts(json: &Value, conflict: FindConflict) -> String {
+    let mut writer = Writer {
+        out: String::new(),
+        path: vec![],
+        conflict,
+    };
+    writer.value(json, 0, false);
+    writer.out.push('\n');
+    writer.out
+}
+
+struct Writer<'a> {
+    out: String,
+    /// The keys leading to the value being written, with `"[]"` for array elements.
+    path: Vec<String>,
+    conflict: FindConflict<'a>,
+}
+
+impl Writer<'_> {
+    fn indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn field(&mut self, key: &str, value: &Value, depth: usize) {
+        write!(self.out, "{}: ", Value::String(key.to_owned()))
+            .expect("writing to a String can't fail");
+        self.value(value, depth, ONE_PER_LINE.contains(&key));
+    }
+
+    fn value(&mut self, json: &Value, depth: usize, one_per_line: bool) {
+        match json {
+            Value::Array(values) if !values.is_empty() => {
+                self.out.push_str("[\n");
+                self.path.push("[]".to_owned());
+                for (i, value) in values.iter().enumerate() {
+                    let comma = if i + 1 < values.len() { "," } else { "" };
+                    self.line(value, depth + 1, comma, |writer, value| {
+                        if one_per_line {
+                            writer.out.push_str(&value.to_string());
+                        } else {
+                            writer.value(value, depth + 1, false);
+                        }
+                    });
+                }
+                self.path.pop();
+                self.indent(depth);
+                self.out.push(']');
+            }
+            Value::Object(map) if !map.is_empty() => {
+                self.out.push_str("{\n");
+                for (i, (key, value)) in map.iter().enumerate() {
+                    let comma = if i + 1 < map.len() { "," } else { "" };
+                    self.path.push(key.clone());
+                    self.line(value, depth + 1, comma, |writer, value| {
+                        writer.field(key, value, depth + 1);
+                    });
+                    self.path.pop();
+                }
+                self.indent(depth);
+                self.out.push('}');
+            }
+            _ => self.out.push_str(&json.to_string()),
+        }
+    }
+
+    /// Writes a field or array element with `write`, on its own line or lines.
+    fn line(
+        &mut self,
+        value: &Value,
+        depth: usize,
+        comma: &str,
+        write: impl Fn(&mut Writer, &Value),
+    ) {
+        let Some((ours, theirs)) = (self.conflict)(&self.path, value) else {
+            self.indent(depth);
+            write(self, value);
+            self.out.push_str(comma);
+            self.out.push('\n');
+            return;
+        };
+        let mut side = |marker: &str, value: Option<Value>| {
+            self.out.push_str(marker);
+            if let Some(value) = value {
+                // Nothing inside a conflict conflicts again.
+                let mut writer = Writer {
+                    out: String::new(),
+                    path: self.path.clone(),
+                    conflict: &|_, _| None,
+                };
+                writer.indent(depth);
+                write(&mut writer, &value);
+                self.out.push_str(&writer.out);
+                self.out.push_str(comma);
+                self.out.push('\n');
+            }
+        };
+        side("<<<<<<< ours\n", ours);
+        side("=======\n", theirs);
+        self.out.push_str(">>>>>>> theirs\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let json = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [
+                    {"entity_number": 1, "name": "locomotive", "position": {"x": 5.0, "y": 3}},
+                    {"entity_number": 2, "name": "small-lamp", "position": {"x": 0.5, "y": -1.5}},
+                    {"entity_number": 3, "name": "small-lamp", "position": {"x": -0.5, "y": -1.5}},
+                ],
+                "wires": [[2, 1, 1, 1], [3, 2, 2, 2]],
+                "schedules": [{"locomotives": [1], "schedule": {"records": []}}],
+                "version": 562949957353472u64,
+            }
+        });
+        assert_eq!(
+            to_canonical_string(json).unwrap(),
+            r#"{
+  "blueprint": {
+    "entities": [
+      {"entity_number":1,"name":"small-lamp","position":{"x":-0.5,"y":-1.5}},
+      {"entity_number":2,"name":"small-lamp","position":{"x":0.5,"y":-1.5}},
+      {"entity_number":3,"name":"locomotive","position":{"x":5,"y":3}}
+    ],
+    "item": "blueprint",
+    "schedules": [
+      {
+        "locomotives": [
+          3
+        ],
+        "schedule": {
+          "records": []
+        }
+      }
+    ],
+    "version": 562949957353472,
+    "wires": [
+      [1,2,2,2],
+      [2,1,3,1]
+    ]
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_canonical_is_stable() {
+        let bp = "0eNrlXUuP47gR/isLHwN7IL6lQfaU3HPJJVgMGu62ukcZt+3I8sxOFvPfQ1KyRLcpu6oktZDZBJhe2tYnsh5UsYr89MficXvKD2WxqxYf/1gUT/vdcfHxtz8Wx+Jlt966z3br13zxcVGui+3ix3JR7Db574uP7Men5SLfVUVV5PUVvvH9YXd6fcxL+4Pl+cpjZa99+VytPMRycdgf7VX7nQO3SCuWpPbH3+1/ZZn48WN5BcVbqKdT+TXfeKDV+g4Ut53dFGX+VP8gjQALUh9ZrI+SApVmMShFgjIxKB2V3GMUymQd1F3JGVIfVayPKQkqaigZZriqg7oYLpMRZJYsQ0dYPX1eF7tV4yQxeG0+tDfgH9TlLaJ3IDlMGjVGhvEYk3RYl4LgMWgxCnRUAhgf0qYHOoqMcSmtMMgagywwyBgX0wyDjPE4lWGQMwwyRoM8wSBjNMgxfqcwGuQcg4zRIMc8uCRGgxzjgxKlQYwPSpQGMT4oURrE+KBEaRDjgwKlQYwPCowGBcYHBUaDAuODAqNBgfFBgdGgwPggx2hQYHyQozSI8UGO0iDGBzlKgwYRcPC+gEPHkEmRp4nG7IIUelqs+xGXTBDQrA86JgBJCjlNdKUhOQkruiKQAhlsm6wNto16G2yz2B0kZWFk3iBHtaUw2rojB42Ug1tpnBHFhzgmaeVmosstSXOg+Po5o49Vp/GxqoSSODDJfS0rkufo+HKfByPfrMt4eJjeHy3mgZRkt/skEZJrsazkYlikfIaOzjIq8Ij94/6wL6v4eki2wrLKLPP/nPJj9fBcbKu8PLqfHWvt1pmrLqUVvWfnMY/Fyyrf2kvL4ml12G/z24sxe/MYXorEa4OHHrwMJhMXJo8lE52QbC2eo2JIz0+61IrLstxPVmnSs0lHvUwLypyiNaCXkiTTeC8VxX/f9pIlMegBDyXN41OXJj2UdPShpFOSGONY2QDT5BDTNAlp5NFHqGH0sEknV2GTit2CU+ImzQCCEJS46S1y1GKNRMqFJTfkksXuoEg2F9eipliEij7GjaH0qweLFOyp6JRvSKslZQBJ5JS0WnoLHUVmJGFGBZBy+sSiQM+8lPScUoDnVEoqdal47UdRqglKQQxBU6oJClCpSQ2lmgBCTinVBBByRqkmQJCzhFJNACEzSjUBhMwp1QQQsqBUE0DIklJNACErSjUBhKwp1QQQsqFUE0DIKaWaAELOKNUECLJ9PlDKCTBoRqknwKA5paAAgxaUigIMWlJKCjBoRakpwKA1pagAgzaUqgIMOqWUFWDQGaWuAIJmCWX1rSCrb8ZoUWg0+mKMlBhRIg5Gyj/2gckBATJo5c0YKS+p4htumKavvRVs7c2YoSy+FYPIovMztwOwWu+q1dP+9bHYrat9T0a6S+so5gdgr6zK/fbhMf+8/lrYyy5yij35xeWizUBefHruzbZ4fl7lvx+2tgNf86Ptyn9O663tvP1yty9fvXhtVw/r0nf14+JX/8HJbWhUPz65/GV0xBl6xFK9x4iLcr+z5pMfK9xYpZ8uGrjOrw/roxPc6lDuvxabvBwM3Xn54+n5eQTAwNPrnPQImJ1rH/bfLN7xW1E9fcYaT4fXeffaanS4OHvNMtjnBDVLkb6HWZ5LU4hx8rhFHu0P1y/5yo7tCw5QxO2wraC8Frti97LalMV2i0NO4wZ5OL0e/r1+QnaTqaglPp62X1bF7piXVsoDutfZ4nZvR/t5bT/eEIH1LVNkeFNk72GKZwMiuF4aN8nXfFOcXq9KcVRgcbPAN9wsj6dHqw4v+jGmyOf1sRrXMMlg/cbIKeUBBSkPMC7o9QEFqg8wTqrt9cSanBS4yiwOppHV6C7RlsQBg9Wg1fd2tV2/Hu5FkDJtZottPUXU50de/L+P/t+1q0VH79eFruuyqD6/5pXtPiJ4fbPdI41PWh32g/16U7Qz2HNRHquH7rhM9f3g+vK1KKuTt5+zMPwvVvnaxiH2jnbiszAP55nV9Wq52B/ysvbrj4u/2Ev3p+pwQoP3eFAG1UsQ5w7QS7BbFK6XMMT+k+gl3Pt6Wy9BoDdEL5yglzDG/LPoRYD1wkbRi6Tohf359KKw26f07QcWauty+2zueZwK0k4WaeJgKaVMLiFlchZsWO6Jfm8/sE18Jw8Ldiu/jStjBbAOUF8l7eIG/FSUT6eiesh368dtvll8rMpTvmw/bo06ZtN9SaV6odIYcPImQv2rtcQpslTuAO3pmHc78NxAoiLtX/rcFmm9UQC602/STByrQ/vY6DglXtTkeTbYXA4wUDaLgcaTTPesdLnY7l+K48XU7jvUXiQJtk1PeMENXGIMnE1k4CNm9mJjVJgxJhONkZ4NvTU0jfGoZBaPuhj4PI6Elj3cfwxCATKdRQEXxYJ5FICuV8AVkCKcW2YTOffQ8kmvf2eY0aUTjW5QtalvaAoTrYaKe0fPeavWeZyHYlxg/1GYCFeaqR6O45RKe22NYwapJxrkgPJy78AwEXWovfd8/sd1O1MkQDc0uEtJjFL0LEoJbHEeRSCdAS58Rcm109e0wbFIcDInvHNfMkcZzJQlJ5qyhpe5+Y3JK8X4iZzFT6ICGO4xguAxZF3AfSfDKETMtJgJt3LMown8dhKwCi5O9951ezFZwE/eL3PD34PTxgDz4vMEK+dtR/NYFmrXE9yoUOEvnyrGJ+3oumVPAjOsqfJ69D11t4aGScvKqdKyI+zuuzVGhZkO5sldXih3nikBbV/waQGTPJbzlGPiJjiPJujuAFcJJuoXk2UzSRuOe/08peyyoa/INCaKFTMlFr2E5zFjuHbBZhtQaYAXwSK9vwg2mOSkmCpvN2yTbp9bGExQJqZKvI4/LExGUsyT/Lrs2HAvTAleiDcquDdi8o9inqTwzNKfSPCYiFaoeSLa7gDDPKJHnqCAC19j5tOpEqbDT530Tqyo0FBNtdolnn+5NTBMGljMkwa+Vus8zkMzL7gPYbYbCD5xRoV2yKzPzFJMZlVMlVkd5bhb7xAxCVbBZ82ojFV7TAekVCYoPwbsYgAVzFNCidrgPJoguwNcIYKwNGX3l6YhFRv8jAo9q5EqCrmeNJB3ktBOYcQPTgQMbWj2EAmi12MpiQ5R9ugxo5D1SQBZH8sSCsOehDDsMRRBW0uxJyEUOiiGtpZjDwYtKCR7MGhJYdmDQSsKzR4MWlN49mDQhkK0B4NOKUx7MOiMQrUHgeYoojaZoaAZhWwPBs0pbHswaEGh24NBSwrfHgxaUfj2YNCawrcHgzYUvj0YdErh24NBZxS+PRA0Syh8ezBoRuHbg0FzCt8eDFpQ+PZg0JLCtweDJtFvx2MxzjSFvE9CyPs4o50Ojr8UNeBs8z289TLKoMQhrsjmeBSdcj44qGP2MP1zjsmABG89kXzUappdKOXV59IfyapOZZkjczwq6c2AcI7JgIRDFL2USWPUmyJDHr7eVoSiE1H20NU25/ATxdm18NGrYs4xW8CUmcik3c62EW0YU75T+p1s+FAc8lW1X72UdgSbecz3TR/GtVxM7S60pGml7ixrHllDbRouYUyBLrTrcX0Vb0Mwr8WU55SacHQjjglztlbJicbkorXdynZgzDkWs0csHNmk3h6MdBafR0oa7PkCRUyj3vGJNt9zbGQJU3lq+Lj7RovteD4q+DCapL5lEJxdLESjx6hCEmmZJrV+r6tZrB9sJXDrpxK8jG79XSYAR1rc6wOaSM8z/sjeFPJGGp8h8tdM7hvnkY5wOlolNCfBCBvuKymRg2t6iV9Y2JxyR5s6XPoZka1mVH+Oc4qP49EyITLWjDrCPhrPkcbIiNQ1k/pQTKszeRHVwMB+JDmRdmtSDcStbiYd0F0ArgUq+8zUq+jd0b2Ke/WYb6u5xP+mF+PKXRI5m8bOy2BHCJpcFZE9Z1KjellX+Vym5O89rgFpIh/WqAYEHhfIbMwwHp6+bAGOX3CqHOjxYEWEPqbH+veKc0l6JwE9C4Kj+3uvjGsr1xFcm7QTHaNXsHvjOP2mygae7AdlXWahPCFu2K6i8pmMOr7t+kjcwXBzbILIwTSpn1zpciZ/IdkU3G8kkaBoUtlf2NlMckfbOlzmikjiw0c+crbLV9/WWHqbm56siRw+4wZZp934c5QhMvdM+yzvdDjX0xxnRXAfSYlEPdMuiTrLmkneSNuGyzsjsvCMOyeFYflInhvSC8K5eOhRP4ryT7xXpjQY+lwzBU63YMvVfBgRT98qGEW1J/RkEfdu8zwiBR1H0eyJqRJp5f5x7/Jo4w1LETl4Jo5jG+0N9zlGi2ER1gP3OE0k25m2nno2qZlkjTJpuKwNkSlkWlnnh3VRrg7jsMbSxB10YVyJp0Q6g2kkfufdhf+cS/7+8vLkp/6Vs/2xDT8jEnvMooZ/zTbDN6BTqMAkFL4HeuRs2DDeir5ILqAdvNjue+v4onhzEPDdLWq9+159LnYvAMM65rvNQ7V/8INbfHxeb4+2E7714IZ6yDeoCcVBVucXqrKVXDG/wF8di92X1VexuNRu8iGTmftfmiYmS4wrZWScpY4Z7sX3+NH/69W+XLyud/a2dV+PD9vitfDva4ypDfuidC7Q5w4N6U3pPUckAy48PA8Ih/CAcKNJRzp7/M1gXviadWCAfqZIUZhgIXz1knumovfIKAfvGHl6ShPKJuoB92OUcuWA+3FKomTA/UQsdo5FtaHlxabhMl9vHvwD8MFNXO0jDbxa7VlU0giPBkgEUxjg5q3HDB0tJnnP9dsBD727Id59nLGnRMmPM/YMQ3TFeibiOAlAwMgEm4lZcmMmzqK3YKSHaNwHUFRM7RNOZHEwQXjLfTh7Jz9+zvfa84A7Kr5z9aZc3AMARN3XecF7Hq9atkh8+JHKAE0MOHTKVNgvOcrh3DSEVAPO5osQSI/HW6GbhG3UAhXaAoM4590skLRjr8cAyXtH5YXxiDH3/Iu4WQ4+LyHitkk+MSbjJjrwnJa8ZaCa8PAIQ/Gf9+FhCJIJFw0/r2RS9KQWyOXdJrUhdfbY3EbdSyTT+LxG3cGTxicz+rZFGZ/Fhu3z7JnKaBueb85gGd4a2Tta492kOrY6fGGbdxOdA0xfkOtQ94I3asG/5/FIqrOfzxxEbEoElKKIuZ/9/HO/SEjL4PjKVSS0ZbCJgwlMold1YPdZmUVAe0op2ogesnGRKELuTZDJxkWiKWTjAkA2LlBEqJ1h9OiSxAQueoScUei6BYSuW6DYTlu6bgFg4BQottOWrhsGzSl03TBoQaHrhkFLCl03DFpR6Lph0JpC1w2DNhS6bhh0SqHrhkFnFLpuEDRPKHTdMGhGoeuGQXMKXTcMWlDoumHQkkLXDYNWFLpuGLSm0HXDoA2FrhsGnVLoumHQGYWuGwQtEgpdNwyaUei6YdCcQtcNgxYUum4YNGmLR0/AJBQp+hJxME2h1BawrS0i4Jkqyv0O9uYv0bOHSgjirkjf2yhgBu4ev989mdB2C/Z2T7JBqRN+raNpMnmHIi+f3Ju5fTLquHpdv6z/W+yQJYuVf1PI6/r3h3NWiWcy0ybJdH8mQPJhK79e0VOKtiLpFb1813wD+Lq/uYvGyUcsL/r8sMurb/vyy7HeGrNpdwS+lHm+a/fGXPT3+pp6J2NziQfoSXsEzCzQ3ZXi/2l3JX0f5bLemNRsdWw8qx5D+BnKZN7szXT/fyw2xS+/FVX++mvfbPDpl7+X+8Mv/3h+XiB2YAqp6DswoY8pSXoTW88TNWBEuSq33n5MJZDtlyJgRoHgczQ+cdO7m1ajAkFRkITy6AW8/WLWiEmkLWQckBNFyq/KE9HwLyCXwJnENX5UZQGBQlDAubGs4RkMV6H6zdD91lS5GJjcDVWvfRlhlVIlYmASyaj4GoQfHES+ud+19RieDt5aKDSjDkrBBsWp+BKGL6j4AoYvqfiRDfuflotvtu0U85u1f7VkXC/Vp2XdsPbr/zt1X9iFpGswli6Z/Zn917cy17KxrG/Zda5r8abFfEs2Le1Rshqlaamg5V6pVLfSpfC/9NdZbNuyzl+3uGuZ5n4W27ZS0bSUw7Qt1bbc7F+3fD/l+bq6JdqW8ndQbcvxmvuW+439ZXN399f+skGxz+7rVoOp/HWqGbv7241B1Shp21J+RKptOZ60uuVlrRpJKN9r6x++pb10dYOplW9lF63zd/66s8yM15GRbSuQmW+58+l1S1z8sm6ppuXvYJoxGN/PtNF76nt21krTOn/nUNy5qXNL+Za7H/e25N4OxZpWpwf/6dK9/8V/x+V1q7FB99aI61Y9BodmW5lYiq4l0vC74JedHhwDtv1ONj2rW+frpO+Z5E3LozSW5f8uHVli3VK+ZZqWR2msp2lluu6Zu2+rB/+bJW8sy/9d8say/N8lb2zJcb7YVmMhTev8nbcXh1m3fD8bC2lamanvrnmoFW9L7lQvC1rn6+r7ne+Q+lbWtLJA0/7v0pXiRdvijYU0LcPblvXbRNZ3z/xos2a0mWqkVLdqmTV3z7LmDq7lr1+6Bb9DEV5joplR/F//nW95/YUtO8NYvXHf4v472XxXt0Tb6uYX96n0v+Sf7JzqljYu3tye8kNZ7NzU/NU+Cv2kq7TLVGTK2EBPGv7jx/8ApECMsg==";
+        let json: Value =
+            serde_json::from_str(&crate::blueprint::blueprint_to_json(bp).unwrap()).unwrap();
+        let canonical = to_canonical_string(json.clone()).unwrap();
+        // Same blueprint, as far as the game is concerned
+        let reparsed: Value = serde_json::from_str(&canonical).unwrap();
+        assert_eq!(
+            crate::blueprint::count_entities::count(&reparsed.to_string(), &Default::default())
+                .unwrap(),
+            crate::blueprint::count_entities::count(&json.to_string(), &Default::default())
+                .unwrap()
+        );
+        // Canonicalizing is idempotent
+        assert_eq!(to_canonical_string(reparsed).unwrap(), canonical);
+    }
+}
@@ -0,0 +1,812 @@
+//! Semantic differences between two blueprints, blueprint books or planners.
+//!
+//! Entities are matched by name and position rather than `entity_number`, so renumbering (or
+//! re-exporting from the game) doesn't show up as a change.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{blueprint::BlueprintType, error::Result};
+
+/// Fields that are compared separately, or that change without the blueprint changing.
+const NOT_FIELDS: &[&str] = &[
+    "active_index",
+    "blueprints",
+    "description",
+    "entities",
+    "item",
+    "label",
+    "version",
+    "wires",
+];
+
+/// Everything that changed between two blueprints.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// The `index` of each book entry on the way down to a change; empty for the top level.
+pub type EntryPath = Vec<u64>;
+
+/// A single difference.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// The entry changed kind, e.g. from a blueprint to a blueprint book.
+    KindChanged {
+        entry: EntryPath,
+        old: &'static str,
+        new: &'static str,
+    },
+    LabelChanged {
+        entry: EntryPath,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    DescriptionChanged {
+        entry: EntryPath,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// Any other field, like `icons`, `tiles` or a planner's `settings.mappers`.
+    FieldChanged {
+        entry: EntryPath,
+        #[serde(flatten)]
+        field: FieldChange,
+    },
+    EntityAdded {
+        entry: EntryPath,
+        entity: EntityRef,
+    },
+    EntityRemoved {
+        entry: EntryPath,
+        entity: EntityRef,
+    },
+    /// An otherwise unchanged entity was moved.
+    EntityMoved {
+        entry: EntryPath,
+        name: String,
+        old: Position,
+        new: Position,
+    },
+    /// An entity in the same place changed some of its settings.
+    EntityChanged {
+        entry: EntryPath,
+        entity: EntityRef,
+        fields: Vec<FieldChange>,
+    },
+    WireAdded {
+        entry: EntryPath,
+        from: WireEnd,
+        to: WireEnd,
+    },
+    WireRemoved {
+        entry: EntryPath,
+        from: WireEnd,
+        to: WireEnd,
+    },
+    /// A book entry was added; `entry` is the book it was added to.
+    EntryAdded {
+        entry: EntryPath,
+        index: u64,
+        label: Option<String>,
+    },
+    EntryRemoved {
+        entry: EntryPath,
+        index: u64,
+        label: Option<String>,
+    },
+    /// A book entry moved to a different `index` in the same book.
+    EntryMoved {
+        entry: EntryPath,
+        old_index: u64,
+        new_index: u64,
+        label: Option<String>,
+    },
+}
+
+/// A field with its old and new values; `None` when it's absent on that side.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// An entity, identified the way a player would: by what it is and where it is.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EntityRef {
+    pub name: String,
+    pub position: Position,
+}
+
+/// One end of a wire.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WireEnd {
+    pub entity: EntityRef,
+    pub connector: u8,
+}
+
+/// Compares two blueprint entries of any kind, recursing into books.
+pub fn diff(old: &Value, new: &Value) -> Result<Diff> {
+    let mut diff = Diff::default();
+    diff_entry(old, new, &mut vec![], &mut diff.changes)?;
+    Ok(diff)
+}
+
+fn diff_entry(
+    old: &Value,
+    new: &Value,
+    entry: &mut EntryPath,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let old = BlueprintType::<&Value>::new(old)?;
+    let new = BlueprintType::<&Value>::new(new)?;
+    if old.kind_name() != new.kind_name() {
+        changes.push(Change::KindChanged {
+            entry: entry.clone(),
+            old: old.kind_name(),
+            new: new.kind_name(),
+        });
+        return Ok(());
+    }
+    if old.label() != new.label() {
+        changes.push(Change::LabelChanged {
+            entry: entry.clone(),
+            old: old.label().map(str::to_owned),
+            new: new.label().map(str::to_owned),
+        });
+    }
+    if description(&old) != description(&new) {
+        changes.push(Change::DescriptionChanged {
+            entry: entry.clone(),
+            old: description(&old).map(str::to_owned),
+            new: description(&new).map(str::to_owned),
+        });
+    }
+    changes.extend(
+        diff_fields(&fields(old.any()), &fields(new.any()))
+            .into_iter()
+            .map(|field| Change::FieldChanged {
+                entry: entry.clone(),
+                field,
+            }),
+    );
+    match (old, new) {
+        (BlueprintType::Blueprint(old), BlueprintType::Blueprint(new)) => {
+            diff_entities(old, new, entry, changes)
+        }
+        (BlueprintType::BlueprintBook(old), BlueprintType::BlueprintBook(new)) => {
+            diff_books(old, new, entry, changes)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn description<'a>(bp: &BlueprintType<&'a Value>) -> Option<&'a str> {
+    let any = match bp {
+        BlueprintType::Blueprint(value)
+        | BlueprintType::BlueprintBook(value)
+        | BlueprintType::UpgradePlanner(value)
+        | BlueprintType::DeconstructionPlanner(value) => *value,
+    };
+    any.get("description")
+        .or_else(|| any.get("settings")?.get("description"))?
+        .as_str()
+}
+
+/// The fields of a blueprint not handled elsewhere, with planner settings flattened into
+/// `settings.<field>`.
+fn fields(json: &Value) -> Map<String, Value> {
+    let mut fields = Map::new();
+    for (key, value) in json.as_object().into_iter().flatten() {
+        if NOT_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        match value {
+            Value::Object(settings) if key == "settings" => {
+                for (key, value) in settings {
+                    if !NOT_FIELDS.contains(&key.as_str()) {
+                        fields.insert(format!("settings.{key}"), value.clone());
+                    }
+                }
+            }
+            _ => {
+                fields.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    fields
+}
+
+fn diff_fields(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<FieldChange> {
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|field| old.get(*field) != new.get(*field))
+        .map(|field| FieldChange {
+            field: field.clone(),
+            old: old.get(field).cloned(),
+            new: new.get(field).cloned(),
+        })
+        .collect()
+}
+
+fn array<'a>(json: &'a Value, key: &str) -> &'a [Value] {
+    json.get(key)
+        .and_then(Value::as_array)
+        .map(|values| &values[..])
+        .unwrap_or_default()
+}
+
+/// An entity of a blueprint, as far as diffing is concerned.
+struct Placed {
+    number: Option<u64>,
+    entity: EntityRef,
+    /// Everything except `entity_number` and `position`.
+    settings: Map<String, Value>,
+}
+
+impl Placed {
+    fn new(json: &Value) -> Placed {
+        let coordinate = |key| {
+            json.get("position")
+                .and_then(|position| position.get(key))
+                .and_then(Value::as_f64)
+                .unwrap_or_default()
+        };
+        let mut settings = json.as_object().cloned().unwrap_or_default();
+        settings.remove("entity_number");
+        settings.remove("position");
+        Placed {
+            number: json.get("entity_number").and_then(Value::as_u64),
+            entity: EntityRef {
+                name: json
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+                position: Position {
+                    x: coordinate("x"),
+                    y: coordinate("y"),
+                },
+            },
+            settings,
+        }
+    }
+
+    /// Name and position, with `-0.0` and `0.0` treated as the same place.
+    fn key(&self) -> (&str, u64, u64) {
+        let Position { x, y } = self.entity.position;
+        (&self.entity.name, (x + 0.0).to_bits(), (y + 0.0).to_bits())
+    }
+}
+
+fn diff_entities(
+    old: &Value,
+    new: &Value,
+    entry: &EntryPath,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let old_entities = array(old, "entities")
+        .iter()
+        .map(Placed::new)
+        .collect::<Vec<_>>();
+    let new_entities = array(new, "entities")
+        .iter()
+        .map(Placed::new)
+        .collect::<Vec<_>>();
+
+    // Which old entity (if any) each new entity is.
+    let mut matches: Vec<Option<usize>> = vec![None; new_entities.len()];
+    // Several entities can share a name and position (rails crossing each other), so prefer the
+    // one whose settings are the same, and only then take any that's left.
+    let mut old_by_key = HashMap::<_, Vec<usize>>::new();
+    for (i, placed) in old_entities.iter().enumerate() {
+        old_by_key.entry(placed.key()).or_default().push(i);
+    }
+    let mut matched_old = vec![false; old_entities.len()];
+    for (new_i, placed) in new_entities.iter().enumerate() {
+        let candidates = old_by_key.get(&placed.key()).map_or(&[][..], Vec::as_slice);
+        let unmatched = || candidates.iter().copied().filter(|&i| !matched_old[i]);
+        let found = unmatched()
+            .find(|&i| old_entities[i].settings == placed.settings)
+            .or_else(|| unmatched().next());
+        if let Some(old_i) = found {
+            matches[new_i] = Some(old_i);
+            matched_old[old_i] = true;
+            let fields = diff_fields(&old_entities[old_i].settings, &placed.settings);
+            if !fields.is_empty() {
+                changes.push(Change::EntityChanged {
+                    entry: entry.clone(),
+                    entity: placed.entity.clone(),
+                    fields,
+                });
+            }
+        }
+    }
+    // Anything left over that's identical except for its position was moved.
+    for (old_i, old_placed) in old_entities.iter().enumerate() {
+        if matched_old[old_i] {
+            continue;
+        }
+        let moved = new_entities.iter().enumerate().position(|(new_i, placed)| {
+            matches[new_i].is_none()
+                && placed.entity.name == old_placed.entity.name
+                && placed.settings == old_placed.settings
+        });
+        if let Some(new_i) = moved {
+            matches[new_i] = Some(old_i);
+            matched_old[old_i] = true;
+            changes.push(Change::EntityMoved {
+                entry: entry.clone(),
+                name: old_placed.entity.name.clone(),
+                old: old_placed.entity.position,
+                new: new_entities[new_i].entity.position,
+            });
+        } else {
+            changes.push(Change::EntityRemoved {
+                entry: entry.clone(),
+                entity: old_placed.entity.clone(),
+            });
+        }
+    }
+    for (new_i, placed) in new_entities.iter().enumerate() {
+        if matches[new_i].is_none() {
+            changes.push(Change::EntityAdded {
+                entry: entry.clone(),
+                entity: placed.entity.clone(),
+            });
+        }
+    }
+
+    // Compare wires by identity: old entities are 0..old_len, new entities share the identity of
+    // the old entity they matched, or get a fresh one.
+    let old_ids = old_entities
+        .iter()
+        .enumerate()
+        .filter_map(|(i, placed)| Some((placed.number?, (i, &placed.entity))))
+        .collect::<HashMap<_, _>>();
+    let new_ids = new_entities
+        .iter()
+        .zip(&matches)
+        .enumerate()
+        .filter_map(|(i, (placed, matched))| {
+            let id = matched.unwrap_or(old_entities.len() + i);
+            Some((placed.number?, (id, &placed.entity)))
+        })
+        .collect::<HashMap<_, _>>();
+    let old_wires = wires(old, &old_ids);
+    let new_wires = wires(new, &new_ids);
+    let old_keys = old_wires
+        .iter()
+        .map(|(key, _)| *key)
+        .collect::<HashSet<_>>();
+    let new_keys = new_wires
+        .iter()
+        .map(|(key, _)| *key)
+        .collect::<HashSet<_>>();
+    for (key, (from, to)) in old_wires {
+        if !new_keys.contains(&key) {
+            changes.push(Change::WireRemoved {
+                entry: entry.clone(),
+                from,
+                to,
+            });
+        }
+    }
+    for (key, (from, to)) in new_wires {
+        if !old_keys.contains(&key) {
+            changes.push(Change::WireAdded {
+                entry: entry.clone(),
+                from,
+                to,
+            });
+        }
+    }
+    Ok(())
+}
+
+type WireKey = ((usize, u8), (usize, u8));
+
+/// The wires of a blueprint keyed by entity identity, in blueprint order. Wires to unknown
+/// entities are skipped.
+fn wires(
+    blueprint: &Value,
+    ids: &HashMap<u64, (usize, &EntityRef)>,
+) -> Vec<(WireKey, (WireEnd, WireEnd))> {
+    let mut seen = BTreeSet::new();
+    let mut result = vec![];
+    for wire in array(blueprint, "wires") {
+        let end = |i: usize| -> Option<((usize, u8), WireEnd)> {
+            let (id, entity) = ids.get(&wire.get(i)?.as_u64()?)?;
+            let connector = u8::try_from(wire.get(i + 1)?.as_u64()?).ok()?;
+            Some((
+                (*id, connector),
+                WireEnd {
+                    entity: (*entity).clone(),
+                    connector,
+                },
+            ))
+        };
+        let (Some(a), Some(b)) = (end(0), end(2)) else {
+            continue;
+        };
+        // Wires aren't directional.
+        let (a, b) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+        let key = (a.0, b.0);
+        if seen.insert(key) {
+            result.push((key, (a.1, b.1)));
+        }
+    }
+    result
+}
+
+struct Entry<'a> {
+    index: u64,
+    label: Option<String>,
+    json: &'a Value,
+}
+
+fn entries(book: &Value) -> Result<Vec<Entry<'_>>> {
+    array(book, "blueprints")
+        .iter()
+        .enumerate()
+        .map(|(position, json)| {
+            Ok(Entry {
+                index: json
+                    .get("index")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(position as u64),
+                label: BlueprintType::<&Value>::new(json)?
+                    .label()
+                    .map(str::to_owned),
+                json,
+            })
+        })
+        .collect()
+}
+
+fn diff_books(
+    old: &Value,
+    new: &Value,
+    entry: &mut EntryPath,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    let old_entries = entries(old)?;
+    let new_entries = entries(new)?;
+    let mut pairs = vec![];
+    let mut old_used = vec![false; old_entries.len()];
+    let mut new_used = vec![false; new_entries.len()];
+    // Pair entries by index and label, then by label alone (a reorder), then by index alone (a
+    // relabel).
+    for pass in 0..3 {
+        let matches = |old: &Entry, new: &Entry| match pass {
+            0 => old.index == new.index && old.label == new.label,
+            1 => old.label.is_some() && old.label == new.label,
+            _ => old.index == new.index,
+        };
+        for (new_i, new_entry) in new_entries.iter().enumerate() {
+            if new_used[new_i] {
+                continue;
+            }
+            let found = old_entries
+                .iter()
+                .enumerate()
+                .position(|(old_i, old_entry)| !old_used[old_i] && matches(old_entry, new_entry));
+            if let Some(old_i) = found {
+                old_used[old_i] = true;
+                new_used[new_i] = true;
+                pairs.push((old_i, new_i));
+            }
+        }
+    }
+    pairs.sort_by_key(|&(_, new_i)| new_entries[new_i].index);
+
+    for (old_i, old_entry) in old_entries.iter().enumerate() {
+        if !old_used[old_i] {
+            changes.push(Change::EntryRemoved {
+                entry: entry.clone(),
+                index: old_entry.index,
+                label: old_entry.label.clone(),
+            });
+        }
+    }
+    for (new_i, new_entry) in new_entries.iter().enumerate() {
+        if !new_used[new_i] {
+            changes.push(Change::EntryAdded {
+                entry: entry.clone(),
+                index: new_entry.index,
+                label: new_entry.label.clone(),
+            });
+        }
+    }
+    for (old_i, new_i) in pairs {
+        let (old_entry, new_entry) = (&old_entries[old_i], &new_entries[new_i]);
+        if old_entry.index != new_entry.index {
+            changes.push(Change::EntryMoved {
+                entry: entry.clone(),
+                old_index: old_entry.index,
+                new_index: new_entry.index,
+                label: new_entry.label.clone(),
+            });
+        }
+        entry.push(new_entry.index);
+        diff_entry(old_entry.json, new_entry.json, entry, changes)?;
+        entry.pop();
+    }
+    Ok(())
+}
+
+impl Display for Diff {
+    /// One change per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl Display for EntityRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.name, self.position)
+    }
+}
+
+impl Display for WireEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let connector = match self.connector {
+            1 => "red",
+            2 => "green",
+            3 => "output red",
+            4 => "output green",
+            5 => "copper",
+            6 => "right copper",
+            _ => return write!(f, "{} connector {}", self.entity, self.connector),
+        };
+        write!(f, "{} {connector}", self.entity)
+    }
+}
+
+/// A quoted string, or `none` when it's absent.
+struct Text<'a>(&'a Option<String>, &'static str);
+
+impl Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(text) => write!(f, "{text:?}"),
+            None => write!(f, "{}", self.1),
+        }
+    }
+}
+
+fn label_text(label: &Option<String>) -> Text<'_> {
+    Text(label, "(no label)")
+}
+
+struct Field<'a>(&'a Option<Value>);
+
+impl Display for Field<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{value}"),
+            None => write!(f, "(none)"),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Change::KindChanged { entry, .. }
+        | Change::LabelChanged { entry, .. }
+        | Change::DescriptionChanged { entry, .. }
+        | Change::FieldChanged { entry, .. }
+        | Change::EntityAdded { entry, .. }
+        | Change::EntityRemoved { entry, .. }
+        | Change::EntityMoved { entry, .. }
+        | Change::EntityChanged { entry, .. }
+        | Change::WireAdded { entry, .. }
+        | Change::WireRemoved { entry, .. }
+        | Change::EntryAdded { entry, .. }
+        | Change::EntryRemoved { entry, .. }
+        | Change::EntryMoved { entry, .. }) = self;
+        if !entry.is_empty() {
+            let path = entry.iter().map(u64::to_string).collect::<Vec<_>>();
+            write!(f, "[{}] ", path.join("/"))?;
+        }
+        match self {
+            Change::KindChanged { old, new, .. } => write!(f, "kind: {old} -> {new}"),
+            Change::LabelChanged { old, new, .. } => {
+                write!(f, "label: {} -> {}", label_text(old), label_text(new))
+            }
+            Change::DescriptionChanged { old, new, .. } => {
+                write!(
+                    f,
+                    "description: {} -> {}",
+                    Text(old, "(none)"),
+                    Text(new, "(none)")
+                )
+            }
+            Change::FieldChanged { field, .. } => write!(
+                f,
+                "{}: {} -> {}",
+                field.field,
+                Field(&field.old),
+                Field(&field.new)
+            ),
+            Change::EntityAdded { entity, .. } => write!(f, "+ {entity}"),
+            Change::EntityRemoved { entity, .. } => write!(f, "- {entity}"),
+            Change::EntityMoved { name, old, new, .. } => {
+                write!(f, "~ {name} moved from {old} to {new}")
+            }
+            Change::EntityChanged { entity, fields, .. } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| field.field.as_str())
+                    .collect::<Vec<_>>();
+                write!(f, "* {entity} changed {}", fields.join(", "))
+            }
+            Change::WireAdded { from, to, .. } => write!(f, "+ wire {from} to {to}"),
+            Change::WireRemoved { from, to, .. } => write!(f, "- wire {from} to {to}"),
+            Change::EntryAdded { index, label, .. } => {
+                write!(f, "+ entry {index}: {}", label_text(label))
+            }
+            Change::EntryRemoved { index, label, .. } => {
+                write!(f, "- entry {index}: {}", label_text(label))
+            }
+            Change::EntryMoved {
+                old_index,
+                new_index,
+                label,
+                ..
+            } => write!(
+                f,
+                "~ entry {}: moved from index {old_index} to {new_index}",
+                label_text(label)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_blueprint() {
+        let old = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "label": "Lamps",
+                "entities": [
+                    {"entity_number": 1, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}},
+                    {"entity_number": 2, "name": "small-lamp", "position": {"x": 2.5, "y": 0.5}},
+                    {"entity_number": 3, "name": "small-lamp", "position": {"x": 4.5, "y": 0.5}},
+                    {"entity_number": 4, "name": "iron-chest", "position": {"x": 6.5, "y": 0.5}},
+                ],
+                "wires": [[1, 1, 2, 1], [2, 2, 3, 2]],
+                "version": 562949957353472u64,
+            }
+        });
+        // Renumbered, one lamp moved, one lamp changed, chest replaced, one wire rewired.
+        let new = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "label": "More lamps",
+                "entities": [
+                    {"entity_number": 1, "name": "wooden-chest", "position": {"x": 6.5, "y": 0.5}},
+                    {"entity_number": 2, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}},
+                    {"entity_number": 3, "name": "small-lamp", "position": {"x": 2.5, "y": 0.5},
+                        "color": {"r": 1, "g": 0, "b": 0}},
+                    {"entity_number": 4, "name": "small-lamp", "position": {"x": 4.5, "y": 2.5}},
+                ],
+                "wires": [[3, 1, 2, 1], [3, 1, 4, 1]],
+                "version": 562949957353473u64,
+            }
+        });
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(
+            diff.to_string(),
+            "\
+label: \"Lamps\" -> \"More lamps\"
+* small-lamp at (2.5, 0.5) changed color
+~ small-lamp moved from (4.5, 0.5) to (4.5, 2.5)
+- iron-chest at (6.5, 0.5)
++ wooden-chest at (6.5, 0.5)
+- wire small-lamp at (2.5, 0.5) green to small-lamp at (4.5, 0.5) green
++ wire small-lamp at (2.5, 0.5) red to small-lamp at (4.5, 2.5) red
+"
+        );
+        assert_eq!(
+            serde_json::to_value(&diff.changes[1]).unwrap(),
+            json!({
+                "change": "entity_changed",
+                "entry": [],
+                "entity": {"name": "small-lamp", "position": {"x": 2.5, "y": 0.5}},
+                "fields": [{"field": "color", "old": null, "new": {"r": 1, "g": 0, "b": 0}}],
+            })
+        );
+        assert!(super::diff(&new, &new).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_rail_crossing() {
+        let crossing =
+            |entities: Value| json!({"blueprint": {"item": "blueprint", "entities": entities}});
+        let rail = |number, direction| {
+            json!({"entity_number": number, "name": "straight-rail", "position": {"x": 1, "y": 1},
+                "direction": direction})
+        };
+        let old = crossing(json!([rail(1, 0), rail(2, 4)]));
+        assert!(diff(&old, &old).unwrap().is_empty());
+        let new = crossing(json!([rail(1, 4), rail(2, 0)]));
+        assert!(diff(&old, &new).unwrap().is_empty());
+        let new = crossing(json!([rail(1, 4), rail(2, 8)]));
+        assert_eq!(
+            diff(&old, &new).unwrap().to_string(),
+            "* straight-rail at (1, 1) changed direction\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_book() {
+        let blueprint = |index, label: &str| json!({"index": index, "blueprint": {"item": "blueprint", "label": label}});
+        let old = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "blueprints": [blueprint(0, "A"), blueprint(1, "B"), blueprint(2, "C")],
+            }
+        });
+        let new = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "description": "Now with D",
+                "blueprints": [
+                    blueprint(0, "A"),
+                    blueprint(1, "C"),
+                    blueprint(3, "D"),
+                    {"index": 4, "upgrade_planner": {"item": "upgrade-planner", "settings": {"description": "upgrade"}}},
+                ],
+            }
+        });
+        assert_eq!(
+            diff(&old, &new).unwrap().to_string(),
+            "\
+description: (none) -> \"Now with D\"
+- entry 1: \"B\"
++ entry 3: \"D\"
++ entry 4: (no label)
+~ entry \"C\": moved from index 2 to 1
+"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! The error type shared by everything in this crate.
+
+use std::{
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+};
+
+/// Everything that can go wrong while decoding, transforming, saving or loading blueprints.
+#[derive(Debug)]
+pub enum Error {
+    /// The blueprint string didn't start with the `0` version byte.
+    BadHeader,
+    /// The body of the blueprint string wasn't valid base64.
+    BadBase64(base64::DecodeError),
+    /// The base64-decoded data wasn't a complete zlib stream.
+    TruncatedZlib(io::Error),
+    /// The blueprint (or a saved file, if `path` is set) didn't contain valid json.
+    InvalidJson {
+        path: Option<PathBuf>,
+        source: serde_json::Error,
+    },
+    /// The json didn't contain any of the known blueprint kinds; contains the keys that were found.
+    UnknownBlueprintType(Vec<String>),
+    /// The json was a blueprint, but not shaped the way we expected.
+    InvalidBlueprint(String),
+    /// A quality name that isn't in the quality table.
+    UnknownQuality(String),
+    /// A quality at a path we don't know whether to upgrade.
+    UnhandledQuality(Vec<String>),
+    /// Reading or writing a file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A saved blueprint book directory couldn't be loaded back into a book.
+    InvalidBook { path: PathBuf, reason: String },
+    /// An inventory to compare counts against couldn't be read.
+    InvalidInventory(String),
+    /// The qualities from a quality ladder file or prototype dump don't form a ladder.
+    InvalidQualities(String),
+    /// Options were given that can't be used together.
+    InvalidOptions(String),
+    /// Looking up the git commit for `load --stamp` failed.
+    Git(String),
+    /// Interacting with the terminal (stdin, clipboard, paste prompt) failed.
+    Terminal(io::Error),
+    /// The user cancelled the paste prompt.
+    Cancelled,
+}
+
+/// `Result` defaulting to this crate's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io { path, source }
+    }
+
+    pub(crate) fn invalid_book(path: impl Into<PathBuf>, reason: impl Into<String>) -> Error {
+        Error::InvalidBook {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// The process exit code used when exiting because of this error.
+    ///
+    /// These are stable so that scripts can tell failures apart. 2 is for usage errors: the same as
+    /// clap's, for [`Error::InvalidOptions`].
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::InvalidOptions(_) => 2,
+            Error::BadHeader => 10,
+            Error::BadBase64(_) => 11,
+            Error::TruncatedZlib(_) => 12,
+            Error::InvalidJson { .. } => 13,
+            Error::UnknownBlueprintType(_) => 14,
+            Error::InvalidBlueprint(_) => 15,
+            Error::UnknownQuality(_) => 16,
+            Error::UnhandledQuality(_) => 17,
+            Error::Io { .. } => 20,
+            Error::InvalidBook { .. } => 21,
+            Error::Git(_) => 22,
+            Error::Terminal(_) => 23,
+            Error::InvalidInventory(_) => 24,
+            Error::InvalidQualities(_) => 25,
+            Error::Cancelled => 130,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadHeader => write!(f, "blueprint string should start with '0'"),
+            Error::BadBase64(e) => write!(f, "bad base64 in blueprint string: {e}"),
+            Error::TruncatedZlib(e) => write!(f, "bad zlib data in blueprint string: {e}"),
+            Error::InvalidJson { path: None, source } => write!(f, "invalid json: {source}"),
+            Error::InvalidJson {
+                path: Some(path),
+                source,
+            } => write!(f, "invalid json in {path:?}: {source}"),
+            Error::UnknownBlueprintType(keys) => {
+                write!(f, "blueprint has unknown type, found keys {keys:?}")
+            }
+            Error::InvalidBlueprint(reason) => write!(f, "invalid blueprint: {reason}"),
+            Error::UnknownQuality(quality) => write!(f, "can't find quality {quality:?}"),
+            Error::UnhandledQuality(path) => write!(
+                f,
+                "unhandled quality in blueprint at {path:?}; not sure whether it should be upgraded"
+            ),
+            Error::Io { path, source } => write!(f, "{path:?}: {source}"),
+            Error::InvalidBook { path, reason } => write!(f, "{path:?}: {reason}"),
+            Error::InvalidOptions(reason) => write!(f, "invalid options: {reason}"),
+            Error::Git(reason) => write!(f, "git: {reason}"),
+            Error::InvalidInventory(reason) => write!(f, "invalid inventory: {reason}"),
+            Error::InvalidQualities(reason) => write!(f, "invalid quality ladder: {reason}"),
+            Error::Terminal(e) => write!(f, "terminal: {e}"),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::BadBase64(e) => Some(e),
+            Error::TruncatedZlib(e) | Error::Terminal(e) => Some(e),
+            Error::InvalidJson { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            Error::BadHeader
+            | Error::UnknownBlueprintType(_)
+            | Error::InvalidBlueprint(_)
+            | Error::UnknownQuality(_)
+            | Error::UnhandledQuality(_)
+            | Error::InvalidBook { .. }
+            | Error::InvalidOptions(_)
+            | Error::Git(_)
+            | Error::InvalidInventory(_)
+            | Error::InvalidQualities(_)
+            | Error::Cancelled => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::InvalidJson { path: None, source }
+    }
+}
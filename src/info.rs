@@ -0,0 +1,319 @@
+//! Human-readable summaries of blueprints.
+
+use std::fmt::Write;
+
+use crate::{
+    blueprint::{BlueprintType, Version},
+    error::Result,
+    save::format_icon_tag,
+};
+
+/// Describes a blueprint: its kind, label, version, icons, contents, and for books, every entry.
+pub fn describe(json: &serde_json::Value) -> Result<String> {
+    let mut out = String::new();
+    describe_inner(json, 0, &mut out)?;
+    Ok(out)
+}
+
+fn describe_inner(json: &serde_json::Value, depth: usize, out: &mut String) -> Result<()> {
+    let bp = BlueprintType::<&serde_json::Value>::new(json)?;
+    let indent = "  ".repeat(depth);
+    let index = json
+        .get("index")
+        .and_then(|index| index.as_u64())
+        .map(|index| format!("[{index}] "))
+        .unwrap_or_default();
+    let label = bp.label().unwrap_or("(no label)");
+    writeln!(out, "{indent}{index}{kind}: {label}", kind = bp.kind_name())
+        .expect("writing to a String can't fail");
+
+    let mut field = |name: &str, value: &dyn std::fmt::Display| {
+        writeln!(out, "{indent}  {name}: {value}").expect("writing to a String can't fail");
+    };
+    let any = bp.any();
+    if let Some(version) = any.get("version").and_then(|version| version.as_u64()) {
+        field("version", &Version::from_packed(version));
+    }
+    let icons = any
+        .get("icons")
+        .or_else(|| {
+            any.get("settings")
+                .and_then(|settings| settings.get("icons"))
+        })
+        .and_then(|icons| icons.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|icon| format_icon_tag(icon.get("signal")?))
+        .collect::<Vec<_>>();
+    if !icons.is_empty() {
+        field("icons", &icons.join(" "));
+    }
+
+    match bp {
+        BlueprintType::Blueprint(blueprint) => {
+            let entities = array(blueprint, "entities");
+            let tiles = array(blueprint, "tiles");
+            field("entities", &entities.len());
+            field("tiles", &tiles.len());
+            field("wires", &array(blueprint, "wires").len());
+            let legacy = blueprint
+                .get("version")
+                .and_then(|version| version.as_u64())
+                .is_some_and(|version| Version::from_packed(version).major < 2);
+            let footprints = entities
+                .iter()
+                .filter_map(|entity| entity_footprint(entity, legacy))
+                .chain(tiles.iter().filter_map(tile_footprint));
+            if let Some(((min_x, min_y), (max_x, max_y))) = bounding_box(footprints) {
+                field(
+                    "bounding box",
+                    &format!("({min_x}, {min_y}) to ({max_x}, {max_y})"),
+                );
+            }
+            field("snap to grid", &snap_to_grid(blueprint));
+            let parameterized = !array(blueprint, "parameters").is_empty();
+            field("parameterized", &if parameterized { "yes" } else { "no" });
+        }
+        BlueprintType::BlueprintBook(book) => {
+            for entry in array(book, "blueprints") {
+                describe_inner(entry, depth + 1, out)?;
+            }
+        }
+        BlueprintType::UpgradePlanner(_) | BlueprintType::DeconstructionPlanner(_) => {}
+    }
+    Ok(())
+}
+
+fn array<'a>(json: &'a serde_json::Value, key: &str) -> &'a [serde_json::Value] {
+    json.get(key)
+        .and_then(|value| value.as_array())
+        .map(|values| &values[..])
+        .unwrap_or_default()
+}
+
+fn position(json: &serde_json::Value) -> Option<(f64, f64)> {
+    let position = json.get("position")?;
+    Some((position.get("x")?.as_f64()?, position.get("y")?.as_f64()?))
+}
+
+/// A box, as its top left and bottom right corners.
+type Area = ((f64, f64), (f64, f64));
+
+/// Width and height in tiles of base game entities, facing north, for those that aren't 1×1.
+#[rustfmt::skip]
+const ENTITY_SIZES: &[(&str, f64, f64)] = &[
+    ("accumulator", 2.0, 2.0),
+    ("agricultural-tower", 3.0, 3.0),
+    ("arithmetic-combinator", 1.0, 2.0),
+    ("artillery-turret", 3.0, 3.0),
+    ("assembling-machine-1", 3.0, 3.0),
+    ("assembling-machine-2", 3.0, 3.0),
+    ("assembling-machine-3", 3.0, 3.0),
+    ("asteroid-collector", 3.0, 3.0),
+    ("beacon", 3.0, 3.0),
+    ("big-electric-pole", 2.0, 2.0),
+    ("big-mining-drill", 5.0, 5.0),
+    ("biochamber", 3.0, 3.0),
+    ("boiler", 3.0, 2.0),
+    ("burner-mining-drill", 2.0, 2.0),
+    ("cargo-bay", 4.0, 4.0),
+    ("cargo-landing-pad", 8.0, 8.0),
+    ("centrifuge", 3.0, 3.0),
+    ("chemical-plant", 3.0, 3.0),
+    ("crusher", 2.0, 3.0),
+    ("cryogenic-plant", 5.0, 5.0),
+    ("decider-combinator", 1.0, 2.0),
+    ("electric-furnace", 3.0, 3.0),
+    ("electric-mining-drill", 3.0, 3.0),
+    ("electromagnetic-plant", 4.0, 4.0),
+    ("flamethrower-turret", 2.0, 3.0),
+    ("foundry", 5.0, 5.0),
+    ("fusion-generator", 3.0, 5.0),
+    ("fusion-reactor", 6.0, 6.0),
+    ("gun-turret", 2.0, 2.0),
+    ("heat-exchanger", 3.0, 2.0),
+    ("heating-tower", 3.0, 3.0),
+    ("lab", 3.0, 3.0),
+    ("laser-turret", 2.0, 2.0),
+    ("lightning-collector", 2.0, 2.0),
+    ("nuclear-reactor", 5.0, 5.0),
+    ("offshore-pump", 1.0, 2.0),
+    ("oil-refinery", 5.0, 5.0),
+    ("pump", 1.0, 2.0),
+    ("pumpjack", 3.0, 3.0),
+    ("radar", 3.0, 3.0),
+    ("railgun-turret", 4.0, 4.0),
+    ("recycler", 2.0, 4.0),
+    ("roboport", 4.0, 4.0),
+    ("rocket-silo", 9.0, 9.0),
+    ("rocket-turret", 3.0, 3.0),
+    ("selector-combinator", 1.0, 2.0),
+    ("solar-panel", 3.0, 3.0),
+    ("space-platform-hub", 8.0, 8.0),
+    ("splitter", 2.0, 1.0),
+    ("fast-splitter", 2.0, 1.0),
+    ("express-splitter", 2.0, 1.0),
+    ("turbo-splitter", 2.0, 1.0),
+    ("steam-engine", 3.0, 5.0),
+    ("steam-turbine", 3.0, 5.0),
+    ("steel-furnace", 2.0, 2.0),
+    ("stone-furnace", 2.0, 2.0),
+    ("storage-tank", 3.0, 3.0),
+    ("straight-rail", 2.0, 2.0),
+    ("substation", 2.0, 2.0),
+    ("tesla-turret", 3.0, 3.0),
+    ("train-stop", 2.0, 2.0),
+    // Rolling stock, lying north-south
+    ("artillery-wagon", 2.0, 6.0),
+    ("cargo-wagon", 2.0, 6.0),
+    ("fluid-wagon", 2.0, 6.0),
+    ("locomotive", 2.0, 6.0),
+];
+
+/// The area an entity covers, going by [`ENTITY_SIZES`]. Entities that aren't listed, like modded
+/// ones, count as 1×1. `legacy` is for 1.1 blueprints, which have 8 directions rather than 16.
+fn entity_footprint(entity: &serde_json::Value, legacy: bool) -> Option<Area> {
+    let (x, y) = position(entity)?;
+    let name = entity.get("name").and_then(|name| name.as_str())?;
+    let (mut width, mut height) = ENTITY_SIZES
+        .iter()
+        .find(|(entity, _, _)| *entity == name)
+        .map_or((1.0, 1.0), |&(_, width, height)| (width, height));
+    let direction = entity
+        .get("direction")
+        .and_then(|direction| direction.as_u64())
+        .unwrap_or(0);
+    let sideways = if legacy {
+        direction % 4 == 2
+    } else {
+        direction % 8 == 4
+    };
+    // Rolling stock turns by orientation instead: 0 is north, 0.25 east.
+    let orientation = entity
+        .get("orientation")
+        .and_then(|orientation| orientation.as_f64())
+        .unwrap_or(0.0);
+    let across = (0.125..0.375).contains(&(orientation % 0.5));
+    if sideways || across {
+        (width, height) = (height, width);
+    }
+    Some((
+        (x - width / 2.0, y - height / 2.0),
+        (x + width / 2.0, y + height / 2.0),
+    ))
+}
+
+/// Tiles are 1×1, positioned by their top left corner.
+fn tile_footprint(tile: &serde_json::Value) -> Option<Area> {
+    let (x, y) = position(tile)?;
+    Some(((x, y), (x + 1.0, y + 1.0)))
+}
+
+/// The smallest box containing all of `boxes`.
+fn bounding_box(boxes: impl Iterator<Item = Area>) -> Option<Area> {
+    boxes.reduce(|((min_x, min_y), (max_x, max_y)), ((x1, y1), (x2, y2))| {
+        (
+            (x1.min(min_x), y1.min(min_y)),
+            (x2.max(max_x), y2.max(max_y)),
+        )
+    })
+}
+
+fn snap_to_grid(blueprint: &serde_json::Value) -> String {
+    let Some(grid) = blueprint.get("snap-to-grid") else {
+        return "no".to_owned();
+    };
+    let coordinate = |json: &serde_json::Value, key| {
+        json.get(key)
+            .and_then(|value| value.as_f64())
+            .unwrap_or_default()
+    };
+    let mut result = format!("{}x{}", coordinate(grid, "x"), coordinate(grid, "y"));
+    if blueprint
+        .get("absolute-snapping")
+        .and_then(|absolute| absolute.as_bool())
+        .unwrap_or(false)
+    {
+        result.push_str(", absolute");
+        if let Some(offset) = blueprint.get("position-relative-to-grid") {
+            write!(
+                result,
+                ", offset ({}, {})",
+                coordinate(offset, "x"),
+                coordinate(offset, "y")
+            )
+            .expect("writing to a String can't fail");
+        }
+    } else {
+        result.push_str(", relative");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use super::*;
+
+    #[test]
+    fn test_describe_book() {
+        let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let json = serde_json::Value::from_str(&json).unwrap();
+        let description = describe(&json).unwrap();
+        let headers = description
+            .lines()
+            .filter(|line| line.contains("blueprint"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "blueprint book: (no label)",
+                "  [0] blueprint: BP Name 1",
+                "  [1] blueprint book: Nested Book",
+                "    [6] blueprint: (no label)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_blueprint() {
+        let json = serde_json::json!({
+            "blueprint": {
+                "item": "blueprint",
+                "label": "Grid",
+                "icons": [
+                    {"index": 1, "signal": {"name": "rail"}},
+                    {"index": 2, "signal": {"name": "fast-inserter", "quality": "rare"}},
+                ],
+                "entities": [
+                    {"entity_number": 1, "name": "small-lamp", "position": {"x": -1.5, "y": 0.5}},
+                    {"entity_number": 2, "name": "small-lamp", "position": {"x": 3.5, "y": -2.5}},
+                    {"entity_number": 3, "name": "pump", "position": {"x": 12, "y": 0.5}, "direction": 4},
+                ],
+                "tiles": [{"name": "landfill", "position": {"x": 10, "y": 0}}],
+                "wires": [[1, 1, 2, 1]],
+                "snap-to-grid": {"x": 4, "y": 2},
+                "absolute-snapping": true,
+                "position-relative-to-grid": {"x": 1, "y": 0},
+                "parameters": [{"type": "id", "name": "parameter-0"}],
+                "version": 562949957353472u64,
+            }
+        });
+        assert_eq!(
+            describe(&json).unwrap(),
+            "\
+blueprint: Grid
+  version: 2.0.60.0
+  icons: [icon=rail] [icon=fast-inserter,quality=rare]
+  entities: 3
+  tiles: 1
+  wires: 1
+  bounding box: (-2, -3) to (13, 1)
+  snap to grid: 4x2, absolute, offset (1, 0)
+  parameterized: yes
+"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Helpers for manipulating Factorio blueprint strings.
+//!
+//! This is the library behind the `factorio-bp-helper` command line tool. The main entry points
+//! are [`blueprint_to_json`] and [`json_to_blueprint`] for decoding and encoding blueprint
+//! strings, [`blueprint::model`] for a typed view of the json, and the tools built on top of
+//! them: [`blueprint::count_entities`], [`blueprint::upgrade_quality`], [`info`], [`diff`],
+//! [`canonical`], [`save`] and [`load`]. [`prototypes`] reads the game's prototype dump for tools
+//! that need to know more than the blueprint says.
+//!
+//! Optional cargo features:
+//! - `cli`: the command line tool itself.
+//! - `clipboard`: `--to-clipboard` and the paste prompt in the command line tool.
+//! - `git`: [`load::stamp`], for tagging loaded blueprints with the current git commit, and
+//!   renaming and removing files in the git index when [`save::save`] syncs a directory.
+
+pub mod blueprint;
+pub mod canonical;
+pub mod diff;
+pub mod error;
+pub mod info;
+mod json_walk;
+pub mod load;
+pub mod merge;
+pub mod prototypes;
+pub mod save;
+pub mod table;
+#[cfg(test)]
+mod test_util;
+pub mod textconv;
+
+pub use blueprint::{blueprint_to_json, json_to_blueprint};
+pub use error::{Error, Result};
@@ -1,17 +1,30 @@
+//! Loading blueprints saved by [`crate::save::save`].
+
 use std::{fs, path::Path};
 
 use itertools::Itertools;
 
-use crate::blueprint;
+use crate::{
+    blueprint::BlueprintType,
+    error::{Error, Result},
+};
 
-fn load_file(path: &Path) -> serde_json::Value {
-    serde_json::from_str(
-        &fs::read_to_string(path)
-            .unwrap_or_else(|e| panic!("error reading {path:?}: {e}", path = &path)),
-    )
-    .unwrap_or_else(|e| panic!("invalid json in {path:?}: {e}"))
+fn load_file(path: &Path) -> Result<serde_json::Value> {
+    serde_json::from_str(&fs::read_to_string(path).map_err(Error::io(path))?).map_err(|source| {
+        Error::InvalidJson {
+            path: Some(path.to_owned()),
+            source,
+        }
+    })
 }
-pub fn load(path: &Path) -> serde_json::Value {
+
+/// Loads a blueprint from a json file, or a blueprint book from a directory written by `save`.
+///
+/// Entries of a book are ordered by the number at the start of their file name, falling back to
+/// their `index` in the json for files without one (see [`crate::save::Naming`]). Anything in a
+/// book directory that `save --sync` would leave alone is ignored: files that don't end in
+/// `.json`, json that isn't a blueprint, and directories without a `book.json`.
+pub fn load(path: &Path) -> Result<serde_json::Value> {
     let files = match path
         .read_dir()
         .and_then(|files| files.collect::<Result<Vec<_>, _>>())
@@ -19,24 +32,33 @@ pub fn load(path: &Path) -> serde_json::Value {
         Ok(files) => files,
         Err(e) => match e.kind() {
             std::io::ErrorKind::NotADirectory => return load_file(path),
-            _ => {
-                panic!("error loading {path:?}: {e}");
-            }
+            _ => return Err(Error::io(path)(e)),
         },
     };
     let mut book_json: Option<serde_json::Value> = None;
     let mut entries = vec![];
     for file in files {
         let path = file.path();
-        let json = load(&path);
+        // Leave room for notes, READMEs and so on alongside the blueprints, the same as `save
+        // --sync` does.
+        if path.is_dir() {
+            if !path.join("book.json").is_file() {
+                continue;
+            }
+        } else if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let json = load(&path)?;
         if file.file_name() == "book.json" {
             book_json = Some(json)
-        } else {
-            let json_index: Option<u64> = json.get("index").map(|val| {
-                val.as_number()
-                    .and_then(|num| num.as_u64())
-                    .unwrap_or_else(|| panic!("invalid index {val:?} in {path:?}"))
-            });
+        } else if BlueprintType::<&serde_json::Value>::new(&json).is_ok() {
+            let json_index: Option<u64> = json
+                .get("index")
+                .map(|val| {
+                    val.as_u64()
+                        .ok_or_else(|| Error::invalid_book(&path, format!("invalid index {val}")))
+                })
+                .transpose()?;
             let index = if let Some(filename) = file.file_name().to_str()
                 && let Some((first_word, _)) = filename.split_once(' ')
                 && let Ok(filename_index) = first_word.parse::<u64>()
@@ -54,9 +76,9 @@ pub fn load(path: &Path) -> serde_json::Value {
                 }
                 filename_index
             } else {
-                json_index.unwrap_or_else(|| {
-                    panic!("error: {path:?} missing index, can't load into book")
-                })
+                json_index.ok_or_else(|| {
+                    Error::invalid_book(&path, "missing index, can't load into book")
+                })?
             };
 
             entries.push((path, index, json));
@@ -64,51 +86,59 @@ pub fn load(path: &Path) -> serde_json::Value {
     }
     let book_json_path = path.join("book.json");
     let Some(mut book_json) = book_json else {
-        panic!("{book_json_path:?} not found");
+        return Err(Error::invalid_book(book_json_path, "not found"));
     };
     entries.sort_by_key(|(_path, index, _json)| *index);
     for (l, r) in entries.iter().tuple_windows() {
         if l.1 == r.1 {
-            panic!(
-                "error: {l:?} and {r:?} have same index {index}",
-                l = l.0,
-                r = r.0,
-                index = l.1
-            );
+            return Err(Error::invalid_book(
+                path,
+                format!(
+                    "{l:?} and {r:?} have same index {index}",
+                    l = l.0,
+                    r = r.0,
+                    index = l.1
+                ),
+            ));
         }
     }
     let blueprints = book_json
         .get_mut("blueprint_book")
-        .unwrap_or_else(|| panic!("error: {book_json_path:?} not a blueprint book??"))
+        .ok_or_else(|| Error::invalid_book(&book_json_path, "not a blueprint book"))?
         .get_mut("blueprints")
-        .unwrap_or_else(|| panic!("error: {book_json_path:?} missing empty blueprints array"))
+        .ok_or_else(|| Error::invalid_book(&book_json_path, "missing empty blueprints array"))?
         .as_array_mut()
-        .unwrap_or_else(|| {
-            panic!("error: {book_json_path:?} blueprints wrong type (expected array)")
-        });
+        .ok_or_else(|| {
+            Error::invalid_book(&book_json_path, "blueprints wrong type (expected array)")
+        })?;
     if !blueprints.is_empty() {
-        panic!("error: {book_json_path:?} blueprints array not empty")
+        return Err(Error::invalid_book(
+            &book_json_path,
+            "blueprints array not empty",
+        ));
     }
     *blueprints = entries
         .into_iter()
         .map(|(_path, _index, json)| json)
         .collect();
-    book_json
+    Ok(book_json)
 }
 
-pub(crate) fn stamp(json: &mut serde_json::Value, path: &Path) {
+/// Adds the HEAD commit of the git repository containing `path` to the blueprint's description.
+#[cfg(feature = "git")]
+pub fn stamp(json: &mut serde_json::Value, path: &Path) -> Result<()> {
     let dir = if path.is_dir() {
         path
     } else {
         path.parent().unwrap_or(Path::new("."))
     };
     let repo =
-        gix::discover(dir).unwrap_or_else(|e| panic!("error: couldn't find git repository: {e}"));
+        gix::discover(dir).map_err(|e| Error::Git(format!("couldn't find git repository: {e}")))?;
     let id = repo
         .head_id()
-        .unwrap_or_else(|e| panic!("error: couldn't get HEAD commit: {e}"));
-    let mut bp = blueprint::BlueprintType::<&mut serde_json::Value>::new(json);
-    bp.set_tag_in_description("last_commit", &format!("{id}"));
+        .map_err(|e| Error::Git(format!("couldn't get HEAD commit: {e}")))?;
+    let mut bp = crate::blueprint::BlueprintType::<&mut serde_json::Value>::new(json)?;
+    bp.set_tag_in_description("last_commit", &format!("{id}"))
 }
 
 #[cfg(test)]
@@ -122,13 +152,12 @@ mod tests {
     // See also save tests which check round-tripping.
 
     #[test]
-    #[should_panic = "have same index 1"]
     fn test_load_duplicate() {
         let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
         let dir = tempfile::tempdir().unwrap();
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
-        crate::save::save(json.clone(), Some(dir.path()));
+        crate::save::save(json.clone(), Some(dir.path()), &Default::default()).unwrap();
         let files = read_dir_unwrap(dir.path());
         assert_eq!(files, &["Untitled"]);
         let subfiles = read_dir_unwrap(&dir.path().join("Untitled"));
@@ -140,7 +169,42 @@ mod tests {
         )
         .unwrap();
 
-        let loaded_json = load(&dir.path().join("Untitled"));
-        assert_eq!(loaded_json, json)
+        let err = load(&dir.path().join("Untitled")).unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidBook { reason, .. } if reason.ends_with("have same index 1")),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_load_after_sync() {
+        let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
+        let dir = tempfile::tempdir().unwrap();
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let json = serde_json::Value::from_str(&json).expect("should contain valid json");
+        crate::save::save(json.clone(), Some(dir.path()), &Default::default()).unwrap();
+        let book_dir = dir.path().join("Untitled");
+        std::fs::create_dir(book_dir.join("notes")).unwrap();
+        std::fs::write(book_dir.join("notes").join("todo.txt"), "more belts").unwrap();
+        std::fs::write(book_dir.join("meta.json"), r#"{"author": "me"}"#).unwrap();
+
+        let options = crate::save::SaveOptions {
+            sync: true,
+            ..Default::default()
+        };
+        crate::save::save(json.clone(), Some(dir.path()), &options).unwrap();
+        assert_eq!(
+            read_dir_unwrap(&book_dir),
+            [
+                "0 BP Name 1.json",
+                "1 Nested Book",
+                "book.json",
+                "meta.json",
+                "notes"
+            ]
+        );
+        let mut expected = json;
+        crate::canonical::canonicalize(&mut expected).unwrap();
+        assert_eq!(load(&book_dir).unwrap(), expected);
     }
 }
@@ -1,18 +1,24 @@
-mod blueprint;
-mod json_walk;
-mod load;
-mod save;
-#[cfg(test)]
-mod test_util;
-
 use std::fmt::Write;
-use std::io::{Read, stderr, stdin};
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::str::FromStr;
 
-use blueprint::{blueprint_to_json, json_to_blueprint};
-use clap::{Parser, Subcommand};
-use crossterm::clipboard::CopyToClipboard;
+use clap::{Parser, Subcommand, ValueEnum};
+use factorio_bp_helper::{
+    Error, Result, blueprint,
+    blueprint::{
+        Quality, count_entities, inventory,
+        loadout::{self, Loadout},
+        quality_ladder::QualityLadder,
+        raw_materials,
+        upgrade_quality::{self, QualityRules, Rule, UpgradeOptions},
+    },
+    blueprint_to_json, canonical, diff, info, json_to_blueprint, load, merge,
+    prototypes::Prototypes,
+    save,
+    table::TableFormat,
+    textconv,
+};
 
 /// Collection of factorio blueprint helpers
 #[derive(Parser, Debug)]
@@ -26,29 +32,167 @@ struct Args {
 enum Commands {
     /// Counts the number of items needed to construct the blueprint.
     CountEntities {
+        /// What to count
+        #[arg(long, value_enum, default_value_t = CountInclude::Entities)]
+        include: CountInclude,
+        /// Looks up what places each entity in a `data-raw-dump.json` from `factorio --dump-data`,
+        /// instead of only knowing the base game
+        #[arg(long, value_name = "DATA_RAW_DUMP")]
+        prototypes: Option<PathBuf>,
+        /// Expands the counts into the raw resources they're made from, using the recipes in
+        /// the prototype dump
+        #[arg(long, requires = "prototypes")]
+        raw: bool,
+        /// With `--raw`, items not to expand further, e.g. `--available electronic-circuit,iron-gear-wheel`
+        #[arg(long, requires = "raw", value_delimiter = ',')]
+        available: Vec<String>,
+        /// Also counts red, green and copper wire connections, as `red-wire`, `green-wire` and
+        /// `copper-cable`
+        #[arg(long, conflicts_with_all = ["raw", "breakdown"])]
+        wires: bool,
+        /// Prints only what's missing from an inventory: a csv, tsv or json file, or a blueprint
+        /// string of constant combinators
+        #[arg(long, conflicts_with_all = ["raw", "breakdown"])]
+        inventory: Option<String>,
+        /// With `--inventory`, also prints what's left over
+        #[arg(long, requires = "inventory", conflicts_with = "to_blueprint")]
+        surplus: bool,
+        /// Counts each blueprint in a book separately, with subtotals for each book
+        #[arg(long, conflicts_with_all = ["raw", "to_blueprint"])]
+        breakdown: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CountFormat::Text, conflicts_with = "to_blueprint")]
+        format: CountFormat,
+        /// Order of the counts
+        #[arg(long, value_enum, default_value_t = CountSort::Count, conflicts_with = "raw")]
+        sort: CountSort,
         /// Outputs the counts as a blueprint of a constant combinator
         #[arg(long)]
         to_blueprint: bool,
+        /// With `--to-blueprint`, what to put the items in
+        #[arg(long, value_enum, requires = "to_blueprint", default_value_t = CountLoadout::ConstantCombinator)]
+        loadout: CountLoadout,
+        /// With `--to-blueprint`, the most signals per combinator; more are split across several
+        /// combinators wired together
+        #[arg(long, requires = "to_blueprint", default_value_t = 1000)]
+        max_signals: usize,
+        /// With `--to-blueprint`, puts each quality in its own section of the combinator or requests
+        #[arg(long, requires = "to_blueprint")]
+        by_quality: bool,
         /// Sends the output to the clipboard
         #[arg(long)]
         to_clipboard: bool,
         blueprint_string: Option<String>,
     },
+    /// Summarizes a blueprint: kind, label, version, icons and contents.
+    Info { blueprint_string: Option<String> },
+    /// Compares two blueprints: entities, wires, labels, descriptions and book entries.
+    Diff {
+        /// Blueprint string, json file or directory written by `save`
+        old: String,
+        /// Blueprint string, json file or directory written by `save`
+        new: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
     /// Unwraps a blueprint string to reveal the json representation.
-    Unwrap { blueprint_string: Option<String> },
+    Unwrap {
+        /// Outputs canonical json: sorted, renumbered, one line per entity
+        #[arg(long)]
+        canonical: bool,
+        blueprint_string: Option<String>,
+    },
 
     /// Wraps json from stdin into a blueprint string.
     Wrap {},
 
-    /// Upgrades the quality of recipies/filters/conditions without upgrading entities/modules
+    /// Upgrades the quality of recipies/filters/conditions, and optionally entities/modules/icons
     UpgradeQuality {
+        /// Sets every quality to this one, instead of moving it up a tier
+        #[arg(long, conflicts_with_all = ["steps", "down"])]
+        to: Option<String>,
+        /// How many tiers to move each quality; stops at the top of the ladder (or the bottom)
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+        /// Moves qualities down instead of up
+        #[arg(long)]
+        down: bool,
+        /// Only changes qualities that are currently this one
+        #[arg(long)]
+        from: Option<String>,
+        /// Reads the qualities from a file instead of using the base game's: a json list, worst
+        /// first, or a `data-raw-dump.json` (or just its `quality` section), following each
+        /// quality's `next`
+        #[arg(long, value_name = "FILE")]
+        qualities: Option<PathBuf>,
+        /// Also upgrades the entities themselves
+        #[arg(long)]
+        entities: bool,
+        /// Also upgrades modules (and other items requested for entities)
+        #[arg(long)]
+        modules: bool,
+        /// Also upgrades the icons of blueprints, books and planners
+        #[arg(long)]
+        icons: bool,
+        /// Leaves recipes, filters and signals alone
+        #[arg(long)]
+        no_recipes: bool,
+        /// Upgrades everything: recipes, filters, signals, entities, modules and icons
+        #[arg(long)]
+        everything: bool,
+        /// Reads extra paths to upgrade, ignore or leave alone from a json file, like
+        /// `{"upgrade": ["blueprint.entities[].some_signal.quality"], "ignore": [], "no_upgrade": []}`;
+        /// `*` matches any one key
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
+        /// Asks what to do with qualities at unknown paths instead of failing, and offers to save
+        /// the answers to `--rules`
+        #[arg(long)]
+        interactive: bool,
+        /// Sends the output to the clipboard
+        #[arg(long)]
+        to_clipboard: bool,
+        blueprint_string: Option<String>,
+    },
+    /// Converts a Factorio 1.1 blueprint (or book) to the 2.0 format.
+    Migrate {
         /// Sends the output to the clipboard
         #[arg(long)]
         to_clipboard: bool,
         blueprint_string: Option<String>,
     },
     /// Saves blueprint as a .json file, or as a directory of json files if it's a blueprint book.
-    Save { blueprint_string: Option<String> },
+    Save {
+        /// How to lay out the saved json
+        #[arg(long, value_enum, default_value_t = SaveFormat::Canonical)]
+        format: SaveFormat,
+        /// How to name saved files and directories
+        #[arg(long, value_enum, default_value_t = SaveNaming::Indexed)]
+        naming: SaveNaming,
+        /// Directory to save into, instead of the current directory
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        /// Update an existing file or directory in place: rewrite changed files, remove deleted
+        /// blueprints, and rename (also in git) when labels or indices changed. Not with `--naming
+        /// hash`
+        #[arg(long)]
+        sync: bool,
+        blueprint_string: Option<String>,
+    },
+    /// Lists a blueprint one entity/tile/wire per line, for use as a git textconv filter.
+    GitTextconv {
+        /// Blueprint string, or json or blueprint string file
+        blueprint: String,
+    },
+    /// Three-way merges saved blueprint json, for use as a git merge driver (`%O %A %B`).
+    ///
+    /// Writes the result to OURS. Exits with 1 if there were conflicts.
+    GitMerge {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+    },
     /// Loads previously-saved blueprints from the given file or directory.
     Load {
         file: PathBuf,
@@ -61,121 +205,625 @@ enum Commands {
     },
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// Json, for scripts
+    Json,
+}
+
+/// Layout of json files written by `save`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SaveFormat {
+    /// Sorted keys, entities ordered by position, one line per entity, tile and wire
+    Canonical,
+    /// Pretty-printed json as exported by the game
+    Pretty,
+}
+
+impl From<SaveFormat> for save::Format {
+    fn from(format: SaveFormat) -> Self {
+        match format {
+            SaveFormat::Canonical => save::Format::Canonical,
+            SaveFormat::Pretty => save::Format::Pretty,
+        }
+    }
+}
+
+/// How `save` names files.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SaveNaming {
+    /// Book index and label, e.g. "3 Smelting.json"
+    Indexed,
+    /// Slug of the label, e.g. "smelting.json"; reordering a book doesn't rename files
+    Slug,
+    /// Hash of the contents
+    Hash,
+}
+
+impl From<SaveNaming> for save::Naming {
+    fn from(naming: SaveNaming) -> Self {
+        match naming {
+            SaveNaming::Indexed => save::Naming::Indexed,
+            SaveNaming::Slug => save::Naming::Slug,
+            SaveNaming::Hash => save::Naming::Hash,
+        }
+    }
+}
+
+/// What `count-entities` counts.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CountInclude {
+    /// Entities and tiles
+    Entities,
+    /// Entities and tiles, plus the modules, fuel, ammo etc. they request
+    All,
+    /// Only the modules, fuel, ammo etc. requested by entities
+    Requests,
+}
+
+impl From<CountInclude> for count_entities::Include {
+    fn from(include: CountInclude) -> Self {
+        match include {
+            CountInclude::Entities => count_entities::Include::Entities,
+            CountInclude::All => count_entities::Include::All,
+            CountInclude::Requests => count_entities::Include::Requests,
+        }
+    }
+}
+
+/// Output format of `count-entities`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CountFormat {
+    /// Human-readable text
+    Text,
+    /// Json, for scripts
+    Json,
+    /// Comma-separated name, quality and count columns
+    Csv,
+    /// A markdown table
+    Markdown,
+    /// Tab-separated name, quality and count columns
+    Tsv,
+}
+
+impl CountFormat {
+    fn table(self) -> Option<TableFormat> {
+        match self {
+            CountFormat::Text | CountFormat::Json => None,
+            CountFormat::Csv => Some(TableFormat::Csv),
+            CountFormat::Markdown => Some(TableFormat::Markdown),
+            CountFormat::Tsv => Some(TableFormat::Tsv),
+        }
+    }
+}
+
+/// Order of `count-entities` output.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CountSort {
+    /// Most needed first
+    Count,
+    /// By item name
+    Name,
+    /// By quality, normal first
+    Quality,
+}
+
+impl From<CountSort> for count_entities::SortBy {
+    fn from(sort: CountSort) -> Self {
+        match sort {
+            CountSort::Count => count_entities::SortBy::Count,
+            CountSort::Name => count_entities::SortBy::Name,
+            CountSort::Quality => count_entities::SortBy::Quality,
+        }
+    }
+}
+
+/// What `count-entities --to-blueprint` puts the items in.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CountLoadout {
+    /// Constant combinators outputting the counts as signals
+    ConstantCombinator,
+    /// Cargo wagons with a filtered slot per stack; use `--prototypes` for stack sizes
+    CargoWagons,
+    /// A requester chest requesting the items
+    RequesterChest,
+    /// A space platform hub requesting the items from the planet
+    SpacePlatformHub,
+}
+
+impl CountLoadout {
+    /// The loadout, or `None` for constant combinators.
+    fn loadout(self) -> Option<Loadout> {
+        match self {
+            CountLoadout::ConstantCombinator => None,
+            CountLoadout::CargoWagons => Some(Loadout::CargoWagons),
+            CountLoadout::RequesterChest => Some(Loadout::RequesterChest),
+            CountLoadout::SpacePlatformHub => Some(Loadout::SpacePlatformHub),
+        }
+    }
+}
+
 mod terminal;
 
 impl Commands {
-    fn run(self) {
+    fn run(self) -> Result<ExitCode> {
         match self {
             Commands::CountEntities {
+                include,
+                prototypes,
+                raw,
+                available,
+                wires,
+                inventory,
+                surplus,
+                breakdown,
+                format,
+                sort,
                 to_blueprint,
+                loadout,
+                max_signals,
+                by_quality,
                 to_clipboard,
                 blueprint_string,
             } => {
                 let blueprint_string = if let Some(blueprint_string) = blueprint_string {
                     blueprint_string
                 } else {
-                    terminal::prompt_blueprint()
+                    terminal::prompt_blueprint()?
                 };
-                let json = blueprint::blueprint_to_json(&blueprint_string);
-                let counts = blueprint::count_entities::count(&json);
-                let mut counts = counts
-                    .into_iter()
-                    .map(|(key, count)| (key, i64::try_from(count).unwrap()))
-                    .collect::<Vec<_>>();
-                counts.sort_by_key(|(__key, count)| -count);
+                let json = blueprint::blueprint_to_json(&blueprint_string)?;
+                let mut options = count_entities::CountOptions::default();
+                options.include = include.into();
+                options.prototypes = prototypes.as_deref().map(Prototypes::load).transpose()?;
+                // Sorting by quality follows the dump's qualities, if it has any.
+                let ladder = options
+                    .prototypes
+                    .as_ref()
+                    .and_then(Prototypes::quality_ladder)
+                    .cloned()
+                    .unwrap_or_default();
+                if breakdown {
+                    let mut breakdown = count_entities::breakdown(&json, &options)?;
+                    breakdown.sort(sort.into(), &ladder);
+                    let formatted = match format {
+                        CountFormat::Text => breakdown.to_string(),
+                        CountFormat::Json => serde_json::to_string_pretty(&breakdown)?,
+                        CountFormat::Csv | CountFormat::Markdown | CountFormat::Tsv => {
+                            breakdown.to_table(format.table().expect("a table format"))
+                        }
+                    };
+                    output_counts(formatted.trim_end(), to_clipboard)?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+                let mut counts = count_entities::count(&json, &options)?;
+                let mut wire_counts = if wires {
+                    count_entities::count_wires(&json)?.item_counts()
+                } else {
+                    Vec::new()
+                };
+                if let Some(inventory) = inventory {
+                    let inventory = read_inventory_arg(&inventory)?;
+                    for wire in wire_counts.drain(..) {
+                        counts.insert((wire.name, Quality(None)), wire.count);
+                    }
+                    let mut comparison = inventory::compare(&counts, &inventory);
+                    comparison.sort(sort.into(), &ladder);
+                    if to_blueprint {
+                        // The combinator below is made from what's missing.
+                        counts = comparison
+                            .shortfall
+                            .into_iter()
+                            .map(|count| ((count.name, Quality(count.quality)), count.count))
+                            .collect();
+                    } else {
+                        let formatted = match format {
+                            CountFormat::Text => comparison.to_text(surplus),
+                            CountFormat::Json if surplus => {
+                                serde_json::to_string_pretty(&comparison)?
+                            }
+                            CountFormat::Json => serde_json::to_string_pretty(
+                                &serde_json::json!({"shortfall": comparison.shortfall}),
+                            )?,
+                            CountFormat::Csv | CountFormat::Markdown | CountFormat::Tsv => {
+                                comparison
+                                    .to_table(format.table().expect("a table format"), surplus)
+                            }
+                        };
+                        output_counts(formatted.trim_end(), to_clipboard)?;
+                        return Ok(ExitCode::SUCCESS);
+                    }
+                }
+                let raw = raw.then(|| {
+                    let prototypes = options
+                        .prototypes
+                        .as_ref()
+                        .expect("clap requires --prototypes with --raw");
+                    let available = available.into_iter().collect();
+                    raw_materials::expand(&counts, prototypes, &available)
+                });
                 if to_blueprint {
-                    let combinator = blueprint::make_constant_combinator_json(counts);
+                    for wire in &wire_counts {
+                        counts.insert((wire.name.clone(), Quality(None)), wire.count);
+                    }
+                    let counts = match &raw {
+                        // Signals and requests are whole numbers; round up so there's enough.
+                        Some(raw) => raw
+                            .totals
+                            .iter()
+                            .map(|(name, amount)| {
+                                ((name.clone(), Quality(None)), amount.ceil() as u64)
+                            })
+                            .collect(),
+                        None => counts,
+                    };
+                    let combinator = match loadout.loadout() {
+                        None => {
+                            let mut counts = counts
+                                .into_iter()
+                                .map(|(key, count)| (key, i64::try_from(count).unwrap_or(i64::MAX)))
+                                .collect::<Vec<_>>();
+                            counts.sort_by_key(|(__key, count)| -count);
+                            let mut combinator_options = blueprint::CombinatorOptions::default();
+                            combinator_options.max_signals = max_signals;
+                            combinator_options.by_quality = by_quality;
+                            combinator_options.ladder = ladder.clone();
+                            blueprint::make_constant_combinator_json(counts, &combinator_options)
+                        }
+                        Some(loadout) => loadout::make_loadout_json(
+                            &counts,
+                            loadout,
+                            options.prototypes.as_ref(),
+                            by_quality,
+                            &ladder,
+                        ),
+                    };
                     let bp = blueprint::json_to_blueprint(combinator);
                     if to_clipboard {
-                        crossterm::execute!(stderr(), CopyToClipboard::to_clipboard_from(bp))
-                            .unwrap();
+                        terminal::copy_to_clipboard(&bp)?;
                         println!("blueprint copied to clipboard.")
                     } else {
                         println!("{bp}");
                     }
                 } else {
-                    let mut formatted = String::new();
-                    for ((name, quality), count) in counts {
-                        writeln!(
-                            formatted,
-                            "{count} {name}{quality}",
-                            quality = quality.fmt_suffix()
-                        )
-                        .expect("a Display implementation returned an error unexpectedly");
-                    }
-                    let formatted = formatted.trim_end();
-                    if to_clipboard {
-                        crossterm::execute!(
-                            stderr(),
-                            CopyToClipboard::to_clipboard_from(formatted)
-                        )
-                        .unwrap();
-                        println!("counts copied to clipboard.");
+                    let formatted = if let Some(raw) = raw {
+                        match format {
+                            CountFormat::Text => raw.to_string(),
+                            CountFormat::Json => serde_json::to_string_pretty(&raw)?,
+                            CountFormat::Csv | CountFormat::Markdown | CountFormat::Tsv => {
+                                raw.to_table(format.table().expect("a table format"))
+                            }
+                        }
                     } else {
-                        println!("{formatted}");
-                    }
+                        let mut counts = count_entities::item_counts(counts);
+                        count_entities::sort_counts(&mut counts, sort.into(), &ladder);
+                        match format {
+                            CountFormat::Text => {
+                                let mut formatted = String::new();
+                                for count in counts {
+                                    writeln!(formatted, "{count}").expect(
+                                        "a Display implementation returned an error unexpectedly",
+                                    );
+                                }
+                                if wires {
+                                    formatted.push_str("\nwires:\n");
+                                    if wire_counts.is_empty() {
+                                        formatted.push_str("  none\n");
+                                    }
+                                    for count in wire_counts {
+                                        writeln!(formatted, "  {count}").expect(
+                                            "a Display implementation returned an error unexpectedly",
+                                        );
+                                    }
+                                }
+                                formatted
+                            }
+                            CountFormat::Json => {
+                                counts.extend(wire_counts);
+                                serde_json::to_string_pretty(&counts)?
+                            }
+                            CountFormat::Csv | CountFormat::Markdown | CountFormat::Tsv => {
+                                counts.extend(wire_counts);
+                                count_entities::counts_table(
+                                    &counts,
+                                    format.table().expect("a table format"),
+                                )
+                            }
+                        }
+                    };
+                    output_counts(formatted.trim_end(), to_clipboard)?;
+                }
+            }
+            Commands::Info { blueprint_string } => {
+                let blueprint_string = match blueprint_string {
+                    Some(blueprint_string) => blueprint_string,
+                    None => terminal::prompt_blueprint()?,
+                };
+                let json = blueprint_to_json(&blueprint_string)?;
+                let json = serde_json::Value::from_str(&json)?;
+                print!("{}", info::describe(&json)?);
+            }
+            Commands::Diff { old, new, format } => {
+                let diff = diff::diff(&read_blueprint_arg(&old)?, &read_blueprint_arg(&new)?)?;
+                match format {
+                    OutputFormat::Text => print!("{diff}"),
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
                 }
             }
-            Commands::Unwrap { blueprint_string } => {
-                let blueprint_string = blueprint_string.unwrap_or_else(terminal::prompt_blueprint);
-                let json = blueprint_to_json(&blueprint_string);
-                println!("{json}");
+            Commands::Unwrap {
+                canonical,
+                blueprint_string,
+            } => {
+                let blueprint_string = match blueprint_string {
+                    Some(blueprint_string) => blueprint_string,
+                    None => terminal::prompt_blueprint()?,
+                };
+                let json = blueprint_to_json(&blueprint_string)?;
+                if canonical {
+                    let json = serde_json::Value::from_str(&json)?;
+                    print!("{}", canonical::to_canonical_string(json)?);
+                } else {
+                    println!("{json}");
+                }
             }
             Commands::Wrap {} => {
-                let mut buf = String::new();
-                stdin().read_to_string(&mut buf).unwrap();
-                let blueprint_string = json_to_blueprint(serde_json::from_str(&buf).unwrap());
+                let buf = terminal::read_stdin()?;
+                let blueprint_string = json_to_blueprint(serde_json::from_str(&buf)?);
                 println!("{blueprint_string}");
             }
             Commands::UpgradeQuality {
+                to,
+                steps,
+                down,
+                from,
+                qualities,
+                entities,
+                modules,
+                icons,
+                no_recipes,
+                everything,
+                rules,
+                interactive,
                 to_clipboard,
                 blueprint_string,
             } => {
                 let blueprint_string = if let Some(blueprint_string) = blueprint_string {
                     blueprint_string
                 } else {
-                    terminal::prompt_blueprint()
+                    terminal::prompt_blueprint()?
+                };
+                let json = blueprint::blueprint_to_json(&blueprint_string)?;
+                let json: serde_json::Value = serde_json::from_str(&json)?;
+                let mut options = if everything {
+                    UpgradeOptions::everything()
+                } else {
+                    UpgradeOptions::default()
+                };
+                options.entities |= entities;
+                options.modules |= modules;
+                options.icons |= icons;
+                options.recipes = !no_recipes;
+                options.to = to;
+                options.steps = steps;
+                options.down = down;
+                options.from = from;
+                if let Some(qualities) = qualities {
+                    options.ladder = QualityLadder::load(&qualities)?;
+                }
+                // Interactively, answers can go into a rules file that doesn't exist yet.
+                if let Some(rules) = &rules
+                    && (!interactive || rules.exists())
+                {
+                    options.rules = QualityRules::load(rules)?;
+                }
+                let (json, summary) = loop {
+                    match upgrade_quality::upgrade_with_options(json.clone(), &options) {
+                        Err(Error::UnhandledQuality(path)) if interactive => {
+                            let pattern = upgrade_quality::rule_pattern(&path);
+                            let rule = match terminal::ask(
+                                &format!(
+                                    "unknown quality at {pattern}: [u]pgrade, [s]kip or [a]bort?"
+                                ),
+                                &['u', 's', 'a'],
+                            )? {
+                                'u' => Rule::Upgrade,
+                                's' => Rule::NoUpgrade,
+                                _ => return Err(Error::Cancelled),
+                            };
+                            options.rules.add(rule, pattern);
+                            if let Some(rules) = &rules
+                                && terminal::ask(
+                                    &format!("save to {rules:?}? [y]es or [n]o"),
+                                    &['y', 'n'],
+                                )? == 'y'
+                            {
+                                options.rules.save(rules)?;
+                            }
+                        }
+                        result => break result?,
+                    }
+                };
+                // The blueprint goes to stdout, so the summary goes to stderr.
+                eprint!("{summary}");
+                let bp = blueprint::json_to_blueprint(json);
+                if to_clipboard {
+                    terminal::copy_to_clipboard(&bp)?;
+                    println!("blueprint copied to clipboard.")
+                } else {
+                    println!("{bp}");
+                }
+            }
+            Commands::Migrate {
+                to_clipboard,
+                blueprint_string,
+            } => {
+                let blueprint_string = match blueprint_string {
+                    Some(blueprint_string) => blueprint_string,
+                    None => terminal::prompt_blueprint()?,
                 };
-                let json = blueprint::blueprint_to_json(&blueprint_string);
-                let json: serde_json::Value =
-                    serde_json::from_str(&json).expect("blueprint should contain valid json");
-                let bp = blueprint::json_to_blueprint(blueprint::upgrade_quality::upgrade(json));
+                let json = blueprint_to_json(&blueprint_string)?;
+                let json = serde_json::Value::from_str(&json)?;
+                let migrated = blueprint::migrate::migrate(json)?;
+                for unmigrated in &migrated.unmigrated {
+                    eprintln!("WARN: {unmigrated}");
+                }
+                let bp = json_to_blueprint(migrated.json);
                 if to_clipboard {
-                    crossterm::execute!(stderr(), CopyToClipboard::to_clipboard_from(bp)).unwrap();
+                    terminal::copy_to_clipboard(&bp)?;
                     println!("blueprint copied to clipboard.")
                 } else {
                     println!("{bp}");
                 }
             }
-            Commands::Save { blueprint_string } => {
-                let blueprint_string = blueprint_string.unwrap_or_else(terminal::prompt_blueprint);
-                let json = blueprint_to_json(&blueprint_string);
-                let json = serde_json::Value::from_str(&json).expect("should contain valid json");
+            Commands::Save {
+                format,
+                naming,
+                output_dir,
+                sync,
+                blueprint_string,
+            } => {
+                let blueprint_string = match blueprint_string {
+                    Some(blueprint_string) => blueprint_string,
+                    None => terminal::prompt_blueprint()?,
+                };
+                let json = blueprint_to_json(&blueprint_string)?;
+                let json = serde_json::Value::from_str(&json)?;
 
-                save::save(json, None);
+                let mut options = save::SaveOptions::default();
+                options.format = format.into();
+                options.naming = naming.into();
+                options.sync = sync;
+                let actions = save::save(json, output_dir.as_deref(), &options)?;
+                for action in &actions {
+                    if !matches!(action, save::Action::Unchanged(_)) {
+                        println!("{action}");
+                    }
+                }
+                if sync {
+                    let count = |kind: fn(&save::Action) -> bool| {
+                        actions.iter().filter(|action| kind(action)).count()
+                    };
+                    println!(
+                        "{} created, {} updated, {} renamed, {} removed, {} unchanged.",
+                        count(|action| matches!(action, save::Action::Created(_))),
+                        count(|action| matches!(action, save::Action::Updated(_))),
+                        count(|action| matches!(action, save::Action::Renamed { .. })),
+                        count(|action| matches!(action, save::Action::Removed(_))),
+                        count(|action| matches!(action, save::Action::Unchanged(_))),
+                    );
+                }
+            }
+            Commands::GitTextconv { blueprint } => {
+                print!("{}", textconv::textconv(&read_blueprint_arg(&blueprint)?)?);
+            }
+            Commands::GitMerge { base, ours, theirs } => {
+                let merged = merge::merge(
+                    &read_blueprint_file(&base)?,
+                    &read_blueprint_file(&ours)?,
+                    &read_blueprint_file(&theirs)?,
+                )?;
+                std::fs::write(&ours, merged.text).map_err(|source| Error::Io {
+                    path: ours.clone(),
+                    source,
+                })?;
+                if merged.conflicts > 0 {
+                    eprintln!("{} conflicts in {ours:?}", merged.conflicts);
+                    return Ok(ExitCode::FAILURE);
+                }
             }
             Commands::Load {
                 file,
                 to_clipboard,
                 stamp,
             } => {
-                let mut json = load::load(&file);
+                let mut json = load::load(&file)?;
                 if stamp {
-                    load::stamp(&mut json, &file);
+                    stamp_json(&mut json, &file)?;
                 }
                 let bp = json_to_blueprint(json);
                 if to_clipboard {
-                    crossterm::execute!(stderr(), CopyToClipboard::to_clipboard_from(bp)).unwrap();
+                    terminal::copy_to_clipboard(&bp)?;
                     println!("blueprint copied to clipboard.")
                 } else {
                     println!("{bp}");
                 }
             }
         }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Prints the output of `count-entities`, or copies it to the clipboard.
+fn output_counts(formatted: &str, to_clipboard: bool) -> Result<()> {
+    if to_clipboard {
+        terminal::copy_to_clipboard(formatted)?;
+        println!("counts copied to clipboard.");
+    } else {
+        println!("{formatted}");
+    }
+    Ok(())
+}
+
+/// Reads `count-entities --inventory`: a file if there's one at that path, else a blueprint string.
+fn read_inventory_arg(arg: &str) -> Result<count_entities::Counts> {
+    let path = std::path::Path::new(arg);
+    if path.is_file() {
+        inventory::parse(&std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?)
+    } else {
+        inventory::parse(arg)
+    }
+}
+
+/// Reads a blueprint given on the command line as a path to a saved file or directory, or as a
+/// blueprint string.
+fn read_blueprint_arg(arg: &str) -> Result<serde_json::Value> {
+    let path = std::path::Path::new(arg);
+    if path.is_file() {
+        read_blueprint_file(path)
+    } else if path.exists() {
+        load::load(path)
+    } else {
+        Ok(serde_json::Value::from_str(&blueprint_to_json(arg)?)?)
+    }
+}
+
+/// Reads a file containing either json or a blueprint string.
+fn read_blueprint_file(path: &std::path::Path) -> Result<serde_json::Value> {
+    let contents = std::fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    if contents.trim_start().starts_with('{') {
+        serde_json::from_str(&contents).map_err(|source| Error::InvalidJson {
+            path: Some(path.to_owned()),
+            source,
+        })
+    } else {
+        Ok(serde_json::Value::from_str(&blueprint_to_json(&contents)?)?)
     }
 }
 
-fn main() {
+#[cfg(feature = "git")]
+fn stamp_json(json: &mut serde_json::Value, file: &std::path::Path) -> Result<()> {
+    load::stamp(json, file)
+}
+
+#[cfg(not(feature = "git"))]
+fn stamp_json(_json: &mut serde_json::Value, _file: &std::path::Path) -> Result<()> {
+    Err(Error::Git("built without git support".to_owned()))
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    args.command.run();
+    match args.command.run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
@@ -0,0 +1,871 @@
+//! Three-way merging of saved blueprint json, meant for use as a git merge driver.
+//!
+//! Blueprints are merged entity by entity, keyed by name, position and direction, so two people editing
+//! different parts of the same blueprint merge cleanly. Wires are merged as a set. Other fields
+//! (label, description, icons, planner settings, ...) are merged one field at a time.
+//!
+//! Where the same entity or field changed differently on both sides, the output contains git-style
+//! conflict markers around just those lines, and isn't valid json until they're resolved.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    blueprint::BlueprintType,
+    canonical,
+    error::{Error, Result},
+};
+
+/// The kind keys a blueprint entry can have; see [`BlueprintType`].
+const KINDS: &[&str] = &[
+    "blueprint",
+    "blueprint_book",
+    "upgrade_planner",
+    "deconstruction_planner",
+];
+
+/// The result of [`merge`].
+#[derive(Debug)]
+pub struct Merged {
+    /// The merged json in [`canonical`] layout, with conflict markers if there
+    /// were conflicts.
+    pub text: String,
+    /// How many entities or fields conflicted.
+    pub conflicts: usize,
+}
+
+/// An entity (or tile) identity: name, position and direction, with `-0.0` and `0.0` treated as
+/// the same. The direction tells apart rails crossing each other.
+type Key = (String, u64, u64, u64);
+
+type WireKey = ((Key, u8), (Key, u8));
+
+/// Something that changed differently on both sides.
+struct Conflict {
+    /// How deeply nested the conflicting lines are in the canonical layout.
+    depth: usize,
+    /// Which field or entity conflicted.
+    what: Conflicted,
+    ours: Option<Value>,
+    theirs: Option<Value>,
+}
+
+enum Conflicted {
+    Field(String),
+    Entity(Key),
+    Tile(Key),
+}
+
+/// Merges `ours` and `theirs`, which were both changed from `base`.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Result<Merged> {
+    let kind = |json| -> Result<&'static str> {
+        let bp = BlueprintType::<&Value>::new(json)?;
+        Ok(bp.kind_name())
+    };
+    if kind(base)? != kind(ours)? || kind(ours)? != kind(theirs)? {
+        // Nothing sensible to merge; conflict on the whole file.
+        return Ok(Merged {
+            text: format!(
+                "<<<<<<< ours\n{}=======\n{}>>>>>>> theirs\n",
+                canonical::to_canonical_string(ours.clone())?,
+                canonical::to_canonical_string(theirs.clone())?
+            ),
+            conflicts: 1,
+        });
+    }
+    let kind_key = KINDS
+        .iter()
+        .copied()
+        .find(|key| ours.get(key).is_some())
+        .expect("BlueprintType::new found a kind");
+    let object = |json: &Value| -> Result<Map<String, Value>> {
+        json.as_object()
+            .cloned()
+            .ok_or_else(|| Error::InvalidBlueprint("blueprint should be a json object".into()))
+    };
+
+    let mut conflicts = vec![];
+    let mut merged = merge_fields(
+        &object(base)?,
+        &object(ours)?,
+        &object(theirs)?,
+        &[kind_key],
+        1,
+        &mut conflicts,
+    );
+    let inner = |json: &Value| object(&json[kind_key]);
+    let inner = if kind_key == "blueprint" {
+        merge_blueprint(inner(base)?, inner(ours)?, inner(theirs)?, &mut conflicts)?
+    } else {
+        merge_fields(
+            &inner(base)?,
+            &inner(ours)?,
+            &inner(theirs)?,
+            &[],
+            2,
+            &mut conflicts,
+        )
+    };
+    merged.insert(kind_key.to_owned(), Value::Object(inner));
+    let mut merged = Value::Object(merged);
+    canonical::canonicalize(&mut merged)?;
+
+    let numbers = entity_numbers(&merged[kind_key]);
+    for conflict in &mut conflicts {
+        canonicalize_sides(conflict, &numbers)?;
+    }
+    let text = canonical::format_canonical_with_conflicts(&merged, &|path, value| {
+        find_conflict(&conflicts, path, value)
+    });
+    Ok(Merged {
+        text,
+        conflicts: conflicts.len(),
+    })
+}
+
+/// Picks the side that changed, or `None` if both changed differently.
+fn three_way<T: PartialEq>(base: T, ours: T, theirs: T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours)
+    } else if ours == base {
+        Some(theirs)
+    } else {
+        None
+    }
+}
+
+/// Merges the fields of an object one by one, leaving out `skip`.
+fn merge_fields(
+    base: &Map<String, Value>,
+    ours: &Map<String, Value>,
+    theirs: &Map<String, Value>,
+    skip: &[&str],
+    depth: usize,
+    conflicts: &mut Vec<Conflict>,
+) -> Map<String, Value> {
+    let mut merged = Map::new();
+    let keys = ours
+        .keys()
+        .chain(theirs.keys())
+        .chain(base.keys())
+        .filter(|key| !skip.contains(&key.as_str()))
+        .collect::<BTreeSet<_>>();
+    for key in keys {
+        let (ours, theirs) = (ours.get(key), theirs.get(key));
+        let value = match three_way(base.get(key), ours, theirs) {
+            Some(value) => value,
+            // Re-exporting bumps the version; that's not worth a conflict.
+            None if key == "version" => ours
+                .into_iter()
+                .chain(theirs)
+                .max_by_key(|value| value.as_u64()),
+            None => {
+                conflicts.push(Conflict {
+                    depth,
+                    what: Conflicted::Field(key.clone()),
+                    ours: ours.cloned(),
+                    theirs: theirs.cloned(),
+                });
+                ours.or(theirs)
+            }
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+fn position_key(json: &Value, name: &str) -> Key {
+    let coordinate = |key| {
+        json.get("position")
+            .and_then(|position| position.get(key))
+            .and_then(Value::as_f64)
+            .unwrap_or_default()
+            + 0.0
+    };
+    (
+        name.to_owned(),
+        coordinate("x").to_bits(),
+        coordinate("y").to_bits(),
+        json.get("direction")
+            .and_then(Value::as_u64)
+            .unwrap_or_default(),
+    )
+}
+
+fn entity_key(entity: &Value) -> Key {
+    position_key(
+        entity,
+        entity
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+    )
+}
+
+/// Tiles are keyed by position alone: two tiles can't share a position.
+fn tile_key(tile: &Value) -> Key {
+    position_key(tile, "")
+}
+
+fn key_to_json((name, x, y, direction): &Key) -> Value {
+    json!([name, f64::from_bits(*x), f64::from_bits(*y), direction])
+}
+
+fn key_from_json(json: &Value) -> Option<Key> {
+    let name = json.get(0)?.as_str()?;
+    let x = json.get(1)?.as_f64()?;
+    let y = json.get(2)?.as_f64()?;
+    let direction = json.get(3)?.as_u64()?;
+    Some((
+        name.to_owned(),
+        (x + 0.0).to_bits(),
+        (y + 0.0).to_bits(),
+        direction,
+    ))
+}
+
+fn array(json: &Map<String, Value>, key: &str) -> Vec<Value> {
+    json.get(key)
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// A blueprint with entities identified by [`Key`] instead of `entity_number`.
+struct Keyed {
+    fields: Map<String, Value>,
+    entities: Vec<(Key, Value)>,
+    tiles: Vec<(Key, Value)>,
+    wires: HashSet<WireKey>,
+}
+
+impl Keyed {
+    fn new(mut blueprint: Map<String, Value>) -> Keyed {
+        let mut keys = HashMap::new();
+        let entities = array(&blueprint, "entities")
+            .into_iter()
+            .map(|mut entity| {
+                let key = entity_key(&entity);
+                if let Some(number) = entity
+                    .as_object_mut()
+                    .and_then(|entity| entity.remove("entity_number"))
+                    .and_then(|number| number.as_u64())
+                {
+                    keys.insert(number, key.clone());
+                }
+                (key, entity)
+            })
+            .collect();
+        let tiles = array(&blueprint, "tiles")
+            .into_iter()
+            .map(|tile| (tile_key(&tile), tile))
+            .collect();
+        let wires = array(&blueprint, "wires")
+            .iter()
+            .filter_map(|wire| {
+                let end = |i: usize| -> Option<(Key, u8)> {
+                    let key = keys.get(&wire.get(i)?.as_u64()?)?;
+                    Some((key.clone(), u8::try_from(wire.get(i + 1)?.as_u64()?).ok()?))
+                };
+                let (a, b) = (end(0)?, end(2)?);
+                Some(if a <= b { (a, b) } else { (b, a) })
+            })
+            .collect();
+        for (field, value) in blueprint.iter_mut() {
+            for reference in entity_references(field, value) {
+                if let Some(key) = reference.as_u64().and_then(|number| keys.get(&number)) {
+                    *reference = key_to_json(key);
+                }
+            }
+        }
+        for key in ["entities", "tiles", "wires"] {
+            blueprint.remove(key);
+        }
+        Keyed {
+            fields: blueprint,
+            entities,
+            tiles,
+            wires,
+        }
+    }
+}
+
+/// Merges entities (or tiles) by key.
+///
+/// Entities stacked on the same key are merged as a multiset: if that can't be done cleanly, the
+/// key conflicts on the first of each side's entities there.
+fn merge_keyed(
+    base: &[(Key, Value)],
+    ours: &[(Key, Value)],
+    theirs: &[(Key, Value)],
+    conflicted: fn(Key) -> Conflicted,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<(Key, Value)> {
+    fn lookup(items: &[(Key, Value)]) -> HashMap<&Key, Vec<&Value>> {
+        let mut map = HashMap::<_, Vec<_>>::new();
+        for (key, value) in items {
+            map.entry(key).or_default().push(value);
+        }
+        for values in map.values_mut() {
+            values.sort_by_cached_key(|value| value.to_string());
+        }
+        map
+    }
+    fn values<'a>(map: &'a HashMap<&Key, Vec<&'a Value>>, key: &Key) -> &'a [&'a Value] {
+        map.get(key).map_or(&[], Vec::as_slice)
+    }
+    let (base_map, ours_map, theirs_map) = (lookup(base), lookup(ours), lookup(theirs));
+    let mut seen = HashSet::new();
+    let mut merged = vec![];
+    for (key, _) in ours.iter().chain(theirs).chain(base) {
+        if !seen.insert(key) {
+            continue;
+        }
+        let (ours, theirs) = (values(&ours_map, key), values(&theirs_map, key));
+        let values = three_way(values(&base_map, key), ours, theirs).unwrap_or_else(|| {
+            conflicts.push(Conflict {
+                depth: 3,
+                what: conflicted(key.clone()),
+                ours: ours.first().copied().cloned(),
+                theirs: theirs.first().copied().cloned(),
+            });
+            if ours.is_empty() {
+                &theirs[..1]
+            } else {
+                &ours[..1]
+            }
+        });
+        merged.extend(values.iter().map(|&value| (key.clone(), value.clone())));
+    }
+    merged
+}
+
+fn merge_blueprint(
+    base: Map<String, Value>,
+    ours: Map<String, Value>,
+    theirs: Map<String, Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<Map<String, Value>> {
+    let (base, ours, theirs) = (Keyed::new(base), Keyed::new(ours), Keyed::new(theirs));
+    let mut merged = merge_fields(
+        &base.fields,
+        &ours.fields,
+        &theirs.fields,
+        &[],
+        2,
+        conflicts,
+    );
+    let entities = merge_keyed(
+        &base.entities,
+        &ours.entities,
+        &theirs.entities,
+        Conflicted::Entity,
+        conflicts,
+    );
+    let tiles = merge_keyed(
+        &base.tiles,
+        &ours.tiles,
+        &theirs.tiles,
+        Conflicted::Tile,
+        conflicts,
+    );
+    // A wire is kept if both sides have it, or one side added it.
+    let wires = ours
+        .wires
+        .iter()
+        .chain(theirs.wires.difference(&ours.wires))
+        .filter(|wire| {
+            let (in_ours, in_theirs) = (ours.wires.contains(wire), theirs.wires.contains(wire));
+            (in_ours && in_theirs) || !base.wires.contains(wire)
+        });
+
+    let numbers = entities
+        .iter()
+        .zip(1u64..)
+        .map(|((key, _), number)| (key, number))
+        .collect::<HashMap<_, _>>();
+    let mut wires = wires
+        .filter_map(|((a, a_connector), (b, b_connector))| {
+            Some(json!([
+                numbers.get(a)?,
+                a_connector,
+                numbers.get(b)?,
+                b_connector
+            ]))
+        })
+        .collect::<Vec<_>>();
+    wires.sort_by_key(|wire| wire.to_string());
+    for (field, value) in merged.iter_mut() {
+        renumber_references(field, value, &|key| numbers.get(key).copied());
+    }
+    let entities = entities
+        .iter()
+        .map(|(key, entity)| {
+            let mut entity = entity.clone();
+            entity["entity_number"] = json!(numbers[key]);
+            entity
+        })
+        .collect::<Vec<_>>();
+    for (key, values) in [
+        ("entities", entities),
+        ("tiles", tiles.into_iter().map(|(_, tile)| tile).collect()),
+        ("wires", wires),
+    ] {
+        if !values.is_empty() {
+            merged.insert(key.to_owned(), Value::Array(values));
+        }
+    }
+    Ok(merged)
+}
+
+/// The entity numbers in a blueprint field other than `entities` and `wires`: train schedules'
+/// `locomotives`, and the `stock`, `front` and `back` of `stock_connections`.
+fn entity_references<'a>(field: &str, value: &'a mut Value) -> Vec<&'a mut Value> {
+    let elements = value.as_array_mut().into_iter().flatten();
+    match field {
+        "schedules" => elements
+            .filter_map(|schedule| schedule.get_mut("locomotives")?.as_array_mut())
+            .flatten()
+            .collect(),
+        "stock_connections" => elements
+            .filter_map(Value::as_object_mut)
+            .flat_map(|connection| {
+                connection
+                    .iter_mut()
+                    .filter(|(key, _)| ["stock", "front", "back"].contains(&key.as_str()))
+                    .map(|(_, stock)| stock)
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Turns the [`Key`]s that [`Keyed::new`] put in [`entity_references`] back into entity numbers,
+/// dropping locomotives and stock connections to rolling stock that no longer exists.
+fn renumber_references(field: &str, value: &mut Value, number: &dyn Fn(&Key) -> Option<u64>) {
+    // Whether a reference is still to an entity, after renumbering it.
+    let renumber =
+        |reference: &mut Value| match key_from_json(reference).and_then(|key| number(&key)) {
+            Some(new) => {
+                *reference = json!(new);
+                true
+            }
+            None => reference.is_u64(),
+        };
+    let Some(elements) = value.as_array_mut() else {
+        return;
+    };
+    match field {
+        "schedules" => {
+            for locomotives in elements
+                .iter_mut()
+                .filter_map(|schedule| schedule.get_mut("locomotives")?.as_array_mut())
+            {
+                locomotives.retain_mut(renumber);
+            }
+        }
+        "stock_connections" => elements.retain_mut(|connection| {
+            let Some(connection) = connection.as_object_mut() else {
+                return true;
+            };
+            connection
+                .retain(|key, stock| !matches!(key.as_str(), "front" | "back") || renumber(stock));
+            connection.get_mut("stock").is_none_or(renumber)
+        }),
+        _ => {}
+    }
+}
+
+/// The final `entity_number` of each entity in a canonicalized blueprint.
+fn entity_numbers(blueprint: &Value) -> HashMap<Key, u64> {
+    blueprint
+        .get("entities")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entity| Some((entity_key(entity), entity.get("entity_number")?.as_u64()?)))
+        .collect()
+}
+
+/// Puts our and their sides of `conflict` in the canonical form the merged blueprint is in.
+fn canonicalize_sides(conflict: &mut Conflict, numbers: &HashMap<Key, u64>) -> Result<()> {
+    for value in [&mut conflict.ours, &mut conflict.theirs]
+        .into_iter()
+        .flatten()
+    {
+        canonical::normalize_numbers(value);
+        match &conflict.what {
+            Conflicted::Field(key) if key == "blueprints" => {
+                for entry in value.as_array_mut().into_iter().flatten() {
+                    canonical::canonicalize(entry)?;
+                }
+            }
+            Conflicted::Field(key) => {
+                renumber_references(key, value, &|key| numbers.get(key).copied());
+            }
+            Conflicted::Tile(_) => {}
+            Conflicted::Entity(key) => {
+                if let Some(number) = numbers.get(key) {
+                    value["entity_number"] = json!(number);
+                }
+            }
+        }
+        value.sort_all_objects();
+    }
+    Ok(())
+}
+
+/// Our and their sides of the conflict at `path` in the merged json, if there is one there; see
+/// [`canonical::FindConflict`].
+fn find_conflict(
+    conflicts: &[Conflict],
+    path: &[String],
+    value: &Value,
+) -> Option<canonical::Sides> {
+    let path = path.iter().map(String::as_str).collect::<Vec<_>>();
+    let conflict = conflicts.iter().find(|conflict| {
+        path.len() == conflict.depth
+            && match (&conflict.what, &path[..]) {
+                (Conflicted::Field(key), [.., field]) => key == field,
+                (Conflicted::Entity(key), [.., "entities", "[]"]) => entity_key(value) == *key,
+                (Conflicted::Tile(key), [.., "tiles", "[]"]) => tile_key(value) == *key,
+                _ => false,
+            }
+    })?;
+    Some((conflict.ours.clone(), conflict.theirs.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blueprint(label: &str, entities: Value, wires: Value) -> Value {
+        json!({
+            "blueprint": {
+                "item": "blueprint",
+                "label": label,
+                "entities": entities,
+                "wires": wires,
+                "version": 562949957353472u64,
+            }
+        })
+    }
+
+    #[test]
+    fn test_merge_clean() {
+        let lamp = |number, x: f64| json!({"entity_number": number, "name": "small-lamp", "position": {"x": x, "y": 0.5}});
+        let base = blueprint(
+            "Lamps",
+            json!([lamp(1, 0.5), lamp(2, 2.5)]),
+            json!([[1, 1, 2, 1]]),
+        );
+        // We add a lamp and wire it up, they relabel and remove a lamp.
+        let ours = blueprint(
+            "Lamps",
+            json!([lamp(1, 0.5), lamp(2, 2.5), lamp(3, 4.5)]),
+            json!([[1, 1, 2, 1], [2, 2, 3, 2]]),
+        );
+        let theirs = blueprint("Two lamps", json!([lamp(1, 2.5), lamp(2, 4.5)]), json!([]));
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(
+            merged.text,
+            r#"{
+  "blueprint": {
+    "entities": [
+      {"entity_number":1,"name":"small-lamp","position":{"x":2.5,"y":0.5}},
+      {"entity_number":2,"name":"small-lamp","position":{"x":4.5,"y":0.5}}
+    ],
+    "item": "blueprint",
+    "label": "Two lamps",
+    "version": 562949957353472,
+    "wires": [
+      [1,2,2,2]
+    ]
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let lamp = |number, x: f64, color: Option<&str>| {
+            let mut lamp = json!({"entity_number": number, "name": "small-lamp", "position": {"x": x, "y": 0.5}});
+            if let Some(color) = color {
+                lamp["color"] = json!(color);
+            }
+            lamp
+        };
+        let base = blueprint(
+            "Lamps",
+            json!([lamp(1, 0.5, None), lamp(2, 2.5, None)]),
+            json!([]),
+        );
+        let ours = blueprint(
+            "Our lamps",
+            json!([lamp(1, 0.5, Some("red")), lamp(2, 2.5, None)]),
+            json!([]),
+        );
+        let theirs = blueprint(
+            "Their lamps",
+            json!([lamp(1, 0.5, Some("green")), lamp(2, 2.5, Some("blue"))]),
+            json!([]),
+        );
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 2);
+        assert_eq!(
+            merged.text,
+            r#"{
+  "blueprint": {
+    "entities": [
+<<<<<<< ours
+      {"color":"red","entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5}},
+=======
+      {"color":"green","entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5}},
+>>>>>>> theirs
+      {"color":"blue","entity_number":2,"name":"small-lamp","position":{"x":2.5,"y":0.5}}
+    ],
+    "item": "blueprint",
+<<<<<<< ours
+    "label": "Our lamps",
+=======
+    "label": "Their lamps",
+>>>>>>> theirs
+    "version": 562949957353472
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_merge_rail_crossing() {
+        let rail = |number, direction| {
+            json!({"entity_number": number, "name": "straight-rail", "position": {"x": 1, "y": 1},
+                "direction": direction})
+        };
+        let lamp =
+            json!({"entity_number": 3, "name": "small-lamp", "position": {"x": 4.5, "y": 0.5}});
+        // We add a lamp, they take out one of the crossing rails.
+        let base = blueprint("Crossing", json!([rail(1, 0), rail(2, 4)]), json!([]));
+        let ours = blueprint("Crossing", json!([rail(1, 0), rail(2, 4), lamp]), json!([]));
+        let theirs = blueprint("Crossing", json!([rail(1, 0)]), json!([]));
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(
+            merged.text,
+            r#"{
+  "blueprint": {
+    "entities": [
+      {"entity_number":1,"name":"small-lamp","position":{"x":4.5,"y":0.5}},
+      {"direction":0,"entity_number":2,"name":"straight-rail","position":{"x":1,"y":1}}
+    ],
+    "item": "blueprint",
+    "label": "Crossing",
+    "version": 562949957353472
+  }
+}
+"#
+        );
+
+        // Two entities stacked in exactly the same place, changed differently on each side.
+        let lamp = |number, color: Option<&str>| {
+            let mut lamp = json!({"entity_number": number, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}});
+            if let Some(color) = color {
+                lamp["color"] = json!(color);
+            }
+            lamp
+        };
+        let base = blueprint("Lamps", json!([lamp(1, None), lamp(2, None)]), json!([]));
+        let ours = blueprint(
+            "Lamps",
+            json!([lamp(1, Some("red")), lamp(2, None)]),
+            json!([]),
+        );
+        let theirs = blueprint(
+            "Lamps",
+            json!([lamp(1, None), lamp(2, Some("green"))]),
+            json!([]),
+        );
+        assert_eq!(merge(&base, &ours, &theirs).unwrap().conflicts, 1);
+        let theirs = blueprint("Lamps", json!([lamp(1, None), lamp(2, None)]), json!([]));
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 0);
+        assert!(merged.text.contains(r#""color":"red""#));
+        assert_eq!(merged.text.matches("small-lamp").count(), 2);
+    }
+
+    #[test]
+    fn test_merge_train() {
+        let train = |locomotive: u64, wagon: u64, mut entities: Vec<Value>, station: &str| {
+            entities.extend([
+                json!({"entity_number": locomotive, "name": "locomotive", "position": {"x": 0, "y": 0}, "orientation": 0.25}),
+                json!({"entity_number": wagon, "name": "cargo-wagon", "position": {"x": 7, "y": 0}, "orientation": 0.25}),
+            ]);
+            let mut json = blueprint("Train", json!(entities), json!([]));
+            json["blueprint"]["schedules"] = json!([{
+                "locomotives": [locomotive],
+                "schedule": {"records": [{"station": station}]},
+            }]);
+            json["blueprint"]["stock_connections"] = json!([
+                {"stock": locomotive, "back": wagon},
+                {"stock": wagon, "front": locomotive},
+            ]);
+            json
+        };
+        // We put a lamp before the train, so it's renumbered; they change where it goes.
+        let base = train(1, 2, vec![], "Iron");
+        let lamp =
+            json!({"entity_number": 1, "name": "small-lamp", "position": {"x": 20.5, "y": 0.5}});
+        let ours = train(2, 3, vec![lamp], "Iron");
+        let theirs = train(1, 2, vec![], "Copper");
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 0);
+        let merged = serde_json::from_str::<Value>(&merged.text).unwrap();
+        let names = array(merged["blueprint"].as_object().unwrap(), "entities")
+            .iter()
+            .map(|entity| entity["name"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["locomotive", "cargo-wagon", "small-lamp"]);
+        assert_eq!(
+            merged["blueprint"]["schedules"],
+            json!([{"locomotives": [1], "schedule": {"records": [{"station": "Copper"}]}}])
+        );
+        assert_eq!(
+            merged["blueprint"]["stock_connections"],
+            json!([{"stock": 1, "back": 2}, {"stock": 2, "front": 1}])
+        );
+
+        // They take off the wagon, so nothing's coupled to it any more.
+        let mut theirs = blueprint(
+            "Train",
+            json!([{"entity_number": 1, "name": "locomotive", "position": {"x": 0, "y": 0}, "orientation": 0.25}]),
+            json!([]),
+        );
+        theirs["blueprint"]["schedules"] = base["blueprint"]["schedules"].clone();
+        theirs["blueprint"]["stock_connections"] = json!([{"stock": 1}]);
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts, 0);
+        let merged = serde_json::from_str::<Value>(&merged.text).unwrap();
+        assert_eq!(
+            merged["blueprint"]["schedules"][0]["locomotives"],
+            json!([1])
+        );
+        assert_eq!(
+            merged["blueprint"]["stock_connections"],
+            json!([{"stock": 1}])
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict_reformatted() {
+        // The conflicting blueprints get their entities sorted and renumbered, so they don't
+        // appear in the merged text the way they do in either side.
+        let book = |label: &str, x: f64| {
+            json!({
+                "blueprint_book": {
+                    "item": "blueprint-book",
+                    "label": label,
+                    "blueprints": [{"index": 0, "blueprint": {
+                        "item": "blueprint",
+                        "entities": [
+                            {"entity_number": 1, "name": "small-lamp", "position": {"x": x, "y": 0.5}},
+                            {"entity_number": 2, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5}},
+                        ],
+                        "version": 562949957353472u64,
+                    }}],
+                    "version": 562949957353472u64,
+                }
+            })
+        };
+        let merged = merge(
+            &book("Lamps", 2.5),
+            &book("Lamps", 4.5),
+            &book("Lamps", 6.5),
+        )
+        .unwrap();
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.text,
+            r#"{
+  "blueprint_book": {
+<<<<<<< ours
+    "blueprints": [
+      {
+        "blueprint": {
+          "entities": [
+            {"entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"small-lamp","position":{"x":4.5,"y":0.5}}
+          ],
+          "item": "blueprint",
+          "version": 562949957353472
+        },
+        "index": 0
+      }
+    ],
+=======
+    "blueprints": [
+      {
+        "blueprint": {
+          "entities": [
+            {"entity_number":1,"name":"small-lamp","position":{"x":0.5,"y":0.5}},
+            {"entity_number":2,"name":"small-lamp","position":{"x":6.5,"y":0.5}}
+          ],
+          "item": "blueprint",
+          "version": 562949957353472
+        },
+        "index": 0
+      }
+    ],
+>>>>>>> theirs
+    "item": "blueprint-book",
+    "label": "Lamps",
+    "version": 562949957353472
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict_repeated_value() {
+        // Both sides give the same label and description, so the conflicting description looks
+        // the same as the label apart from its key.
+        let entry = |label: &str, description: &str| {
+            let mut json = blueprint(label, json!([]), json!([]));
+            json["blueprint"]["description"] = json!(description);
+            json
+        };
+        let merged = merge(
+            &entry("Lamps", "Lamps"),
+            &entry("Ours", "Ours"),
+            &entry("Theirs", "Theirs"),
+        )
+        .unwrap();
+        assert_eq!(merged.conflicts, 2);
+        assert_eq!(
+            merged.text,
+            r#"{
+  "blueprint": {
+<<<<<<< ours
+    "description": "Ours",
+=======
+    "description": "Theirs",
+>>>>>>> theirs
+    "item": "blueprint",
+<<<<<<< ours
+    "label": "Ours",
+=======
+    "label": "Theirs",
+>>>>>>> theirs
+    "version": 562949957353472
+  }
+}
+"#
+        );
+    }
+}
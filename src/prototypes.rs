@@ -0,0 +1,456 @@
+//! Prototype data from a `data-raw-dump.json`, as written by `factorio --dump-data`.
+//!
+//! Only the parts needed to work out what builds a blueprint are kept: which item (and how many)
+//! places each entity and tile, the recipes that make each item, which items are mined, how
+//! many of each item fit in a stack, and the order of the qualities.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    blueprint::quality_ladder::{QualityLadder, RawQuality},
+    error::{Error, Result},
+};
+
+/// An item and how many of it are used up placing something.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ItemToPlace {
+    pub item: String,
+    pub count: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn into_first(self) -> Option<T> {
+        match self {
+            OneOrMany::One(one) => Some(one),
+            OneOrMany::Many(many) => many.into_iter().next(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PlaceAsTile {
+    result: String,
+}
+
+/// An ingredient or result of a recipe, or a result of mining.
+#[derive(Deserialize)]
+struct RawProduct {
+    name: String,
+    #[serde(default)]
+    amount: Option<f64>,
+    #[serde(default)]
+    amount_min: Option<f64>,
+    #[serde(default)]
+    amount_max: Option<f64>,
+    #[serde(default)]
+    probability: Option<f64>,
+    #[serde(default)]
+    extra_count_fraction: Option<f64>,
+}
+
+impl RawProduct {
+    /// The average amount produced or consumed.
+    fn expected_amount(&self) -> f64 {
+        let amount = match (self.amount, self.amount_min, self.amount_max) {
+            (Some(amount), _, _) => amount,
+            (None, Some(min), Some(max)) => (min + max) / 2.0,
+            _ => 1.0,
+        };
+        amount * self.probability.unwrap_or(1.0) + self.extra_count_fraction.unwrap_or(0.0)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawRecipe {
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    parameter: bool,
+    #[serde(default)]
+    ingredients: Vec<RawProduct>,
+    #[serde(default)]
+    results: Vec<RawProduct>,
+}
+
+#[derive(Deserialize)]
+struct RawMinable {
+    #[serde(default)]
+    result: Option<String>,
+    #[serde(default)]
+    results: Vec<RawProduct>,
+}
+
+#[derive(Deserialize)]
+struct RawResource {
+    #[serde(default)]
+    minable: Option<RawMinable>,
+}
+
+#[derive(Deserialize)]
+struct RawDump {
+    #[serde(default)]
+    recipe: HashMap<String, RawRecipe>,
+    #[serde(default)]
+    resource: HashMap<String, RawResource>,
+    #[serde(default)]
+    quality: HashMap<String, RawQuality>,
+    #[serde(flatten)]
+    other: HashMap<String, HashMap<String, RawPrototype>>,
+}
+
+/// The fields of any other prototype that we look at; everything else in the dump is skipped.
+#[derive(Deserialize)]
+struct RawPrototype {
+    #[serde(default)]
+    placeable_by: Option<OneOrMany<ItemToPlace>>,
+    #[serde(default)]
+    place_result: Option<String>,
+    #[serde(default)]
+    place_as_tile: Option<PlaceAsTile>,
+    #[serde(default)]
+    stack_size: Option<u64>,
+}
+
+/// One ingredient of a [`Recipe`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ingredient {
+    /// An item or fluid.
+    pub name: String,
+    pub amount: f64,
+}
+
+/// A recipe, as used to make one particular product.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe {
+    pub name: String,
+    pub ingredients: Vec<Ingredient>,
+    /// How many of the product one craft makes, on average.
+    pub amount: f64,
+}
+
+/// What places which entities and tiles, and how items are made, according to a prototype dump.
+#[derive(Clone, Debug, Default)]
+pub struct Prototypes {
+    entities: HashMap<String, ItemToPlace>,
+    tiles: HashMap<String, ItemToPlace>,
+    recipes: HashMap<String, Recipe>,
+    resources: HashSet<String>,
+    stack_sizes: HashMap<String, u64>,
+    qualities: Option<QualityLadder>,
+}
+
+impl Prototypes {
+    /// Reads a `data-raw-dump.json`.
+    pub fn load(path: &Path) -> Result<Prototypes> {
+        let dump = fs::read_to_string(path).map_err(Error::io(path))?;
+        Prototypes::from_dump(&dump).map_err(|e| match e {
+            Error::InvalidJson { path: None, source } => Error::InvalidJson {
+                path: Some(path.to_owned()),
+                source,
+            },
+            e => e,
+        })
+    }
+
+    /// Parses the contents of a `data-raw-dump.json`.
+    pub fn from_dump(dump: &str) -> Result<Prototypes> {
+        let dump = serde_json::from_str::<RawDump>(dump)?;
+        let mut prototypes = Prototypes::default();
+        let mut placed_by_items = Vec::new();
+        for (kind, by_name) in dump.other {
+            let placeable_by = if kind == "tile" {
+                &mut prototypes.tiles
+            } else {
+                &mut prototypes.entities
+            };
+            for (name, prototype) in by_name {
+                if let Some(stack_size) = prototype.stack_size {
+                    prototypes.stack_sizes.insert(name.clone(), stack_size);
+                }
+                if let Some(item) = prototype.placeable_by.and_then(OneOrMany::into_first) {
+                    placeable_by.insert(name.clone(), item);
+                }
+                if let Some(entity) = prototype.place_result.filter(|entity| !entity.is_empty()) {
+                    placed_by_items.push((false, entity, name.clone()));
+                }
+                if let Some(tile) = prototype.place_as_tile {
+                    placed_by_items.push((true, tile.result, name));
+                }
+            }
+        }
+        // An explicit `placeable_by` wins over an item's `place_result`. Sorting makes the choice
+        // stable when several items place the same thing.
+        placed_by_items.sort();
+        for (is_tile, placed, item) in placed_by_items {
+            let placeable_by = if is_tile {
+                &mut prototypes.tiles
+            } else {
+                &mut prototypes.entities
+            };
+            placeable_by
+                .entry(placed)
+                .or_insert(ItemToPlace { item, count: 1 });
+        }
+
+        for minable in dump.resource.into_values().filter_map(|r| r.minable) {
+            prototypes.resources.extend(minable.result);
+            prototypes
+                .resources
+                .extend(minable.results.into_iter().map(|result| result.name));
+        }
+        prototypes.recipes = choose_recipes(dump.recipe);
+        if !dump.quality.is_empty() {
+            prototypes.qualities = Some(QualityLadder::from_prototypes(&dump.quality)?);
+        }
+        Ok(prototypes)
+    }
+
+    /// The item that places the entity `name`, if the dump knows about it.
+    pub fn entity_item(&self, name: &str) -> Option<&ItemToPlace> {
+        self.entities.get(name)
+    }
+
+    /// The item that places the tile `name`, if the dump knows about it.
+    pub fn tile_item(&self, name: &str) -> Option<&ItemToPlace> {
+        self.tiles.get(name)
+    }
+
+    /// How many of the item `name` fit in one inventory slot.
+    pub fn stack_size(&self, name: &str) -> Option<u64> {
+        self.stack_sizes.get(name).copied()
+    }
+
+    /// The qualities in the dump, worst first, if it has any.
+    pub fn quality_ladder(&self) -> Option<&QualityLadder> {
+        self.qualities.as_ref()
+    }
+
+    /// Whether the item or fluid `name` is mined from a resource, like `iron-ore` or `crude-oil`.
+    pub fn is_resource(&self, name: &str) -> bool {
+        self.resources.contains(name)
+    }
+
+    /// The recipe that makes the item or fluid `name`, if there is an obvious one.
+    ///
+    /// That's the recipe with the same name as the item, or else the only recipe making it.
+    /// Recycling recipes and recipe parameters are never used. Resources have no recipe.
+    pub fn recipe_for(&self, name: &str) -> Option<&Recipe> {
+        if self.is_resource(name) {
+            return None;
+        }
+        self.recipes.get(name)
+    }
+}
+
+/// Picks the recipe to make each product with, out of all the recipes producing it.
+fn choose_recipes(recipes: HashMap<String, RawRecipe>) -> HashMap<String, Recipe> {
+    let mut candidates = HashMap::<String, Vec<Recipe>>::new();
+    for (name, recipe) in recipes {
+        let recycling = recipe
+            .category
+            .as_deref()
+            .is_some_and(|category| category.contains("recycling"));
+        if recycling || recipe.parameter {
+            continue;
+        }
+        let ingredients = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| Ingredient {
+                name: ingredient.name.clone(),
+                amount: ingredient.expected_amount(),
+            })
+            .collect::<Vec<_>>();
+        for result in &recipe.results {
+            // Catalysts like the ore in kovarex enrichment are both ingredient and result.
+            let consumed = ingredients
+                .iter()
+                .filter(|ingredient| ingredient.name == result.name)
+                .map(|ingredient| ingredient.amount)
+                .sum::<f64>();
+            let amount = result.expected_amount() - consumed;
+            if amount <= 0.0 {
+                continue;
+            }
+            candidates
+                .entry(result.name.clone())
+                .or_default()
+                .push(Recipe {
+                    name: name.clone(),
+                    ingredients: ingredients
+                        .iter()
+                        .filter(|ingredient| ingredient.name != result.name)
+                        .cloned()
+                        .collect(),
+                    amount,
+                });
+        }
+    }
+    candidates
+        .into_iter()
+        .filter_map(|(product, mut recipes)| {
+            let recipe = match recipes.iter().position(|recipe| recipe.name == product) {
+                Some(same_name) => recipes.swap_remove(same_name),
+                None if recipes.len() == 1 => recipes.pop()?,
+                None => return None,
+            };
+            Some((product, recipe))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_dump() {
+        let dump = r#"{
+            "item": {
+                "rail": {"name": "rail", "place_result": "straight-rail", "stack_size": 100},
+                "stone-brick": {"name": "stone-brick", "place_as_tile": {"result": "stone-path", "condition_size": 1}},
+                "inserter": {"name": "inserter", "place_result": "inserter"}
+            },
+            "rail-planner": {
+                "rail-ramp": {"name": "rail-ramp", "place_result": ""}
+            },
+            "straight-rail": {
+                "straight-rail": {"name": "straight-rail", "placeable_by": {"item": "rail", "count": 1}}
+            },
+            "curved-rail-a": {
+                "curved-rail-a": {"name": "curved-rail-a", "placeable_by": {"item": "rail", "count": 3}}
+            },
+            "rail-ramp": {
+                "rail-ramp": {"name": "rail-ramp", "placeable_by": [{"item": "rail-ramp", "count": 1}]}
+            },
+            "tile": {
+                "stone-path": {"name": "stone-path"}
+            }
+        }"#;
+        let prototypes = Prototypes::from_dump(dump).unwrap();
+        let item = |item: &str, count| {
+            Some(ItemToPlace {
+                item: item.to_owned(),
+                count,
+            })
+        };
+        assert_eq!(
+            prototypes.entity_item("curved-rail-a").cloned(),
+            item("rail", 3)
+        );
+        assert_eq!(
+            prototypes.entity_item("straight-rail").cloned(),
+            item("rail", 1)
+        );
+        assert_eq!(
+            prototypes.entity_item("rail-ramp").cloned(),
+            item("rail-ramp", 1)
+        );
+        assert_eq!(
+            prototypes.entity_item("inserter").cloned(),
+            item("inserter", 1)
+        );
+        assert_eq!(
+            prototypes.tile_item("stone-path").cloned(),
+            item("stone-brick", 1)
+        );
+        assert_eq!(prototypes.entity_item("stone-path"), None);
+        assert_eq!(prototypes.entity_item("modded-thing"), None);
+        assert_eq!(prototypes.stack_size("rail"), Some(100));
+        assert_eq!(prototypes.stack_size("inserter"), None);
+        assert_eq!(prototypes.quality_ladder(), None);
+    }
+
+    #[test]
+    fn test_recipes() {
+        let dump = r#"{
+            "recipe": {
+                "copper-cable": {
+                    "ingredients": [{"type": "item", "name": "copper-plate", "amount": 1}],
+                    "results": [{"type": "item", "name": "copper-cable", "amount": 2}]
+                },
+                "copper-plate": {
+                    "category": "smelting",
+                    "ingredients": [{"type": "item", "name": "copper-ore", "amount": 1}],
+                    "results": [{"type": "item", "name": "copper-plate", "amount": 1}]
+                },
+                "copper-cable-recycling": {
+                    "category": "recycling",
+                    "ingredients": [{"type": "item", "name": "copper-cable", "amount": 1}],
+                    "results": [{"type": "item", "name": "copper-plate", "amount": 1, "probability": 0.125}]
+                },
+                "kovarex-enrichment-process": {
+                    "category": "centrifuging",
+                    "ingredients": [
+                        {"type": "item", "name": "uranium-235", "amount": 40},
+                        {"type": "item", "name": "uranium-238", "amount": 5}
+                    ],
+                    "results": [
+                        {"type": "item", "name": "uranium-235", "amount": 41},
+                        {"type": "item", "name": "uranium-238", "amount": 2}
+                    ]
+                },
+                "basic-oil-processing": {
+                    "ingredients": [{"type": "fluid", "name": "crude-oil", "amount": 100}],
+                    "results": [{"type": "fluid", "name": "petroleum-gas", "amount": 45}]
+                },
+                "advanced-oil-processing": {
+                    "ingredients": [{"type": "fluid", "name": "crude-oil", "amount": 100}],
+                    "results": [{"type": "fluid", "name": "petroleum-gas", "amount": 55}]
+                },
+                "iron-bacteria-cultivation": {
+                    "ingredients": [{"type": "item", "name": "iron-bacteria", "amount": 1}],
+                    "results": [{"type": "item", "name": "iron-ore", "amount": 4}]
+                }
+            },
+            "resource": {
+                "copper-ore": {"minable": {"result": "copper-ore"}},
+                "iron-ore": {"minable": {"result": "iron-ore"}},
+                "crude-oil": {"minable": {"results": [{"type": "fluid", "name": "crude-oil", "amount_min": 10, "amount_max": 10}]}}
+            }
+        }"#;
+        let prototypes = Prototypes::from_dump(dump).unwrap();
+        let ingredient = |name: &str, amount| Ingredient {
+            name: name.to_owned(),
+            amount,
+        };
+        assert_eq!(
+            prototypes.recipe_for("copper-cable"),
+            Some(&Recipe {
+                name: "copper-cable".to_owned(),
+                ingredients: vec![ingredient("copper-plate", 1.0)],
+                amount: 2.0,
+            })
+        );
+        assert_eq!(
+            prototypes.recipe_for("copper-plate").map(|r| &r.name[..]),
+            Some("copper-plate")
+        );
+        assert_eq!(
+            prototypes.recipe_for("uranium-235"),
+            Some(&Recipe {
+                name: "kovarex-enrichment-process".to_owned(),
+                ingredients: vec![ingredient("uranium-238", 5.0)],
+                amount: 1.0,
+            })
+        );
+        assert_eq!(prototypes.recipe_for("uranium-238"), None);
+        assert_eq!(prototypes.recipe_for("petroleum-gas"), None);
+        assert_eq!(prototypes.recipe_for("iron-ore"), None);
+        assert!(prototypes.is_resource("crude-oil"));
+        assert!(!prototypes.is_resource("copper-cable"));
+    }
+}
@@ -1,11 +1,58 @@
+//! Saving blueprints as json files, and blueprint books as directories of them.
+
+#[cfg(feature = "git")]
+mod git_index;
+
 use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fmt::{self, Display},
     fs::File,
     io::{BufWriter, Write},
     mem,
     path::{Path, PathBuf},
 };
 
-use crate::blueprint::BlueprintType;
+use crate::{
+    blueprint::BlueprintType,
+    canonical,
+    error::{Error, Result},
+};
+
+/// How saved json files are laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// [Canonical](crate::canonical) json: sorted, renumbered, one line per entity. Small changes
+    /// in game make small diffs.
+    #[default]
+    Canonical,
+    /// The json as the game exported it, pretty-printed.
+    Pretty,
+}
+
+/// How saved files and directories are named.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Naming {
+    /// The book index followed by the label (or icons), e.g. `3 Smelting.json`.
+    #[default]
+    Indexed,
+    /// A slug of the label (or icons), e.g. `smelting.json`. Indices are only kept in the json, so
+    /// reordering a book doesn't rename files.
+    Slug,
+    /// A hash of the contents, e.g. `8c3a1f0e9b2d4c67.json`. Can't be used with
+    /// [`SaveOptions::sync`], since an edited blueprint no longer has the name it was saved under.
+    Hash,
+}
+
+/// Options for [`save`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct SaveOptions {
+    pub format: Format,
+    pub naming: Naming,
+    /// Update an existing file or directory in place, instead of failing if it exists.
+    pub sync: bool,
+}
 
 fn format_tag(
     typ: &str,
@@ -28,7 +75,7 @@ fn format_entity_tag(entity: &serde_json::Value) -> Option<String> {
     format_tag("entity", entity.get("name"), entity.get("quality"))
 }
 
-fn format_icon_tag(icon: &serde_json::Value) -> Option<String> {
+pub(crate) fn format_icon_tag(icon: &serde_json::Value) -> Option<String> {
     format_tag("icon", icon.get("name"), icon.get("quality"))
 }
 
@@ -139,60 +186,409 @@ fn compute_name(bp: BlueprintType<&serde_json::Value>) -> String {
     name
 }
 
-pub fn save(mut json: serde_json::Value, dir: Option<&Path>) {
-    let index = json
-        .get("index")
-        .and_then(|index| index.as_number().cloned());
-    let mut bp = BlueprintType::<&mut serde_json::Value>::new(&mut json);
-    let mut name = compute_name(bp.as_ref());
+/// Something `save` did to a file or directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Created(PathBuf),
+    /// Rewritten because its contents changed.
+    Updated(PathBuf),
+    /// Already up to date; only reported when syncing.
+    Unchanged(PathBuf),
+    /// Renamed because the blueprint's label or index changed.
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Removed because the blueprint was removed from the book.
+    Removed(PathBuf),
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Created(path) => write!(f, "{path:?} saved."),
+            Action::Updated(path) => write!(f, "{path:?} updated."),
+            Action::Unchanged(path) => write!(f, "{path:?} unchanged."),
+            Action::Renamed { from, to } => write!(f, "{from:?} renamed to {to:?}."),
+            Action::Removed(path) => write!(f, "{path:?} removed."),
+        }
+    }
+}
+
+/// What `save` writes for one blueprint: a json file, or for a book, a directory.
+struct Planned {
+    /// File name, or directory name for books.
+    name: String,
+    index: Option<u64>,
+    label: Option<String>,
+    /// The json file, or `book.json` for books.
+    contents: String,
+    /// The entries of a book.
+    entries: Option<Vec<Planned>>,
+}
+
+/// A lowercase, dash-separated version of `name`, e.g. `[icon=rail] Rails` becomes `icon-rail-rails`.
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    if slug.is_empty() {
+        slug.push_str("untitled");
+    }
+    slug
+}
 
-    if let Some(index) = index {
-        name = format!("{index} {name}");
+/// 64-bit FNV-1a; simple, and stable across versions and platforms, unlike `std`'s hashers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
     }
+    hash
+}
+
+fn file_name(json: &serde_json::Value, naming: Naming) -> Result<String> {
+    let bp = BlueprintType::<&serde_json::Value>::new(json)?;
+    let name = match naming {
+        Naming::Indexed => {
+            let name = compute_name(bp);
+            match json.get("index").filter(|index| index.is_number()) {
+                Some(index) => format!("{index} {name}"),
+                None => name,
+            }
+        }
+        Naming::Slug => slug(&compute_name(bp)),
+        Naming::Hash => {
+            let mut unindexed = json.clone();
+            if let Some(entry) = unindexed.as_object_mut() {
+                entry.remove("index");
+            }
+            let canonical = canonical::to_canonical_string(unindexed)?;
+            format!("{:016x}", fnv1a(canonical.as_bytes()))
+        }
+    };
 
     // replace problematic characters like "/"
-    name = sanitize_filename::sanitize_with_options(
+    Ok(sanitize_filename::sanitize_with_options(
         name,
         sanitize_filename::Options {
             windows: true,
             truncate: true,
             replacement: "_",
         },
-    );
+    ))
+}
+
+fn plan(mut json: serde_json::Value, options: &SaveOptions) -> Result<Planned> {
+    let index = json.get("index").and_then(|index| index.as_u64());
+    let mut name = file_name(&json, options.naming)?;
+    let mut bp = BlueprintType::<&mut serde_json::Value>::new(&mut json)?;
+    let label = bp.as_ref().label().map(str::to_owned);
 
-    let file_path: PathBuf = if let BlueprintType::BlueprintBook(blueprint_book) = &mut bp
+    let entries = if let BlueprintType::BlueprintBook(blueprint_book) = &mut bp
         && let Some(blueprints) = blueprint_book.get_mut("blueprints")
         && let Some(blueprints) = blueprints.as_array_mut()
     {
-        let blueprints = mem::take(blueprints);
-        let path = if let Some(dir) = dir {
-            dir.join(&name)
+        let mut entries = mem::take(blueprints)
+            .into_iter()
+            .map(|blueprint| plan(blueprint, options))
+            .collect::<Result<Vec<_>>>()?;
+        // Without an index in the name, identical names are possible; number the later ones.
+        // `book.json` is taken by the book itself.
+        let mut seen = HashSet::from(["book.json".to_owned()]);
+        for entry in &mut entries {
+            let (stem, extension) = match entry.name.strip_suffix(".json") {
+                Some(stem) => (stem.to_owned(), ".json"),
+                None => (entry.name.clone(), ""),
+            };
+            let mut n = 1;
+            while !seen.insert(entry.name.clone()) {
+                n += 1;
+                entry.name = format!("{stem}-{n}{extension}");
+            }
+        }
+        Some(entries)
+    } else {
+        name.push_str(".json");
+        None
+    };
+
+    let contents = match options.format {
+        Format::Canonical => canonical::to_canonical_string(json)?,
+        Format::Pretty => serde_json::to_string_pretty(&json)?,
+    };
+    Ok(Planned {
+        name,
+        index,
+        label,
+        contents,
+        entries,
+    })
+}
+
+/// Saves a blueprint as a json file named after it, in `dir` or the current directory.
+///
+/// Blueprint books are saved as a directory, containing a `book.json` with everything except the
+/// blueprints, and one file (or directory) per blueprint in the book.
+///
+/// Normally nothing is overwritten. With [`SaveOptions::sync`], an existing file or directory is
+/// updated in place instead: changed files are rewritten, files for blueprints removed from the
+/// book are removed, and files are renamed when a blueprint's label or index changed. Renames
+/// and removals are also made in the git index, if `dir` is in a git repository. Files that
+/// aren't saved blueprints are left alone.
+///
+/// Returns what was done to each file.
+pub fn save(
+    json: serde_json::Value,
+    dir: Option<&Path>,
+    options: &SaveOptions,
+) -> Result<Vec<Action>> {
+    if options.sync && options.naming == Naming::Hash {
+        return Err(Error::InvalidOptions(
+            "can't sync hash-named files: an edited blueprint would be saved next to the old one"
+                .to_owned(),
+        ));
+    }
+    let planned = plan(json, options)?;
+    let dir = dir.unwrap_or(Path::new(""));
+    let mut actions = vec![];
+    if !options.sync {
+        create(&planned, dir, &mut actions)?;
+        return Ok(actions);
+    }
+    let mut syncer = Syncer {
+        actions,
+        #[cfg(feature = "git")]
+        git: git_index::GitIndex::open(if dir.as_os_str().is_empty() {
+            Path::new(".")
         } else {
-            name.clone().into()
-        };
-        std::fs::create_dir(&path)
-            .unwrap_or_else(|e| panic!("error creating book directory {path:?}: {e}"));
-        for blueprint in blueprints {
-            save(blueprint, Some(&path));
+            dir
+        })?,
+    };
+    let path = dir.join(&planned.name);
+    match &planned.entries {
+        Some(entries) if path.is_dir() => syncer.sync_book(&path, &planned.contents, entries)?,
+        None if path.is_file() => syncer.write_if_changed(&path, &planned.contents)?,
+        _ => create(&planned, dir, &mut syncer.actions)?,
+    }
+    #[cfg(feature = "git")]
+    if let Some(git) = syncer.git {
+        git.write()?;
+    }
+    Ok(syncer.actions)
+}
+
+fn create_file(path: &Path, contents: &str) -> Result<()> {
+    let out_file = File::create_new(path).map_err(Error::io(path))?;
+    let mut writer = BufWriter::new(out_file);
+    writer
+        .write_all(contents.as_bytes())
+        .map_err(Error::io(path))?;
+    writer.flush().map_err(Error::io(path))
+}
+
+/// Writes `planned` into `dir`, failing if anything already exists.
+fn create(planned: &Planned, dir: &Path, actions: &mut Vec<Action>) -> Result<()> {
+    let path = dir.join(&planned.name);
+    let file_path = if let Some(entries) = &planned.entries {
+        std::fs::create_dir(&path).map_err(Error::io(&path))?;
+        for entry in entries {
+            create(entry, &path, actions)?;
         }
         path.join("book.json")
     } else {
-        let file = format!("{name}.json");
-        if let Some(dir) = dir {
-            dir.join(&file)
+        path
+    };
+    create_file(&file_path, &planned.contents)?;
+    actions.push(Action::Created(file_path));
+    Ok(())
+}
+
+/// A blueprint file (or book directory) found in a directory being synced.
+struct Existing {
+    name: OsString,
+    is_book: bool,
+    index: Option<u64>,
+    label: Option<String>,
+}
+
+impl Existing {
+    /// Reads `path`, returning `None` if it isn't a saved blueprint.
+    fn read(path: &Path) -> Option<Existing> {
+        let is_book = path.is_dir();
+        let json_path = if is_book {
+            path.join("book.json")
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            path.to_owned()
         } else {
-            file.into()
+            return None;
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(json_path).ok()?).ok()?;
+        let bp = BlueprintType::<&serde_json::Value>::new(&json).ok()?;
+        if is_book != matches!(bp, BlueprintType::BlueprintBook(_)) {
+            return None;
         }
-    };
+        Some(Existing {
+            name: path.file_name()?.to_owned(),
+            is_book,
+            index: json.get("index").and_then(|index| index.as_u64()),
+            label: bp.label().map(str::to_owned),
+        })
+    }
+}
 
-    {
-        let out_file = File::create_new(&file_path)
-            .unwrap_or_else(|e| panic!("error creating file {file_path:?}: {e:?}"));
-        let mut writer = BufWriter::new(out_file);
-        serde_json::to_writer_pretty(&mut writer, &json).expect("error writing {file_path}");
-        writer.flush().expect("error writing {file_path}");
+struct Syncer {
+    actions: Vec<Action>,
+    #[cfg(feature = "git")]
+    git: Option<git_index::GitIndex>,
+}
+
+impl Syncer {
+    fn write_if_changed(&mut self, path: &Path, contents: &str) -> Result<()> {
+        let existing = std::fs::read_to_string(path).map_err(Error::io(path))?;
+        if existing == contents {
+            self.actions.push(Action::Unchanged(path.to_owned()));
+        } else {
+            std::fs::write(path, contents).map_err(Error::io(path))?;
+            self.actions.push(Action::Updated(path.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        std::fs::rename(from, to).map_err(Error::io(from))?;
+        #[cfg(feature = "git")]
+        if let Some(git) = &mut self.git {
+            git.rename(from, to)?;
+        }
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<()> {
+        std::fs::remove_file(path).map_err(Error::io(path))?;
+        #[cfg(feature = "git")]
+        if let Some(git) = &mut self.git {
+            git.remove(path)?;
+        }
+        self.actions.push(Action::Removed(path.to_owned()));
+        Ok(())
+    }
+
+    /// Removes a saved book directory, keeping it if it contains anything else.
+    fn remove_book(&mut self, path: &Path) -> Result<()> {
+        self.sync_book_entries(path, &[])?;
+        self.remove_file(&path.join("book.json"))?;
+        if std::fs::remove_dir(path).is_ok() {
+            self.actions.push(Action::Removed(path.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Updates a saved book directory to contain `entries`.
+    fn sync_book(&mut self, path: &Path, book_json: &str, entries: &[Planned]) -> Result<()> {
+        self.sync_book_entries(path, entries)?;
+        let book_json_path = path.join("book.json");
+        if book_json_path.exists() {
+            self.write_if_changed(&book_json_path, book_json)
+        } else {
+            create_file(&book_json_path, book_json)?;
+            self.actions.push(Action::Created(book_json_path));
+            Ok(())
+        }
+    }
+
+    fn sync_book_entries(&mut self, path: &Path, entries: &[Planned]) -> Result<()> {
+        let mut existing = vec![];
+        for file in path.read_dir().map_err(Error::io(path))? {
+            let file = file.map_err(Error::io(path))?;
+            if file.file_name() != "book.json"
+                && let Some(found) = Existing::read(&file.path())
+            {
+                existing.push(found);
+            }
+        }
+
+        // Match up entries with existing files: by name, then by label, then by index. Label comes
+        // before index so that reordering a book renames each file after its own blueprint, rather
+        // than rewriting it with whichever blueprint took its place.
+        let mut matched: Vec<Option<usize>> = vec![None; entries.len()];
+        let mut used = vec![false; existing.len()];
+        for pass in 0..3 {
+            for (entry_i, entry) in entries.iter().enumerate() {
+                if matched[entry_i].is_some() {
+                    continue;
+                }
+                let found = existing.iter().enumerate().position(|(i, existing)| {
+                    !used[i]
+                        && existing.is_book == entry.entries.is_some()
+                        && match pass {
+                            0 => existing.name == entry.name.as_str(),
+                            1 => existing.label.is_some() && existing.label == entry.label,
+                            _ => existing.index.is_some() && existing.index == entry.index,
+                        }
+                });
+                if let Some(i) = found {
+                    used[i] = true;
+                    matched[entry_i] = Some(i);
+                }
+            }
+        }
+
+        for (existing, _) in existing.iter().zip(&used).filter(|(_, used)| !**used) {
+            let existing_path = path.join(&existing.name);
+            if existing.is_book {
+                self.remove_book(&existing_path)?;
+            } else {
+                self.remove_file(&existing_path)?;
+            }
+        }
+
+        // Rename in two steps, in case entries swapped names.
+        let renames = entries
+            .iter()
+            .zip(&matched)
+            .filter_map(|(entry, matched)| {
+                let from = &existing[(*matched)?].name;
+                (from != entry.name.as_str()).then(|| (path.join(from), path.join(&entry.name)))
+            })
+            .collect::<Vec<_>>();
+        for (from, _) in &renames {
+            let mut temporary = from.clone().into_os_string();
+            temporary.push(".renaming");
+            self.rename(from, Path::new(&temporary))?;
+        }
+        for (from, to) in renames {
+            let mut temporary = from.clone().into_os_string();
+            temporary.push(".renaming");
+            self.rename(Path::new(&temporary), &to)?;
+            self.actions.push(Action::Renamed { from, to });
+        }
+
+        for (entry, matched) in entries.iter().zip(&matched) {
+            let entry_path = path.join(&entry.name);
+            match (&entry.entries, matched) {
+                (_, None) => create(entry, path, &mut self.actions)?,
+                (Some(entries), Some(_)) => {
+                    self.sync_book(&entry_path, &entry.contents, entries)?
+                }
+                (None, Some(_)) => self.write_if_changed(&entry_path, &entry.contents)?,
+            }
+        }
+        Ok(())
     }
-    println!("{file_path:?} saved.");
 }
 
 #[cfg(test)]
@@ -202,31 +598,55 @@ mod tests {
     use crate::test_util::read_dir_unwrap;
 
     use super::*;
+
+    fn canonicalized(json: &serde_json::Value) -> serde_json::Value {
+        let mut json = json.clone();
+        canonical::canonicalize(&mut json).unwrap();
+        json
+    }
+
     #[test]
     fn test_save_blueprint() {
         let bp = "0eNqtlN1ugzAMhd/F12Eaf+uK1CeZJhSCu0WChDmhWlXl3Wd+1G6l6wXbFcE6Pv4OSThB1fTYkTYeihNoZY2D4uUETr8Z2Qw1I1uEAhw2qLylSNm20kbyEoIAbWr8hCIOrwLQeO01Tgbjy7E0fVshsUDcMxLQWce91gwT2S+NHwUcoYiS+Pkh5zm1Ju4aBZkAxvRkm7LCd3nQbMBdtkOSk2KewbbTomwle3rqcQYuF/E0WRNZQu756GXD6FzETisIIYhFnGR1nM2KOMr2ZkgzPpfsynYsXtCT5MJN+nQ1fbKCfiaKPEnj9kgXyNLZnhSWzrNWfUv0O3q2Gj39A/peN/4H+Fxg+dX3HjzbTtKIU8Dudop8dYr4fzZgvhfnOGTb87ma7sn1Ft05dOGirtH5IQUPXrbI+iCNwjpSmlSv/c27xv8R7bHlyuXPJOCA5MYw+VOyzbbbfJPmabZJQvgCXQumXg==";
         let dir = tempfile::tempdir().unwrap();
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
-        save(json.clone(), Some(dir.path()));
+        save(json.clone(), Some(dir.path()), &SaveOptions::default()).unwrap();
         let files = read_dir_unwrap(dir.path());
         assert_eq!(files, &["[icon=selector-combinator].json"]);
         let written_json =
             std::fs::read_to_string(dir.path().join("[icon=selector-combinator].json")).unwrap();
         let written_json = serde_json::Value::from_str(&written_json).unwrap();
-        assert_eq!(written_json, json);
+        assert_eq!(written_json, canonicalized(&json));
 
-        let loaded_json = crate::load::load(&dir.path().join("[icon=selector-combinator].json"));
-        assert_eq!(loaded_json, json)
+        let loaded_json =
+            crate::load::load(&dir.path().join("[icon=selector-combinator].json")).unwrap();
+        assert_eq!(loaded_json, canonicalized(&json))
+    }
+
+    #[test]
+    fn test_save_pretty() {
+        let bp = "0eNqtlN1ugzAMhd/F12Eaf+uK1CeZJhSCu0WChDmhWlXl3Wd+1G6l6wXbFcE6Pv4OSThB1fTYkTYeihNoZY2D4uUETr8Z2Qw1I1uEAhw2qLylSNm20kbyEoIAbWr8hCIOrwLQeO01Tgbjy7E0fVshsUDcMxLQWce91gwT2S+NHwUcoYiS+Pkh5zm1Ju4aBZkAxvRkm7LCd3nQbMBdtkOSk2KewbbTomwle3rqcQYuF/E0WRNZQu756GXD6FzETisIIYhFnGR1nM2KOMr2ZkgzPpfsynYsXtCT5MJN+nQ1fbKCfiaKPEnj9kgXyNLZnhSWzrNWfUv0O3q2Gj39A/peN/4H+Fxg+dX3HjzbTtKIU8Dudop8dYr4fzZgvhfnOGTb87ma7sn1Ft05dOGirtH5IQUPXrbI+iCNwjpSmlSv/c27xv8R7bHlyuXPJOCA5MYw+VOyzbbbfJPmabZJQvgCXQumXg==";
+        let dir = tempfile::tempdir().unwrap();
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let json = serde_json::Value::from_str(&json).expect("should contain valid json");
+        let options = SaveOptions {
+            format: Format::Pretty,
+            ..Default::default()
+        };
+        save(json.clone(), Some(dir.path()), &options).unwrap();
+        let written_json =
+            std::fs::read_to_string(dir.path().join("[icon=selector-combinator].json")).unwrap();
+        assert_eq!(written_json, serde_json::to_string_pretty(&json).unwrap());
     }
 
     #[test]
     fn test_save_book() {
         let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
         let dir = tempfile::tempdir().unwrap();
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
-        save(json.clone(), Some(dir.path()));
+        save(json.clone(), Some(dir.path()), &SaveOptions::default()).unwrap();
         let files = read_dir_unwrap(dir.path());
         assert_eq!(files, &["Untitled"]);
         let subfiles = read_dir_unwrap(&dir.path().join("Untitled"));
@@ -234,17 +654,333 @@ mod tests {
         let subsubfiles = read_dir_unwrap(&dir.path().join("Untitled/1 Nested Book"));
         assert_eq!(subsubfiles, ["6 [icon=bulk-inserter].json", "book.json"]);
 
-        let loaded_json = crate::load::load(&dir.path().join("Untitled"));
-        assert_eq!(loaded_json, json)
+        let loaded_json = crate::load::load(&dir.path().join("Untitled")).unwrap();
+        assert_eq!(loaded_json, canonicalized(&json))
+    }
+
+    #[test]
+    fn test_save_sync() {
+        let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
+        let dir = tempfile::tempdir().unwrap();
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let mut json = serde_json::Value::from_str(&json).expect("should contain valid json");
+        save(json.clone(), Some(dir.path()), &SaveOptions::default()).unwrap();
+        let book_dir = dir.path().join("Untitled");
+        std::fs::write(book_dir.join("notes.txt"), "not a blueprint").unwrap();
+
+        // Relabel the first blueprint, add a copy of it, and remove the nested book.
+        let blueprints = json["blueprint_book"]["blueprints"].as_array_mut().unwrap();
+        blueprints[0]["blueprint"]["label"] = "Renamed".into();
+        let mut copy = blueprints[0].clone();
+        copy["index"] = 2.into();
+        copy["blueprint"]["label"] = "Copy".into();
+        blueprints[1] = copy;
+
+        let options = SaveOptions {
+            sync: true,
+            ..Default::default()
+        };
+        let actions = save(json.clone(), Some(dir.path()), &options).unwrap();
+        let nested = book_dir.join("1 Nested Book");
+        assert_eq!(
+            actions,
+            [
+                Action::Removed(nested.join("6 [icon=bulk-inserter].json")),
+                Action::Removed(nested.join("book.json")),
+                Action::Removed(nested),
+                Action::Renamed {
+                    from: book_dir.join("0 BP Name 1.json"),
+                    to: book_dir.join("0 Renamed.json"),
+                },
+                Action::Updated(book_dir.join("0 Renamed.json")),
+                Action::Created(book_dir.join("2 Copy.json")),
+                Action::Unchanged(book_dir.join("book.json")),
+            ]
+        );
+        let files = read_dir_unwrap(&book_dir);
+        assert_eq!(
+            files,
+            ["0 Renamed.json", "2 Copy.json", "book.json", "notes.txt"]
+        );
+
+        // Syncing again changes nothing.
+        let actions = save(json.clone(), Some(dir.path()), &options).unwrap();
+        assert!(
+            actions
+                .iter()
+                .all(|action| matches!(action, Action::Unchanged(_))),
+            "{actions:?}"
+        );
+
+        let loaded_json = crate::load::load(&book_dir).unwrap();
+        assert_eq!(loaded_json, canonicalized(&json));
+    }
+
+    #[test]
+    fn test_save_sync_reordered() {
+        let book = |entries: &[(u64, &str)]| {
+            let blueprints = entries
+                .iter()
+                .map(|(index, label)| {
+                    serde_json::json!({"index": index, "blueprint": {"item": "blueprint",
+                        "label": label, "version": 562949957353472u64}})
+                })
+                .collect::<Vec<_>>();
+            serde_json::json!({"blueprint_book": {"item": "blueprint-book", "label": "Book",
+                "blueprints": blueprints, "version": 562949957353472u64}})
+        };
+        let base = book(&[(0, "A"), (1, "B"), (2, "C")]);
+        for (naming, entries, renamed) in [
+            (
+                Naming::Indexed,
+                &[(0, "B"), (1, "A"), (2, "C")][..],
+                &[("1 B.json", "0 B.json"), ("0 A.json", "1 A.json")][..],
+            ),
+            (
+                Naming::Indexed,
+                &[(0, "C"), (1, "A"), (2, "B")],
+                &[
+                    ("2 C.json", "0 C.json"),
+                    ("0 A.json", "1 A.json"),
+                    ("1 B.json", "2 B.json"),
+                ],
+            ),
+            (
+                Naming::Indexed,
+                &[(0, "B"), (1, "A"), (2, "D")],
+                &[
+                    ("1 B.json", "0 B.json"),
+                    ("0 A.json", "1 A.json"),
+                    ("2 C.json", "2 D.json"),
+                ],
+            ),
+            (Naming::Slug, &[(0, "B"), (1, "A"), (2, "C")], &[]),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let options = SaveOptions {
+                naming,
+                ..Default::default()
+            };
+            save(base.clone(), Some(dir.path()), &options).unwrap();
+            let options = SaveOptions {
+                sync: true,
+                ..options
+            };
+            let json = book(entries);
+            let actions = save(json.clone(), Some(dir.path()), &options).unwrap();
+            let book_dir = dir.path().join(if naming == Naming::Slug {
+                "book"
+            } else {
+                "Book"
+            });
+            let actual = actions
+                .iter()
+                .filter_map(|action| match action {
+                    Action::Renamed { from, to } => Some((
+                        from.strip_prefix(&book_dir).unwrap().to_str().unwrap(),
+                        to.strip_prefix(&book_dir).unwrap().to_str().unwrap(),
+                    )),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(actual, renamed, "{entries:?}");
+
+            // Each file still holds the blueprint it's named after.
+            for file in read_dir_unwrap(&book_dir) {
+                let file = file.to_str().unwrap();
+                let Some(stem) = file.strip_suffix(".json").filter(|_| file != "book.json") else {
+                    continue;
+                };
+                let name = stem.split_once(' ').map_or(stem, |(_index, name)| name);
+                let contents = std::fs::read_to_string(book_dir.join(file)).unwrap();
+                let entry = serde_json::Value::from_str(&contents).unwrap();
+                let label = entry["blueprint"]["label"].as_str().unwrap();
+                assert!(name.eq_ignore_ascii_case(label), "{file} holds {label}");
+            }
+            let loaded_json = crate::load::load(&book_dir).unwrap();
+            assert_eq!(loaded_json, canonicalized(&json), "{entries:?}");
+        }
+    }
+
+    /// Saves a blueprint and loads it back.
+    fn save_and_load(json: serde_json::Value) -> serde_json::Value {
+        let dir = tempfile::tempdir().unwrap();
+        save(json, Some(dir.path()), &SaveOptions::default()).unwrap();
+        let files = read_dir_unwrap(dir.path());
+        let loaded = crate::load::load(&dir.path().join(&files[0])).unwrap();
+        loaded["blueprint"].clone()
+    }
+
+    fn entity_name(blueprint: &serde_json::Value, number: &serde_json::Value) -> String {
+        let entities = blueprint["entities"].as_array().unwrap();
+        let entity = entities
+            .iter()
+            .find(|entity| entity["entity_number"] == *number)
+            .unwrap_or_else(|| panic!("no entity {number}"));
+        entity["name"].as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_save_wired_poles() {
+        // 1.1 wires are listed on the entities, and the poles get renumbered by position.
+        let json = serde_json::json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [
+                    {"entity_number": 1, "name": "big-electric-pole", "position": {"x": 21, "y": 1},
+                        "neighbours": [2], "connections": {"1": {"red": [{"entity_id": 2}]}}},
+                    {"entity_number": 2, "name": "medium-electric-pole", "position": {"x": 0.5, "y": 0.5},
+                        "neighbours": [1], "connections": {"1": {"red": [{"entity_id": 1}]}}},
+                ],
+                "version": 281479275675648u64,
+            }
+        });
+        let blueprint = save_and_load(json);
+        for entity in blueprint["entities"].as_array().unwrap() {
+            let other = match entity["name"].as_str().unwrap() {
+                "big-electric-pole" => "medium-electric-pole",
+                _ => "big-electric-pole",
+            };
+            assert_eq!(entity_name(&blueprint, &entity["neighbours"][0]), other);
+            let entity_id = &entity["connections"]["1"]["red"][0]["entity_id"];
+            assert_eq!(entity_name(&blueprint, entity_id), other);
+        }
+    }
+
+    #[test]
+    fn test_save_coupled_train() {
+        let json = serde_json::json!({
+            "blueprint": {
+                "item": "blueprint",
+                "entities": [
+                    {"entity_number": 1, "name": "locomotive", "position": {"x": 10, "y": 3}, "orientation": 0.75},
+                    {"entity_number": 2, "name": "cargo-wagon", "position": {"x": 3, "y": 3}, "orientation": 0.75},
+                ],
+                "schedules": [{"locomotives": [1], "schedule": {"records": []}}],
+                "stock_connections": [{"stock": 1, "back": 2}, {"stock": 2, "front": 1}],
+                "version": 562949957353472u64,
+            }
+        });
+        let blueprint = save_and_load(json);
+        let name = |number| entity_name(&blueprint, number);
+        assert_eq!(
+            name(&blueprint["schedules"][0]["locomotives"][0]),
+            "locomotive"
+        );
+        for connection in blueprint["stock_connections"].as_array().unwrap() {
+            match &name(&connection["stock"])[..] {
+                "locomotive" => assert_eq!(name(&connection["back"]), "cargo-wagon"),
+                _ => assert_eq!(name(&connection["front"]), "locomotive"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_naming() {
+        let bp = "0eNrlUdtqg0AQ/ZUwz2uIJkYU8pIPCH0vRVadliV7sXsJDeK/d1SiLYS00Mc+ztk5lz3TQSUDtlZoX1bGnKHoFsRB8fxlHN5EbfQEO/GmuRwwzRVCAVWQ50hoh9ajhZ6B0A1+QBH3LwxQe+EFTtRxuJY6qIo2i5jdl2DQGkcsowcXUkrybJ0yuBIly9cpWTTCYj1txAmDVyGJOJnc3Gfx2rQt2shYJOX3wCVlIDjo2ihFCow2VMst94ZCwQGG3MFhOat6G3AAhUc1pJ2LYSB5hVQGHJ9WJ7JbxYRdiDRGS/dJvsvzNNum212WLNVsetb9+wNYPgJ/KP/novf3mNHY93K7EzqPzeo4oZy+dcHyJvAbl/iRy3e9zQO9/hMTWy6D";
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
+        let json = serde_json::Value::from_str(&json).expect("should contain valid json");
+        for (naming, expected_files) in [
+            (Naming::Slug, ["book.json", "bp-name-1.json", "nested-book"]),
+            (
+                Naming::Hash,
+                ["42e569d93936ba7b.json", "book.json", "d55390b8569fde65"],
+            ),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let options = SaveOptions {
+                naming,
+                ..Default::default()
+            };
+            save(json.clone(), Some(dir.path()), &options).unwrap();
+            let files = read_dir_unwrap(dir.path());
+            assert_eq!(files.len(), 1, "{files:?}");
+            let book_dir = dir.path().join(&files[0]);
+            assert_eq!(read_dir_unwrap(&book_dir), expected_files, "{naming:?}");
+
+            let loaded_json = crate::load::load(&book_dir).unwrap();
+            assert_eq!(loaded_json, canonicalized(&json), "{naming:?}");
+        }
+    }
+
+    #[test]
+    fn test_save_sync_hash_named() {
+        // An edited book hashes to a new name, so syncing would save a second copy beside it.
+        let dir = tempfile::tempdir().unwrap();
+        let json = serde_json::json!({
+            "blueprint": {"item": "blueprint", "label": "Lamp", "version": 562949957353472u64}
+        });
+        let options = SaveOptions {
+            naming: Naming::Hash,
+            sync: true,
+            ..Default::default()
+        };
+        let err = save(json, Some(dir.path()), &options).unwrap_err();
+        assert!(matches!(err, Error::InvalidOptions(_)), "{err}");
+        assert_eq!(err.exit_code(), 2);
+        assert!(read_dir_unwrap(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_save_slug_named_book() {
+        // A blueprint labelled "Book" mustn't be saved over the book's own book.json.
+        let json = serde_json::json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "Library",
+                "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "label": "Book",
+                        "version": 562949957353472u64}},
+                ],
+                "version": 562949957353472u64,
+            }
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let options = SaveOptions {
+            naming: Naming::Slug,
+            ..Default::default()
+        };
+        save(json.clone(), Some(dir.path()), &options).unwrap();
+        let book_dir = dir.path().join("library");
+        assert_eq!(read_dir_unwrap(&book_dir), ["book-2.json", "book.json"]);
+        let loaded_json = crate::load::load(&book_dir).unwrap();
+        assert_eq!(loaded_json, canonicalized(&json));
+
+        // Syncing leaves it alone rather than taking it for the book's json.
+        let options = SaveOptions {
+            sync: true,
+            ..options
+        };
+        let actions = save(json, Some(dir.path()), &options).unwrap();
+        assert_eq!(
+            actions,
+            [
+                Action::Unchanged(book_dir.join("book-2.json")),
+                Action::Unchanged(book_dir.join("book.json")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(
+            slug("[icon=rail] Rails & Signals"),
+            "icon-rail-rails-signals"
+        );
+        assert_eq!(
+            slug("Upgrade [entity=fast-splitter]…"),
+            "upgrade-entity-fast-splitter"
+        );
+        assert_eq!(slug("???"), "untitled");
     }
 
     #[test]
     fn test_save_deconstruction_planner() {
         let bp = "0eNptj90KwjAMhd8l1xvofhwr+CQio65Rim0620yU0Xc3ol4MvEu+E87JWcDgGChxnEe2gYbJaSKMoBZIyGzpkt4zElt+DmfrGKOQwwKkPYKC0+yupaWEURQo4DZrJ6eiUIheO0Fj8JOOmoPYwl6AJYMPUJt8LICtwyGhw0++D0Zct1/+J0/+M4LdyibLwuhFXtcpf3UKuIuPEFDtruqbvm+7uq2brsr5Baj6WmY=";
         let dir = tempfile::tempdir().unwrap();
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
-        save(json.clone(), Some(dir.path()));
+        save(json.clone(), Some(dir.path()), &SaveOptions::default()).unwrap();
         let files = read_dir_unwrap(dir.path());
         assert_eq!(files, &["[entity=bulk-inserter] [tile=landfill].json"]);
         let written_json = std::fs::read_to_string(
@@ -253,31 +989,31 @@ mod tests {
         )
         .unwrap();
         let written_json = serde_json::Value::from_str(&written_json).unwrap();
-        assert_eq!(written_json, json);
+        assert_eq!(written_json, canonicalized(&json));
     }
 
     #[test]
     fn test_save_upgrade_planner() {
         let bp = "0eNqtkdFqwzAMRf9FzwmUNmkbQ7+klKGuagjEsisroyX43ycz1sIGWx/6ZOkiXx10Z5hiL3iitzgiMwm4GRKpDtynUnuMkcTK/QxnCb5oeosEDoh10BtUwOhLr4KcYhCtjzSq6ZcJxzLhgIN4HE16Dz6ioAZbBDvIFWj4w/KMSesfvvZn4BNdwS1y9S/UZKPSS7D3xVi/nB9gzRNgKRqE2sFfBnR3fIBs86E0Sr6c4ivp+jvpCj4s2SEwuHa97JquazerdtVsljl/Ag1ntSg=";
         let dir = tempfile::tempdir().unwrap();
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
-        save(json.clone(), Some(dir.path()));
+        save(json.clone(), Some(dir.path()), &SaveOptions::default()).unwrap();
         let files = read_dir_unwrap(dir.path());
         const EXPECTED_NAME: &str = "Upgrade [entity=fast-transport-belt] [entity=fast-underground-belt] [entity=fast-splitter].json";
         assert_eq!(files, [EXPECTED_NAME]);
         let written_json = std::fs::read_to_string(dir.path().join(EXPECTED_NAME)).unwrap();
         let written_json = serde_json::Value::from_str(&written_json).unwrap();
-        assert_eq!(written_json, json);
+        assert_eq!(written_json, canonicalized(&json));
     }
 
     #[test]
     fn test_dedupe_names_upgrade_planner() {
         let bp = "0eNq1UMsKwjAQ/Jc9V5A+LA34JSIS2rUGmt2YbNVS8u8mYK968jaPZXaYFWY3ej3gxU2aCD2oFQKKGBpDxlY7hz7B0wpXzzZrsjgEBUhiZIECSNvMn8wD0q6/YZCk3mc9ZV8Bsbd6SlLP1mmvhdMbOEIsQPhLYBDE6ZOXbg0N+AK1j8XPKsbz/4vU8ZyJYGqyzbjbZizgkWYzTKCaQ9nVXde0VVPVbRnjGw8nfKM=";
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
         assert_eq!(
-            compute_name(BlueprintType::<&serde_json::Value>::new(&json)),
+            compute_name(BlueprintType::<&serde_json::Value>::new(&json).unwrap()),
             "Upgrade [entity=steel-chest]"
         )
     }
@@ -285,10 +1021,10 @@ mod tests {
     #[test]
     fn test_upgrade_remove_module() {
         let bp = "0eNp1js0KwjAQhN9lzy1IfywN+CQiEsxaAtlkTbZiKXl3E7BHbzPfDrOzw8pL1Abv7LT3GEHtkFDE+iVVTZoZY5HXHZ4xUGWyMYICK0jQgNdUXWJE01Iwq8NCX6t2VrZy8CGSdgU9ArGOWkJ5AhfIDUj4W4fEsv3q2uSC1Lz1Bj+gTvlWTc2rY3977G/gXfba4EGN524e5nmc+rEfpi7nL1TiT8I=";
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
         assert_eq!(
-            compute_name(BlueprintType::<&serde_json::Value>::new(&json)),
+            compute_name(BlueprintType::<&serde_json::Value>::new(&json).unwrap()),
             "Upgrade [item=empty-module-slot]"
         )
     }
@@ -296,10 +1032,10 @@ mod tests {
     #[test]
     fn test_upgrade_quality() {
         let bp = "0eNqVT1sKwjAQvMt+V5A+LA14EhHZtmsNdDcx3Yql5O4miAfwbx7M7M4Oq58CjnTzM4pQALPDQqpWpiVjRu8pJHjZ4R4cZ003T2CARK1uUIAgZ95bNzyQ+1RSwHPFObsGxAXGOUmDY48B1aUjcIZYgLr/61ZJPewk562M9AZzjNdMlDj73z2H354CXul/mwKmOZVd3XVNWzVV3ZYxfgDMH1WE";
-        let json = crate::blueprint::blueprint_to_json(bp);
+        let json = crate::blueprint::blueprint_to_json(bp).unwrap();
         let json = serde_json::Value::from_str(&json).expect("should contain valid json");
         assert_eq!(
-            compute_name(BlueprintType::<&serde_json::Value>::new(&json)),
+            compute_name(BlueprintType::<&serde_json::Value>::new(&json).unwrap()),
             "Upgrade [entity=biochamber,quality=uncommon]"
         )
     }
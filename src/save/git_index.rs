@@ -0,0 +1,126 @@
+//! Keeping the git index in step with files `save` renames or removes, like `git mv` and `git rm`.
+
+use std::path::{Path, PathBuf};
+
+use gix::bstr::{BStr, BString, ByteSlice};
+
+use crate::error::{Error, Result};
+
+pub(super) struct GitIndex {
+    workdir: PathBuf,
+    index: gix::index::File,
+    changed: bool,
+}
+
+impl GitIndex {
+    /// Opens the index of the repository containing `dir`, if there is one.
+    pub(super) fn open(dir: &Path) -> Result<Option<GitIndex>> {
+        let Ok(repo) = gix::discover(dir) else {
+            return Ok(None);
+        };
+        let Some(workdir) = repo.workdir() else {
+            return Ok(None);
+        };
+        let workdir = workdir.canonicalize().map_err(Error::io(workdir))?;
+        let index = match repo.open_index() {
+            Ok(index) => index,
+            // A fresh repository without an index yet has nothing to update.
+            Err(_) if !repo.index_path().exists() => return Ok(None),
+            Err(e) => return Err(Error::Git(format!("couldn't open index: {e}"))),
+        };
+        Ok(Some(GitIndex {
+            workdir,
+            index,
+            changed: false,
+        }))
+    }
+
+    /// `path` relative to the repository, with `/` separators.
+    fn relative(&self, path: &Path) -> Result<BString> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let parent = parent.canonicalize().map_err(Error::io(parent))?;
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::Git(format!("{path:?} has no file name")))?;
+        let relative = parent
+            .join(file_name)
+            .strip_prefix(&self.workdir)
+            .map_err(|_| Error::Git(format!("{path:?} is outside the repository")))?
+            .to_owned();
+        let components = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        Ok(components.join("/").into())
+    }
+
+    fn is_at_or_under(path: &BStr, prefix: &BStr) -> bool {
+        path == prefix || (path.starts_with(prefix) && path.get(prefix.len()) == Some(&b'/'))
+    }
+
+    /// Moves the entries for a file, or everything under a directory, from `from` to `to`.
+    pub(super) fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (self.relative(from)?, self.relative(to)?);
+        let moved = self
+            .index
+            .entries()
+            .iter()
+            .filter_map(|entry| {
+                let path = entry.path(&self.index);
+                Self::is_at_or_under(path, from.as_ref()).then(|| {
+                    let mut new_path = to.clone();
+                    new_path.extend_from_slice(&path[from.len()..]);
+                    (entry.clone(), new_path)
+                })
+            })
+            .collect::<Vec<_>>();
+        if moved.is_empty() {
+            return Ok(());
+        }
+        self.index
+            .remove_entries(|_, path, _| Self::is_at_or_under(path, from.as_ref()));
+        for (entry, path) in moved {
+            self.index.dangerously_push_entry(
+                entry.stat,
+                entry.id,
+                entry.flags,
+                entry.mode,
+                path.as_ref(),
+            );
+        }
+        self.index.sort_entries();
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Removes the entry for a file.
+    pub(super) fn remove(&mut self, path: &Path) -> Result<()> {
+        let path = self.relative(path)?;
+        let before = self.index.entries().len();
+        self.index
+            .remove_entries(|_, entry_path, _| entry_path == path.as_bstr());
+        self.changed |= self.index.entries().len() != before;
+        Ok(())
+    }
+
+    /// Writes the index back if anything changed.
+    pub(super) fn write(mut self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let options = gix::index::write::Options {
+            // The cached trees are out of date now; git rebuilds them as needed.
+            extensions: gix::index::write::Extensions::Given {
+                tree_cache: false,
+                end_of_index_entry: true,
+            },
+            ..Default::default()
+        };
+        self.index
+            .write(options)
+            .map_err(|e| Error::Git(format!("couldn't write index: {e}")))
+    }
+}
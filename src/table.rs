@@ -0,0 +1,86 @@
+//! Writing rows of strings as csv, tsv or markdown tables, for spreadsheets and wiki pages.
+
+use std::fmt::Write;
+
+/// A table layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    /// Comma-separated values, quoted where needed (RFC 4180).
+    Csv,
+    /// A GitHub-flavored markdown table.
+    Markdown,
+    /// Tab-separated values. Tabs and newlines in values become spaces.
+    Tsv,
+}
+
+/// Formats `rows` under `header`, one line per row.
+pub fn format_table(format: TableFormat, header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    let header = header.iter().map(|column| column.to_string()).collect();
+    for (i, row) in std::iter::once(&header).chain(rows).enumerate() {
+        let line = match format {
+            TableFormat::Csv => row
+                .iter()
+                .map(|value| csv_value(value))
+                .collect::<Vec<_>>()
+                .join(","),
+            TableFormat::Tsv => row
+                .iter()
+                .map(|value| value.replace(['\t', '\n', '\r'], " "))
+                .collect::<Vec<_>>()
+                .join("\t"),
+            TableFormat::Markdown => {
+                let cells = row
+                    .iter()
+                    .map(|value| value.replace('|', "\\|").replace(['\n', '\r'], " "))
+                    .collect::<Vec<_>>();
+                format!("| {} |", cells.join(" | "))
+            }
+        };
+        writeln!(out, "{line}").expect("writing to a String can't fail");
+        if i == 0 && format == TableFormat::Markdown {
+            let rule = vec!["---"; row.len()].join(" | ");
+            writeln!(out, "| {rule} |").expect("writing to a String can't fail");
+        }
+    }
+    out
+}
+
+fn csv_value(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        let rows = [
+            vec!["small-lamp".to_owned(), "normal".to_owned(), "2".to_owned()],
+            vec!["a, \"b\"|c".to_owned(), "rare".to_owned(), "1".to_owned()],
+        ];
+        let header = ["name", "quality", "count"];
+        assert_eq!(
+            format_table(TableFormat::Csv, &header, &rows),
+            "name,quality,count\nsmall-lamp,normal,2\n\"a, \"\"b\"\"|c\",rare,1\n"
+        );
+        assert_eq!(
+            format_table(TableFormat::Tsv, &header, &rows),
+            "name\tquality\tcount\nsmall-lamp\tnormal\t2\na, \"b\"|c\trare\t1\n"
+        );
+        assert_eq!(
+            format_table(TableFormat::Markdown, &header, &rows),
+            "\
+| name | quality | count |
+| --- | --- | --- |
+| small-lamp | normal | 2 |
+| a, \"b\"\\|c | rare | 1 |
+"
+        );
+    }
+}
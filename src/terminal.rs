@@ -1,30 +1,87 @@
-use std::io::{IsTerminal, Read, stderr, stdin};
+use std::io::{self, IsTerminal, Read, stdin};
 
-use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste, KeyCode, KeyModifiers};
+use factorio_bp_helper::{Error, Result};
 
-pub fn prompt_blueprint() -> String {
+pub fn read_stdin() -> Result<String> {
+    let mut buf = String::new();
+    stdin().read_to_string(&mut buf).map_err(Error::Terminal)?;
+    Ok(buf)
+}
+
+#[cfg(feature = "clipboard")]
+pub fn copy_to_clipboard(s: &str) -> Result<()> {
+    use crossterm::clipboard::CopyToClipboard;
+    crossterm::execute!(std::io::stderr(), CopyToClipboard::to_clipboard_from(s))
+        .map_err(Error::Terminal)
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy_to_clipboard(_s: &str) -> Result<()> {
+    Err(Error::Terminal(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "built without clipboard support",
+    )))
+}
+
+/// Asks `question` until the answer starts with one of `choices`, and returns that choice.
+pub fn ask(question: &str, choices: &[char]) -> Result<char> {
+    if !stdin().is_terminal() {
+        return Err(Error::Terminal(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "can't ask questions unless stdin is a terminal",
+        )));
+    }
+    loop {
+        eprint!("{question} ");
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer).map_err(Error::Terminal)? == 0 {
+            return Err(Error::Cancelled);
+        }
+        let choice = answer.trim_start().chars().next();
+        if let Some(choice) = choice.map(|choice| choice.to_ascii_lowercase())
+            && choices.contains(&choice)
+        {
+            return Ok(choice);
+        }
+    }
+}
+
+pub fn prompt_blueprint() -> Result<String> {
     if !stdin().is_terminal() {
-        let mut buf = String::new();
-        stdin()
-            .read_to_string(&mut buf)
-            .expect("error reading from stdin");
-        return buf;
+        return read_stdin();
     }
-    crossterm::execute!(stderr(), EnableBracketedPaste).unwrap();
-    crossterm::terminal::enable_raw_mode().unwrap();
+    prompt_paste()
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn prompt_paste() -> Result<String> {
+    eprintln!("Paste blueprint, then press Ctrl-D:");
+    read_stdin()
+}
+
+#[cfg(feature = "clipboard")]
+fn prompt_paste() -> Result<String> {
+    use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste, KeyCode, KeyModifiers};
+    use std::io::stderr;
+
+    crossterm::execute!(stderr(), EnableBracketedPaste).map_err(Error::Terminal)?;
+    crossterm::terminal::enable_raw_mode().map_err(Error::Terminal)?;
     eprint!("Paste blueprint:\r\n");
     const PROMPT: &str = "🟦❯ ";
     eprint!("{PROMPT}");
     let mut warned = false;
     let blueprint_string = loop {
-        let event = crossterm::event::read().unwrap();
+        let event = match crossterm::event::read() {
+            Ok(event) => event,
+            Err(e) => break Err(e),
+        };
         match event {
             crossterm::event::Event::Key(key_event) => {
                 if key_event.code == KeyCode::Char('c')
                     && key_event.modifiers == KeyModifiers::CONTROL
                 {
                     eprint!("\r\n");
-                    break None;
+                    break Ok(None);
                 }
                 if !warned {
                     warned = true;
@@ -34,12 +91,14 @@ pub fn prompt_blueprint() -> String {
             }
             crossterm::event::Event::Paste(s) => {
                 eprint!("received.\r\n");
-                break Some(s);
+                break Ok(Some(s));
             }
             _ => {}
         }
     };
-    crossterm::execute!(stderr(), DisableBracketedPaste).unwrap();
-    crossterm::terminal::disable_raw_mode().unwrap();
-    blueprint_string.unwrap_or_else(|| std::process::exit(1))
+    crossterm::execute!(stderr(), DisableBracketedPaste).map_err(Error::Terminal)?;
+    crossterm::terminal::disable_raw_mode().map_err(Error::Terminal)?;
+    blueprint_string
+        .map_err(Error::Terminal)?
+        .ok_or(Error::Cancelled)
 }
@@ -0,0 +1,221 @@
+//! A stable, line-per-thing listing of a blueprint, meant for `git diff` as a git textconv filter.
+//!
+//! Every entity, tile and wire gets its own line naming what it is and where it is, so that
+//! changes show up as a few added and removed lines regardless of `entity_number`.
+
+use std::{collections::HashMap, fmt::Write};
+
+use serde_json::Value;
+
+use crate::{
+    blueprint::{BlueprintType, Version},
+    canonical,
+    diff::{EntityRef, Position, WireEnd},
+    error::Result,
+};
+
+/// Fields listed specially rather than as json.
+const SPECIAL: &[&str] = &[
+    "blueprints",
+    "description",
+    "entities",
+    "item",
+    "label",
+    "tiles",
+    "version",
+    "wires",
+];
+
+/// Lists a blueprint, book or planner; book entries are indented under their book.
+pub fn textconv(json: &Value) -> Result<String> {
+    let mut json = json.clone();
+    canonical::canonicalize(&mut json)?;
+    let mut out = String::new();
+    list_entry(&json, 0, &mut out)?;
+    Ok(out)
+}
+
+fn list_entry(json: &Value, depth: usize, out: &mut String) -> Result<()> {
+    let bp = BlueprintType::<&Value>::new(json)?;
+    let indent = "  ".repeat(depth);
+    let mut line = |line: &dyn std::fmt::Display| {
+        writeln!(out, "{indent}{line}").expect("writing to a String can't fail");
+    };
+    let index = json
+        .get("index")
+        .and_then(Value::as_u64)
+        .map(|index| format!("[{index}] "))
+        .unwrap_or_default();
+    line(&format_args!(
+        "{index}{kind}: {label}",
+        kind = bp.kind_name(),
+        label = bp.label().unwrap_or("(no label)")
+    ));
+
+    let any = bp.any();
+    if let Some(version) = any.get("version").and_then(Value::as_u64) {
+        line(&format_args!(
+            "  version: {}",
+            Version::from_packed(version)
+        ));
+    }
+    let description = any
+        .get("description")
+        .or_else(|| any.get("settings")?.get("description"));
+    if let Some(description) = description.and_then(Value::as_str) {
+        for description_line in description.lines() {
+            line(&format_args!("  description: {description_line}"));
+        }
+    }
+    for (key, value) in any.as_object().into_iter().flatten() {
+        if SPECIAL.contains(&key.as_str()) {
+            continue;
+        }
+        if let (Value::Object(settings), "settings") = (value, key.as_str()) {
+            for (key, value) in settings {
+                if key != "description" {
+                    list_field(&format!("settings.{key}"), value, &mut line);
+                }
+            }
+        } else {
+            list_field(key, value, &mut line);
+        }
+    }
+
+    match bp {
+        BlueprintType::Blueprint(blueprint) => {
+            let mut entities = HashMap::new();
+            for entity in array(blueprint, "entities") {
+                let (entity_ref, settings) = placed(entity);
+                match settings {
+                    Some(settings) => line(&format_args!("  entity {entity_ref} {settings}")),
+                    None => line(&format_args!("  entity {entity_ref}")),
+                }
+                if let Some(number) = entity.get("entity_number").and_then(Value::as_u64) {
+                    entities.insert(number, entity_ref);
+                }
+            }
+            for tile in array(blueprint, "tiles") {
+                let (tile_ref, settings) = placed(tile);
+                match settings {
+                    Some(settings) => line(&format_args!("  tile {tile_ref} {settings}")),
+                    None => line(&format_args!("  tile {tile_ref}")),
+                }
+            }
+            for wire in array(blueprint, "wires") {
+                let end = |i: usize| -> Option<WireEnd> {
+                    Some(WireEnd {
+                        entity: entities.get(&wire.get(i)?.as_u64()?)?.clone(),
+                        connector: u8::try_from(wire.get(i + 1)?.as_u64()?).ok()?,
+                    })
+                };
+                match (end(0), end(2)) {
+                    (Some(from), Some(to)) => line(&format_args!("  wire {from} to {to}")),
+                    _ => line(&format_args!("  wire {wire}")),
+                }
+            }
+        }
+        BlueprintType::BlueprintBook(book) => {
+            for entry in array(book, "blueprints") {
+                list_entry(entry, depth + 1, out)?;
+            }
+        }
+        BlueprintType::UpgradePlanner(_) | BlueprintType::DeconstructionPlanner(_) => {}
+    }
+    Ok(())
+}
+
+/// Lists a field as compact json, with arrays split into one line per element.
+fn list_field(key: &str, value: &Value, line: &mut impl FnMut(&dyn std::fmt::Display)) {
+    match value {
+        Value::Array(values) => {
+            for value in values {
+                line(&format_args!("  {key}[]: {value}"));
+            }
+        }
+        _ => line(&format_args!("  {key}: {value}")),
+    }
+}
+
+fn array<'a>(json: &'a Value, key: &str) -> &'a [Value] {
+    json.get(key)
+        .and_then(Value::as_array)
+        .map(|values| &values[..])
+        .unwrap_or_default()
+}
+
+/// An entity or tile's name and position, plus its other settings as compact json, if any.
+fn placed(json: &Value) -> (EntityRef, Option<String>) {
+    let coordinate = |key| {
+        json.get("position")
+            .and_then(|position| position.get(key))
+            .and_then(Value::as_f64)
+            .unwrap_or_default()
+    };
+    let entity_ref = EntityRef {
+        name: json
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned(),
+        position: Position {
+            x: coordinate("x"),
+            y: coordinate("y"),
+        },
+    };
+    let mut settings = json.as_object().cloned().unwrap_or_default();
+    for key in ["entity_number", "name", "position"] {
+        settings.remove(key);
+    }
+    let settings = (!settings.is_empty()).then(|| Value::Object(settings).to_string());
+    (entity_ref, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_textconv() {
+        let json = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "Lamps",
+                "blueprints": [{
+                    "index": 2,
+                    "blueprint": {
+                        "item": "blueprint",
+                        "description": "two lamps\nwired together",
+                        "icons": [{"index": 1, "signal": {"name": "small-lamp"}}],
+                        "entities": [
+                            {"entity_number": 1, "name": "small-lamp", "position": {"x": 2.5, "y": 0.5}},
+                            {"entity_number": 2, "name": "small-lamp", "position": {"x": 0.5, "y": 0.5},
+                                "color": {"r": 1, "g": 0, "b": 0}},
+                        ],
+                        "tiles": [{"name": "stone-path", "position": {"x": 0, "y": 0}}],
+                        "wires": [[1, 1, 2, 1]],
+                        "version": 562949957353472u64,
+                    }
+                }],
+                "version": 562949957353472u64,
+            }
+        });
+        assert_eq!(
+            textconv(&json).unwrap(),
+            r#"blueprint book: Lamps
+  version: 2.0.60.0
+  [2] blueprint: (no label)
+    version: 2.0.60.0
+    description: two lamps
+    description: wired together
+    icons[]: {"index":1,"signal":{"name":"small-lamp"}}
+    entity small-lamp at (0.5, 0.5) {"color":{"b":0,"g":0,"r":1}}
+    entity small-lamp at (2.5, 0.5)
+    tile stone-path at (0, 0)
+    wire small-lamp at (0.5, 0.5) red to small-lamp at (2.5, 0.5) red
+"#
+        );
+    }
+}